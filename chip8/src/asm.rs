@@ -0,0 +1,668 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use thiserror::Error;
+
+use crate::constants::PROGRAM_START;
+
+/// Глубже этого `include` считается зацикленным
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+/// Ошибка сборки; строка указывает на исходник после подстановки `include`
+#[derive(Error, Debug)]
+pub enum AsmError {
+    #[error("{file}:{line}: {message}")]
+    Syntax { file: String, line: usize, message: String },
+
+    #[error("Failed to read {path}: {source}")]
+    Io { path: String, source: io::Error },
+}
+
+/// Результат сборки: образ ROM с адреса 0x200 и таблица меток
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Assembly {
+    pub bytes: Vec<u8>,
+    pub labels: BTreeMap<String, u16>,
+}
+
+impl Assembly {
+    /// Файл символов: `АДРЕС имя` по строке на метку, по возрастанию адреса
+    pub fn symbol_file(&self) -> String {
+        let mut symbols: Vec<(&u16, &String)> = self.labels.iter().map(|(name, addr)| (addr, name)).collect();
+        symbols.sort();
+        symbols
+            .into_iter()
+            .map(|(addr, name)| format!("{:04X} {}\n", addr, name))
+            .collect()
+    }
+}
+
+/// Собрать исходник; `include` ищутся относительно текущего каталога
+pub fn assemble(source: &str) -> Result<Assembly, AsmError> {
+    let mut lines = Vec::new();
+    read_source(source, "<input>", Path::new("."), 0, &mut lines)?;
+    Assembler::default().run(&lines)
+}
+
+/// Собрать файл; `include` ищутся относительно каталога файла
+pub fn assemble_file(path: impl AsRef<Path>) -> Result<Assembly, AsmError> {
+    let mut lines = Vec::new();
+    include_file(path.as_ref(), 0, &mut lines)?;
+    Assembler::default().run(&lines)
+}
+
+/// Строка исходника с местом, откуда она взята
+#[derive(Debug, Clone)]
+struct SourceLine {
+    file: String,
+    line: usize,
+    text: String,
+}
+
+impl SourceLine {
+    fn error(&self, message: impl Into<String>) -> AsmError {
+        AsmError::Syntax { file: self.file.clone(), line: self.line, message: message.into() }
+    }
+}
+
+fn include_file(path: &Path, depth: usize, out: &mut Vec<SourceLine>) -> Result<(), AsmError> {
+    let source = fs::read_to_string(path).map_err(|source| AsmError::Io {
+        path: path.display().to_string(),
+        source,
+    })?;
+    let dir = path.parent().map_or_else(|| PathBuf::from("."), Path::to_path_buf);
+    read_source(&source, &path.display().to_string(), &dir, depth, out)
+}
+
+/// Разложить исходник на строки, подставляя `include "файл"`
+fn read_source(
+    source: &str,
+    file: &str,
+    dir: &Path,
+    depth: usize,
+    out: &mut Vec<SourceLine>,
+) -> Result<(), AsmError> {
+    for (i, text) in source.lines().enumerate() {
+        let line = SourceLine { file: file.to_string(), line: i + 1, text: strip_comment(text).to_string() };
+        let trimmed = line.text.trim();
+        let is_include = trimmed
+            .split_whitespace()
+            .next()
+            .is_some_and(|word| word.eq_ignore_ascii_case("include"));
+        if !is_include {
+            out.push(line);
+            continue;
+        }
+
+        if depth >= MAX_INCLUDE_DEPTH {
+            return Err(line.error("include nested too deeply (recursive include?)"));
+        }
+        let name = trimmed["include".len()..].trim();
+        let name = parse_string(name).ok_or_else(|| line.error("include expects a quoted file name"))?;
+        include_file(&dir.join(name), depth + 1, out)?;
+    }
+    Ok(())
+}
+
+/// Отрезать комментарий `;`, не трогая `;` внутри кавычек
+fn strip_comment(text: &str) -> &str {
+    let mut quoted = false;
+    for (i, c) in text.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            ';' if !quoted => return &text[..i],
+            _ => {}
+        }
+    }
+    text
+}
+
+fn parse_string(s: &str) -> Option<String> {
+    let inner = s.strip_prefix('"')?.strip_suffix('"')?;
+    (!inner.contains('"')).then(|| inner.to_string())
+}
+
+/// Разбить операнды по запятым вне кавычек
+fn split_operands(s: &str) -> Vec<String> {
+    let mut operands = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    for c in s.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                current.push(c);
+            }
+            ',' if !quoted => operands.push(std::mem::take(&mut current).trim().to_string()),
+            _ => current.push(c),
+        }
+    }
+    if !current.trim().is_empty() || !operands.is_empty() {
+        operands.push(current.trim().to_string());
+    }
+    operands
+}
+
+fn is_identifier(s: &str) -> bool {
+    let mut chars = s.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || c == '.')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+}
+
+/// Инструкция или директива данных
+struct Statement<'a> {
+    source: &'a SourceLine,
+    mnemonic: String,
+    operands: Vec<String>,
+}
+
+#[derive(Default)]
+struct Assembler {
+    labels: BTreeMap<String, u16>,
+    constants: BTreeMap<String, String>,
+}
+
+impl Assembler {
+    fn run(mut self, lines: &[SourceLine]) -> Result<Assembly, AsmError> {
+        // Проход 1: адреса меток и тексты констант
+        let mut statements = Vec::new();
+        let mut address = PROGRAM_START as u32;
+        for source in lines {
+            let mut text = source.text.trim();
+
+            if let Some((name, rest)) = text.split_once(':')
+                && is_identifier(name.trim())
+            {
+                let name = name.trim();
+                if address > u16::MAX as u32 {
+                    return Err(source.error(format!("label '{}' is outside of the address space", name)));
+                }
+                self.define_label(source, name, address as u16)?;
+                text = rest.trim();
+            }
+            if text.is_empty() {
+                continue;
+            }
+
+            // NAME = expr
+            if let Some((name, value)) = text.split_once('=')
+                && is_identifier(name.trim())
+            {
+                self.define_constant(source, name.trim(), value.trim())?;
+                continue;
+            }
+
+            let (mnemonic, rest) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+            let rest = rest.trim();
+
+            // NAME equ expr
+            if let Some((word, value)) = rest.split_once(char::is_whitespace)
+                && word.eq_ignore_ascii_case("equ")
+                && is_identifier(mnemonic)
+            {
+                self.define_constant(source, mnemonic, value.trim())?;
+                continue;
+            }
+
+            let statement = Statement {
+                source,
+                mnemonic: mnemonic.to_ascii_uppercase(),
+                operands: split_operands(rest),
+            };
+            address += self.size(&statement)? as u32;
+            statements.push(statement);
+        }
+
+        // Проход 2: генерация кода, все метки уже известны
+        let mut bytes = Vec::new();
+        for statement in &statements {
+            self.emit(statement, &mut bytes)
+                .map_err(|message| statement.source.error(message))?;
+        }
+        Ok(Assembly { bytes, labels: self.labels })
+    }
+
+    fn define_label(&mut self, source: &SourceLine, name: &str, address: u16) -> Result<(), AsmError> {
+        if self.labels.contains_key(name) || self.constants.contains_key(name) {
+            return Err(source.error(format!("'{}' is already defined", name)));
+        }
+        self.labels.insert(name.to_string(), address);
+        Ok(())
+    }
+
+    fn define_constant(&mut self, source: &SourceLine, name: &str, value: &str) -> Result<(), AsmError> {
+        if self.labels.contains_key(name) || self.constants.contains_key(name) {
+            return Err(source.error(format!("'{}' is already defined", name)));
+        }
+        self.constants.insert(name.to_string(), value.to_string());
+        Ok(())
+    }
+
+    /// Размер оператора в байтах; от значений меток не зависит
+    fn size(&self, statement: &Statement) -> Result<usize, AsmError> {
+        let size = match statement.mnemonic.as_str() {
+            "DB" => {
+                let mut size = 0;
+                for operand in &statement.operands {
+                    size += match parse_string(operand) {
+                        Some(text) => text.len(),
+                        None => 1,
+                    };
+                }
+                size
+            }
+            "DW" => statement.operands.len() * 2,
+            "SPRITE" => {
+                let row = statement.operands.first().map_or("", String::as_str);
+                match row.len() {
+                    8 => 1,
+                    16 => 2,
+                    _ => {
+                        return Err(statement.source.error("sprite row must be 8 or 16 pixels wide"));
+                    }
+                }
+            }
+            "LD" if statement
+                .operands
+                .get(1)
+                .is_some_and(|op| op.to_ascii_uppercase().starts_with("LONG ")) =>
+            {
+                4
+            }
+            _ => 2,
+        };
+        Ok(size)
+    }
+
+    /// Значение выражения: числа и имена через `+`/`-`
+    fn eval(&self, expr: &str, depth: usize) -> Result<i64, String> {
+        if depth > MAX_INCLUDE_DEPTH {
+            return Err(format!("constant '{}' refers to itself", expr));
+        }
+        let expr = expr.trim();
+        if expr.is_empty() {
+            return Err("missing value".to_string());
+        }
+
+        let mut total = 0i64;
+        let mut sign = 1i64;
+        let mut term = String::new();
+        let mut terms = Vec::new();
+        for c in expr.chars() {
+            match c {
+                '+' | '-' => {
+                    if !term.trim().is_empty() {
+                        terms.push((sign, std::mem::take(&mut term)));
+                        sign = 1;
+                    }
+                    if c == '-' {
+                        sign = -sign;
+                    }
+                }
+                _ => term.push(c),
+            }
+        }
+        if term.trim().is_empty() {
+            return Err(format!("incomplete expression '{}'", expr));
+        }
+        terms.push((sign, term));
+
+        for (sign, term) in terms {
+            total += sign * self.term(term.trim(), depth)?;
+        }
+        Ok(total)
+    }
+
+    fn term(&self, term: &str, depth: usize) -> Result<i64, String> {
+        let lower = term.to_ascii_lowercase();
+        let number = if let Some(hex) = lower.strip_prefix("0x").or_else(|| lower.strip_prefix('#')) {
+            i64::from_str_radix(hex, 16).ok()
+        } else if let Some(bin) = lower.strip_prefix("0b") {
+            i64::from_str_radix(bin, 2).ok()
+        } else if lower.starts_with(|c: char| c.is_ascii_digit()) {
+            lower.parse().ok()
+        } else {
+            None
+        };
+        if let Some(number) = number {
+            return Ok(number);
+        }
+
+        if let Some(&addr) = self.labels.get(term) {
+            return Ok(addr as i64);
+        }
+        if let Some(value) = self.constants.get(term) {
+            return self.eval(value, depth + 1);
+        }
+        if is_identifier(term) {
+            Err(format!("unknown symbol '{}'", term))
+        } else {
+            Err(format!("invalid number '{}'", term))
+        }
+    }
+
+    /// Значение в диапазоне `0..=max`
+    fn value(&self, expr: &str, max: u16, what: &str) -> Result<u16, String> {
+        let value = self.eval(expr, 0)?;
+        if !(0..=max as i64).contains(&value) {
+            return Err(format!("{} {} out of range (0-{:#X})", what, value, max));
+        }
+        Ok(value as u16)
+    }
+
+    fn emit(&self, statement: &Statement, out: &mut Vec<u8>) -> Result<(), String> {
+        let ops = &statement.operands;
+        match statement.mnemonic.as_str() {
+            "DB" => {
+                for operand in ops {
+                    match parse_string(operand) {
+                        Some(text) => out.extend_from_slice(text.as_bytes()),
+                        None => out.push(self.byte(operand)?),
+                    }
+                }
+                Ok(())
+            }
+            "DW" => {
+                for operand in ops {
+                    out.extend_from_slice(&self.value(operand, 0xFFFF, "word")?.to_be_bytes());
+                }
+                Ok(())
+            }
+            "SPRITE" => {
+                let mut row = 0u16;
+                for c in ops[0].chars() {
+                    row = (row << 1)
+                        | match c {
+                            '#' | 'X' | 'x' | '1' => 1,
+                            '.' | '_' | '0' => 0,
+                            _ => return Err(format!("invalid sprite pixel '{}', use '#' or '.'", c)),
+                        };
+                }
+                if ops[0].len() == 16 {
+                    out.extend_from_slice(&row.to_be_bytes());
+                } else {
+                    out.push(row as u8);
+                }
+                Ok(())
+            }
+            _ => {
+                let words = self.encode(statement)?;
+                for word in words {
+                    out.extend_from_slice(&word.to_be_bytes());
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Байт: 0..=255 или отрицательное число до -128 (дополнительный код)
+    fn byte(&self, expr: &str) -> Result<u8, String> {
+        let value = self.eval(expr, 0)?;
+        if !(-128..=255).contains(&value) {
+            return Err(format!("byte {} out of range", value));
+        }
+        Ok(value as u8)
+    }
+
+    fn encode(&self, statement: &Statement) -> Result<Vec<u16>, String> {
+        let ops: Vec<Operand> = statement.operands.iter().map(|op| Operand::parse(op)).collect();
+        let addr = |expr: &str| self.value(expr, 0xFFF, "address");
+        let nibble = |expr: &str| self.value(expr, 0xF, "value");
+        let byte = |expr: &str| self.byte(expr).map(u16::from);
+        let xy = |opcode: u16, x: u8, y: u8| opcode | (x as u16) << 8 | (y as u16) << 4;
+
+        use Operand::*;
+        let opcode = match (statement.mnemonic.as_str(), ops.as_slice()) {
+            ("CLS", []) => 0x00E0,
+            ("RET", []) => 0x00EE,
+            ("SCD", [Expr(n)]) => 0x00C0 | nibble(n)?,
+            ("SCU", [Expr(n)]) => 0x00D0 | nibble(n)?,
+            ("SCR", []) => 0x00FB,
+            ("SCL", []) => 0x00FC,
+            ("EXIT", []) => 0x00FD,
+            ("LOW", []) => 0x00FE,
+            ("HIGH", []) => 0x00FF,
+            ("SYS", [Expr(a)]) => addr(a)?,
+            ("JP", [Expr(a)]) => 0x1000 | addr(a)?,
+            ("JP", [V(0), Expr(a)]) => 0xB000 | addr(a)?,
+            ("CALL", [Expr(a)]) => 0x2000 | addr(a)?,
+            ("SE", [V(x), V(y)]) => xy(0x5000, *x, *y),
+            ("SE", [V(x), Expr(k)]) => xy(0x3000, *x, 0) | byte(k)?,
+            ("SNE", [V(x), V(y)]) => xy(0x9000, *x, *y),
+            ("SNE", [V(x), Expr(k)]) => xy(0x4000, *x, 0) | byte(k)?,
+            ("SAVE", [V(x), V(y)]) => xy(0x5002, *x, *y),
+            ("LOAD", [V(x), V(y)]) => xy(0x5003, *x, *y),
+            ("LD", [V(x), V(y)]) => xy(0x8000, *x, *y),
+            ("LD", [V(x), Dt]) => xy(0xF007, *x, 0),
+            ("LD", [V(x), K]) => xy(0xF00A, *x, 0),
+            ("LD", [V(x), IndirectI]) => xy(0xF065, *x, 0),
+            ("LD", [V(x), R]) => xy(0xF085, *x, 0),
+            ("LD", [V(x), Expr(k)]) => xy(0x6000, *x, 0) | byte(k)?,
+            ("LD", [I, Long(a)]) => return Ok(vec![0xF000, self.value(a, 0xFFFF, "address")?]),
+            ("LD", [I, Expr(a)]) => 0xA000 | addr(a)?,
+            ("LD", [Dt, V(x)]) => xy(0xF015, *x, 0),
+            ("LD", [St, V(x)]) => xy(0xF018, *x, 0),
+            ("LD", [F, V(x)]) => xy(0xF029, *x, 0),
+            ("LD", [Hf, V(x)]) => xy(0xF030, *x, 0),
+            ("LD", [B, V(x)]) => xy(0xF033, *x, 0),
+            ("LD", [IndirectI, V(x)]) => xy(0xF055, *x, 0),
+            ("LD", [R, V(x)]) => xy(0xF075, *x, 0),
+            ("ADD", [V(x), V(y)]) => xy(0x8004, *x, *y),
+            ("ADD", [V(x), Expr(k)]) => xy(0x7000, *x, 0) | byte(k)?,
+            ("ADD", [I, V(x)]) => xy(0xF01E, *x, 0),
+            ("OR", [V(x), V(y)]) => xy(0x8001, *x, *y),
+            ("AND", [V(x), V(y)]) => xy(0x8002, *x, *y),
+            ("XOR", [V(x), V(y)]) => xy(0x8003, *x, *y),
+            ("SUB", [V(x), V(y)]) => xy(0x8005, *x, *y),
+            ("SHR", [V(x), V(y)]) => xy(0x8006, *x, *y),
+            ("SHR", [V(x)]) => xy(0x8006, *x, 0),
+            ("SUBN", [V(x), V(y)]) => xy(0x8007, *x, *y),
+            ("SHL", [V(x), V(y)]) => xy(0x800E, *x, *y),
+            ("SHL", [V(x)]) => xy(0x800E, *x, 0),
+            ("RND", [V(x), Expr(k)]) => xy(0xC000, *x, 0) | byte(k)?,
+            ("DRW", [V(x), V(y), Expr(n)]) => xy(0xD000, *x, *y) | nibble(n)?,
+            ("SKP", [V(x)]) => xy(0xE09E, *x, 0),
+            ("SKNP", [V(x)]) => xy(0xE0A1, *x, 0),
+            ("PLANE", [Expr(n)]) => 0xF001 | nibble(n)? << 8,
+            ("AUDIO", []) => 0xF002,
+            ("PITCH", [V(x)]) => xy(0xF03A, *x, 0),
+            (mnemonic, _) if is_mnemonic(mnemonic) => {
+                return Err(format!(
+                    "invalid operands for {}: '{}'",
+                    mnemonic,
+                    statement.operands.join(", ")
+                ));
+            }
+            (mnemonic, _) => return Err(format!("unknown instruction '{}'", mnemonic)),
+        };
+        Ok(vec![opcode])
+    }
+}
+
+fn is_mnemonic(name: &str) -> bool {
+    const MNEMONICS: [&str; 33] = [
+        "CLS", "RET", "SCD", "SCU", "SCR", "SCL", "EXIT", "LOW", "HIGH", "SYS", "JP", "CALL",
+        "SE", "SNE", "SAVE", "LOAD", "LD", "ADD", "OR", "AND", "XOR", "SUB", "SHR", "SUBN",
+        "SHL", "RND", "DRW", "SKP", "SKNP", "PLANE", "AUDIO", "PITCH", "SPRITE",
+    ];
+    MNEMONICS.contains(&name)
+}
+
+/// Операнд инструкции
+enum Operand {
+    V(u8),
+    I,
+    IndirectI,
+    Dt,
+    St,
+    K,
+    F,
+    Hf,
+    B,
+    R,
+    /// `LONG адрес` (XO-CHIP)
+    Long(String),
+    /// Число, метка или выражение
+    Expr(String),
+}
+
+impl Operand {
+    fn parse(s: &str) -> Operand {
+        let upper = s.to_ascii_uppercase();
+        if let Some(long) = upper.strip_prefix("LONG ") {
+            // Имя метки берём из исходной строки, с учётом регистра
+            return Operand::Long(s[s.len() - long.len()..].trim().to_string());
+        }
+        match upper.as_str() {
+            "I" => Operand::I,
+            "[I]" => Operand::IndirectI,
+            "DT" => Operand::Dt,
+            "ST" => Operand::St,
+            "K" => Operand::K,
+            "F" => Operand::F,
+            "HF" => Operand::Hf,
+            "B" => Operand::B,
+            "R" => Operand::R,
+            _ => match upper.strip_prefix('V') {
+                Some(n) if n.len() == 1 => match u8::from_str_radix(n, 16) {
+                    Ok(n) => Operand::V(n),
+                    Err(_) => Operand::Expr(s.to_string()),
+                },
+                _ => Operand::Expr(s.to_string()),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disasm::disassemble;
+    use crate::variant::Variant;
+
+    fn bytes(source: &str) -> Vec<u8> {
+        assemble(source).unwrap().bytes
+    }
+
+    fn error(source: &str) -> String {
+        assemble(source).unwrap_err().to_string()
+    }
+
+    /// Псевдослучайный ROM (линейный конгруэнтный генератор)
+    fn random_rom(seed: u32, len: usize) -> Vec<u8> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                (state >> 16) as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn assembles_instructions() {
+        let source = "
+            CLS
+            LD V0, 0x2A
+            LD I, 0x300
+            ADD I, VA
+            DRW V0, V1, 5
+            SHR V3
+            LD [I], VF
+            LD V2, [I]
+            LD HF, V4
+            JP V0, 0x208
+            SE V5, V6
+            RND VC, #0F
+            PLANE 3
+        ";
+        assert_eq!(
+            bytes(source),
+            [
+                0x00, 0xE0, 0x60, 0x2A, 0xA3, 0x00, 0xFA, 0x1E, 0xD0, 0x15, 0x83, 0x06, 0xFF, 0x55,
+                0xF2, 0x65, 0xF4, 0x30, 0xB2, 0x08, 0x55, 0x60, 0xCC, 0x0F, 0xF3, 0x01,
+            ]
+        );
+    }
+
+    #[test]
+    fn resolves_forward_labels_and_constants() {
+        let source = "
+            SPEED=3
+            start: ADD V0, SPEED
+                   CALL draw
+                   JP start
+            draw:
+                   LD I, ball + 1
+                   RET
+            ball:  db 0xFF, 0b10000001
+            LIMIT equ ball - start
+                   dw LIMIT
+        ";
+        let assembly = assemble(source).unwrap();
+        assert_eq!(
+            assembly.bytes,
+            [0x70, 0x03, 0x22, 0x06, 0x12, 0x00, 0xA2, 0x0B, 0x00, 0xEE, 0xFF, 0x81, 0x00, 0x0A]
+        );
+        assert_eq!(assembly.labels["draw"], 0x206);
+        assert_eq!(assembly.symbol_file(), "0200 start\n0206 draw\n020A ball\n");
+    }
+
+    #[test]
+    fn sprite_literals_and_strings() {
+        let source = r#"
+            sprite ..####..
+            sprite #......#
+            sprite ################
+            db "HI; there", -1
+        "#;
+        assert_eq!(
+            bytes(source),
+            [0x3C, 0x81, 0xFF, 0xFF, b'H', b'I', b';', b' ', b't', b'h', b'e', b'r', b'e', 0xFF]
+        );
+    }
+
+    #[test]
+    fn xo_long_index() {
+        assert_eq!(bytes("LD I, LONG target\ntarget: EXIT"), [0xF0, 0x00, 0x02, 0x04, 0x00, 0xFD]);
+    }
+
+    #[test]
+    fn reports_errors_with_line_numbers() {
+        assert_eq!(error("CLS\nJP nowhere"), "<input>:2: unknown symbol 'nowhere'");
+        assert_eq!(error("LD V0, 256"), "<input>:1: byte 256 out of range");
+        assert_eq!(error("a:\na:"), "<input>:2: 'a' is already defined");
+        assert_eq!(error("DRW V0, V1"), "<input>:1: invalid operands for DRW: 'V0, V1'");
+        assert_eq!(error("MOV V0, V1"), "<input>:1: unknown instruction 'MOV'");
+        assert_eq!(error("X = X + 1\ndw X"), "<input>:2: constant 'X + 1' refers to itself");
+    }
+
+    #[test]
+    fn includes_files_relative_to_source() {
+        let dir = std::env::temp_dir().join(format!("chip8-asm-{}", std::process::id()));
+        fs::create_dir_all(dir.join("lib")).unwrap();
+        fs::write(dir.join("main.asm"), "include \"lib/sprites.asm\"\nJP ball").unwrap();
+        fs::write(dir.join("lib/sprites.asm"), "ball: sprite ##..##..").unwrap();
+
+        let assembly = assemble_file(dir.join("main.asm")).unwrap();
+        assert_eq!(assembly.bytes, [0xCC, 0x12, 0x00]);
+
+        fs::write(dir.join("loop.asm"), "include \"loop.asm\"").unwrap();
+        assert!(assemble_file(dir.join("loop.asm")).is_err());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn disassembly_round_trips() {
+        for seed in 0..20 {
+            for variant in Variant::all() {
+                let rom = random_rom(seed, 512);
+                let listing = disassemble(&rom, variant).to_string();
+                let assembly = assemble(&listing).unwrap_or_else(|e| panic!("{}\n{}", e, listing));
+                assert_eq!(assembly.bytes, rom, "seed {} variant {}", seed, variant);
+            }
+        }
+    }
+}
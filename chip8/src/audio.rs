@@ -0,0 +1,434 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use crate::constants::DEFAULT_PITCH;
+use crate::cpu::CPU;
+use crate::scheduler::FRAME_RATE;
+
+/// Частота дискретизации по умолчанию
+pub const SAMPLE_RATE: u32 = 44100;
+/// Частота писка, если программа не загрузила свой звуковой буфер
+pub const BEEP_FREQUENCY: f64 = 440.0;
+/// Громкость по умолчанию (амплитуда меандра)
+pub const DEFAULT_VOLUME: f32 = 0.25;
+/// Скорость воспроизведения буфера XO-CHIP при высоте по умолчанию, бит в секунду
+const PATTERN_RATE: f64 = 4000.0;
+/// Длина звукового буфера XO-CHIP в битах
+const PATTERN_BITS: f64 = 128.0;
+
+/// Куда уходит звук: динамик, файл, тест
+pub trait AudioSink {
+    fn sample_rate(&self) -> u32;
+
+    /// Сэмплы одного кадра: моно, от -1.0 до 1.0
+    fn write(&mut self, samples: &[f32]) -> io::Result<()>;
+
+    /// Дописать всё при завершении (заголовок WAV и т.п.)
+    fn finish(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Генератор звука: пока звуковой таймер не ноль, играет меандр
+/// или однобитный буфер XO-CHIP (F002) со скоростью по высоте (FX3A)
+#[derive(Debug, Clone)]
+pub struct Synth {
+    pub sample_rate: u32,
+    pub volume: f32,
+    // Позиция в периоде меандра (0..1) или в буфере XO-CHIP (0..128 бит)
+    phase: f64,
+    // Дробная часть сэмплов, не поместившаяся в прошлый кадр
+    carry: f64,
+}
+
+impl Synth {
+    pub fn new(sample_rate: u32) -> Self {
+        Synth {
+            sample_rate,
+            volume: DEFAULT_VOLUME,
+            phase: 0.0,
+            carry: 0.0,
+        }
+    }
+
+    /// Скорость воспроизведения буфера XO-CHIP: 4000 * 2^((pitch - 64) / 48)
+    pub fn pattern_rate(pitch: u8) -> f64 {
+        PATTERN_RATE * 2f64.powf((pitch as f64 - DEFAULT_PITCH as f64) / 48.0)
+    }
+
+    /// Сэмплы одного кадра (1/60 секунды) по текущему состоянию звука
+    pub fn render_frame(&mut self, cpu: &CPU, out: &mut Vec<f32>) {
+        let total = self.carry + self.sample_rate as f64 / FRAME_RATE as f64;
+        let count = total as usize;
+        self.carry = total - count as f64;
+
+        if cpu.sound_timer == 0 {
+            // Следующий сигнал начнётся с начала периода
+            self.phase = 0.0;
+            out.extend(std::iter::repeat_n(0.0, count));
+            return;
+        }
+
+        let high = self.volume;
+        let low = -self.volume;
+        if !cpu.pattern_loaded {
+            let step = BEEP_FREQUENCY / self.sample_rate as f64;
+            for _ in 0..count {
+                out.push(if self.phase < 0.5 { high } else { low });
+                self.phase = (self.phase + step) % 1.0;
+            }
+        } else {
+            let step = Self::pattern_rate(cpu.pitch) / self.sample_rate as f64;
+            for _ in 0..count {
+                let bit = (self.phase as usize).min(PATTERN_BITS as usize - 1);
+                let set = cpu.audio_pattern[bit / 8] & (0x80 >> (bit % 8)) != 0;
+                out.push(if set { high } else { low });
+                self.phase = (self.phase + step) % PATTERN_BITS;
+            }
+        }
+    }
+}
+
+/// Вывод в один приёмник со своим генератором
+struct Output {
+    synth: Synth,
+    sink: Box<dyn AudioSink>,
+    // Первая ошибка записи; после неё приёмник больше не трогаем
+    error: Option<io::Error>,
+}
+
+/// Звуковая подсистема машины: генерирует кадр звука во все подключённые приёмники
+#[derive(Default)]
+pub struct Audio {
+    outputs: Vec<Output>,
+    buffer: Vec<f32>,
+}
+
+impl Audio {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_sink(&mut self, sink: Box<dyn AudioSink>) {
+        let synth = Synth::new(sink.sample_rate());
+        self.outputs.push(Output { synth, sink, error: None });
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.outputs.is_empty()
+    }
+
+    /// Громкость всех выходов, от 0.0 до 1.0
+    pub fn set_volume(&mut self, volume: f32) {
+        for output in &mut self.outputs {
+            output.synth.volume = volume.clamp(0.0, 1.0);
+        }
+    }
+
+    /// Звук одного кадра; вызывается до тика таймеров
+    pub fn frame(&mut self, cpu: &CPU) {
+        for output in &mut self.outputs {
+            if output.error.is_some() {
+                continue;
+            }
+            self.buffer.clear();
+            output.synth.render_frame(cpu, &mut self.buffer);
+            if let Err(e) = output.sink.write(&self.buffer) {
+                output.error = Some(e);
+            }
+        }
+    }
+
+    /// Завершить все приёмники и отключить их; возвращает первую ошибку
+    pub fn finish(&mut self) -> io::Result<()> {
+        let mut result = Ok(());
+        for mut output in self.outputs.drain(..) {
+            let finished = match output.error.take() {
+                Some(e) => Err(e),
+                None => output.sink.finish(),
+            };
+            if result.is_ok() {
+                result = finished;
+            }
+        }
+        result
+    }
+}
+
+/// Запись звука в WAV (PCM, 16 бит, моно), чтобы проверить звук без звуковой карты
+pub struct WavSink<W: Write + Seek> {
+    writer: W,
+    sample_rate: u32,
+    // Записано сэмплов
+    samples: u32,
+}
+
+impl WavSink<BufWriter<File>> {
+    pub fn create(path: impl AsRef<Path>, sample_rate: u32) -> io::Result<Self> {
+        WavSink::new(BufWriter::new(File::create(path)?), sample_rate)
+    }
+}
+
+impl<W: Write + Seek> WavSink<W> {
+    /// Пишет заголовок; размеры данных дописываются в `finish`
+    pub fn new(mut writer: W, sample_rate: u32) -> io::Result<Self> {
+        writer.write_all(&wav_header(sample_rate, 0))?;
+        Ok(WavSink { writer, sample_rate, samples: 0 })
+    }
+
+    pub fn samples(&self) -> u32 {
+        self.samples
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write + Seek> AudioSink for WavSink<W> {
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn write(&mut self, samples: &[f32]) -> io::Result<()> {
+        let mut bytes = Vec::with_capacity(samples.len() * 2);
+        for &sample in samples {
+            let value = (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        self.writer.write_all(&bytes)?;
+        self.samples += samples.len() as u32;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.writer.seek(SeekFrom::Start(0))?;
+        self.writer.write_all(&wav_header(self.sample_rate, self.samples))?;
+        self.writer.seek(SeekFrom::End(0))?;
+        self.writer.flush()
+    }
+}
+
+/// Заголовок RIFF/WAVE для `samples` 16-битных моно сэмплов
+fn wav_header(sample_rate: u32, samples: u32) -> [u8; 44] {
+    let data_size = samples * 2;
+    let mut header = [0u8; 44];
+    header[0..4].copy_from_slice(b"RIFF");
+    header[4..8].copy_from_slice(&(36 + data_size).to_le_bytes());
+    header[8..12].copy_from_slice(b"WAVE");
+    header[12..16].copy_from_slice(b"fmt ");
+    header[16..20].copy_from_slice(&16u32.to_le_bytes());
+    header[20..22].copy_from_slice(&1u16.to_le_bytes()); // PCM
+    header[22..24].copy_from_slice(&1u16.to_le_bytes()); // моно
+    header[24..28].copy_from_slice(&sample_rate.to_le_bytes());
+    header[28..32].copy_from_slice(&(sample_rate * 2).to_le_bytes());
+    header[32..34].copy_from_slice(&2u16.to_le_bytes());
+    header[34..36].copy_from_slice(&16u16.to_le_bytes());
+    header[36..40].copy_from_slice(b"data");
+    header[40..44].copy_from_slice(&data_size.to_le_bytes());
+    header
+}
+
+/// Вывод на звуковую карту через cpal
+#[cfg(feature = "sound")]
+pub mod live {
+    use std::collections::VecDeque;
+    use std::io;
+    use std::sync::{Arc, Mutex};
+
+    use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+    use cpal::{FromSample, SampleFormat, SizedSample, Stream, StreamConfig};
+
+    use super::AudioSink;
+    use crate::scheduler::FRAME_RATE;
+
+    /// Больше стольких кадров в очереди не держим, чтобы звук не отставал
+    const MAX_QUEUED_FRAMES: usize = 4;
+
+    /// Приёмник для устройства вывода по умолчанию
+    pub struct LiveSink {
+        // Поток должен жить, пока играет звук
+        _stream: Stream,
+        queue: Arc<Mutex<VecDeque<f32>>>,
+        sample_rate: u32,
+    }
+
+    impl LiveSink {
+        pub fn open() -> io::Result<Self> {
+            let device = cpal::default_host()
+                .default_output_device()
+                .ok_or_else(|| io::Error::other("no audio output device"))?;
+            let supported = device.default_output_config().map_err(io::Error::other)?;
+            let format = supported.sample_format();
+            let config: StreamConfig = supported.into();
+            let queue = Arc::new(Mutex::new(VecDeque::new()));
+
+            let stream = match format {
+                SampleFormat::F32 => build::<f32>(&device, &config, queue.clone()),
+                SampleFormat::I16 => build::<i16>(&device, &config, queue.clone()),
+                SampleFormat::U16 => build::<u16>(&device, &config, queue.clone()),
+                other => return Err(io::Error::other(format!("unsupported sample format {}", other))),
+            }?;
+            stream.play().map_err(io::Error::other)?;
+
+            Ok(LiveSink {
+                _stream: stream,
+                queue,
+                sample_rate: config.sample_rate.0,
+            })
+        }
+    }
+
+    fn build<T>(device: &cpal::Device, config: &StreamConfig, queue: Arc<Mutex<VecDeque<f32>>>) -> io::Result<Stream>
+    where
+        T: SizedSample + FromSample<f32>,
+    {
+        let channels = config.channels as usize;
+        device
+            .build_output_stream(
+                config,
+                move |data: &mut [T], _| {
+                    let mut queue = queue.lock().unwrap();
+                    for frame in data.chunks_mut(channels) {
+                        // Нехватка данных - тишина
+                        let sample = T::from_sample(queue.pop_front().unwrap_or(0.0));
+                        frame.fill(sample);
+                    }
+                },
+                |e| eprintln!("Audio stream error: {}", e),
+                None,
+            )
+            .map_err(io::Error::other)
+    }
+
+    impl AudioSink for LiveSink {
+        fn sample_rate(&self) -> u32 {
+            self.sample_rate
+        }
+
+        fn write(&mut self, samples: &[f32]) -> io::Result<()> {
+            let limit = self.sample_rate as usize / FRAME_RATE as usize * MAX_QUEUED_FRAMES;
+            let mut queue = self.queue.lock().unwrap();
+            queue.extend(samples);
+            let excess = queue.len().saturating_sub(limit);
+            queue.drain(..excess);
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::io::Cursor;
+    use std::rc::Rc;
+
+    /// Приёмник, складывающий сэмплы в общий вектор
+    struct Collect(Rc<RefCell<Vec<f32>>>);
+
+    impl AudioSink for Collect {
+        fn sample_rate(&self) -> u32 {
+            6000
+        }
+
+        fn write(&mut self, samples: &[f32]) -> io::Result<()> {
+            self.0.borrow_mut().extend_from_slice(samples);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn silent_while_timer_is_zero() {
+        let mut synth = Synth::new(SAMPLE_RATE);
+        let mut out = Vec::new();
+        synth.render_frame(&CPU::new(), &mut out);
+        assert_eq!(out.len(), 735);
+        assert!(out.iter().all(|&s| s == 0.0));
+    }
+
+    #[test]
+    fn square_wave_while_timer_runs() {
+        let mut cpu = CPU::new();
+        cpu.sound_timer = 2;
+        let mut synth = Synth::new(SAMPLE_RATE);
+        let mut out = Vec::new();
+        synth.render_frame(&cpu, &mut out);
+
+        // 440 Гц: полупериод ~50 сэмплов, за кадр ~7 периодов
+        let rising = out.windows(2).filter(|w| w[0] < 0.0 && w[1] > 0.0).count();
+        assert!((6..=8).contains(&rising), "{} periods", rising);
+        assert_eq!(out[0], DEFAULT_VOLUME);
+    }
+
+    #[test]
+    fn frames_keep_fractional_samples() {
+        let mut synth = Synth::new(SAMPLE_RATE + 30);
+        let mut out = Vec::new();
+        for _ in 0..FRAME_RATE {
+            synth.render_frame(&CPU::new(), &mut out);
+        }
+        assert_eq!(out.len(), (SAMPLE_RATE + 30) as usize);
+    }
+
+    #[test]
+    fn plays_xo_chip_pattern() {
+        let mut cpu = CPU::new();
+        cpu.sound_timer = 1;
+        cpu.audio_pattern = [0xF0; 16];
+        cpu.pattern_loaded = true;
+        // 4000 бит/с при 8000 Гц: каждый бит длится два сэмпла
+        let mut synth = Synth::new(8000);
+        let mut out = Vec::new();
+        synth.render_frame(&cpu, &mut out);
+        let high = DEFAULT_VOLUME;
+        assert_eq!(out[..10], [high, high, high, high, high, high, high, high, -high, -high]);
+
+        assert_eq!(Synth::pattern_rate(DEFAULT_PITCH + 48), 8000.0);
+    }
+
+    #[test]
+    fn loaded_silent_pattern_stays_silent() {
+        let mut cpu = CPU::new();
+        cpu.sound_timer = 1;
+        cpu.pattern_loaded = true;
+        let mut synth = Synth::new(8000);
+        let mut out = Vec::new();
+        synth.render_frame(&cpu, &mut out);
+        assert!(out.iter().all(|&sample| sample == -DEFAULT_VOLUME));
+    }
+
+    #[test]
+    fn audio_feeds_every_sink() {
+        let first = Rc::new(RefCell::new(Vec::new()));
+        let second = Rc::new(RefCell::new(Vec::new()));
+        let mut audio = Audio::new();
+        audio.add_sink(Box::new(Collect(first.clone())));
+        audio.add_sink(Box::new(Collect(second.clone())));
+
+        let mut cpu = CPU::new();
+        cpu.sound_timer = 1;
+        audio.frame(&cpu);
+        assert_eq!(first.borrow().len(), 100);
+        assert_eq!(*first.borrow(), *second.borrow());
+        audio.finish().unwrap();
+        assert!(audio.is_empty());
+    }
+
+    #[test]
+    fn wav_header_has_final_sizes() {
+        let mut wav = WavSink::new(Cursor::new(Vec::new()), 8000).unwrap();
+        wav.write(&[0.0, 1.0, -1.0]).unwrap();
+        wav.finish().unwrap();
+        let bytes = wav.into_inner().into_inner();
+
+        assert_eq!(bytes.len(), 44 + 6);
+        assert_eq!(&bytes[0..4], b"RIFF");
+        assert_eq!(u32::from_le_bytes(bytes[4..8].try_into().unwrap()), 36 + 6);
+        assert_eq!(u32::from_le_bytes(bytes[24..28].try_into().unwrap()), 8000);
+        assert_eq!(u32::from_le_bytes(bytes[40..44].try_into().unwrap()), 6);
+        assert_eq!(i16::from_le_bytes([bytes[46], bytes[47]]), i16::MAX);
+        assert_eq!(i16::from_le_bytes([bytes[48], bytes[49]]), -i16::MAX);
+    }
+}
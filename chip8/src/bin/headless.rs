@@ -0,0 +1,236 @@
+//! Запуск ROM без окна для CI: сценарий нажатий, N кадров или до 00FD,
+//! затем снимок экрана и состояние машины.
+//!
+//! Коды выхода: 0 - программа остановилась сама, 1 - ошибка запуска,
+//! 2 - кадры кончились раньше, 3 - сбой программы.
+
+use chip8::audio::{self, WavSink};
+use chip8::cheat::{Cheat, CheatList};
+use chip8::headless::{self, KeyTimeline, EXIT_ERROR};
+use chip8::movie::Movie;
+use chip8::profile::{self, Profiler};
+use chip8::quirks::Preset;
+use chip8::rng::RngConfig;
+use chip8::scheduler::{self, FRAME_RATE};
+use chip8::screenshot::{self, GifRecorder};
+use chip8::variant::Variant;
+use chip8::video::{Image, Overlay, Palette};
+use chip8::Chip8;
+use clap::Parser;
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::ops::RangeInclusive;
+use std::path::Path;
+use std::process;
+
+#[derive(Parser)]
+#[command(name = "chip8-headless")]
+#[command(about = "Run a CHIP-8 ROM without a window", version)]
+struct Cli {
+    /// ROM файл
+    rom: String,
+
+    /// Сколько кадров (1/60 секунды) выполнить, если программа не остановится раньше
+    /// (по умолчанию 10 секунд или длина записи --replay)
+    #[arg(long)]
+    frames: Option<u64>,
+
+    /// Скорость: инструкций за кадр
+    #[arg(long, default_value_t = scheduler::DEFAULT_INSTRUCTIONS_PER_FRAME)]
+    speed: usize,
+
+    /// Профиль совместимости: vip, chip48, schip, xochip
+    #[arg(short, long)]
+    quirks: Option<Preset>,
+
+    /// Вариант машины: classic (4KB) или xochip (64KB)
+    #[arg(long, default_value_t = Variant::Classic)]
+    variant: Variant,
+
+    /// Случайные числа для CXKK: thread, seed:N (воспроизводимо) или script:AA,BB,... (по кругу)
+    #[arg(long, default_value_t = RngConfig::Thread, conflicts_with = "replay")]
+    rng: RngConfig,
+
+    /// Файл сценария нажатий ("30 down 5", "40 up 5", "60 tap A 3")
+    #[arg(long)]
+    keys: Option<String>,
+
+    /// Событие сценария прямо в командной строке, можно повторять
+    #[arg(long = "key")]
+    key_events: Vec<String>,
+
+    /// Воспроизвести запись ввода вместо сценария; настройки машины и seed берутся из неё
+    #[arg(long, conflicts_with_all = ["keys", "key_events"])]
+    replay: Option<String>,
+
+    /// Список читов (файл из ~/.config здесь не подхватывается, чтобы прогон не зависел от машины)
+    #[arg(long)]
+    cheats: Option<String>,
+
+    /// Заморозить адрес или регистр: 3F0=09, V3=05, I=300 (значения в hex), можно повторять
+    #[arg(long)]
+    freeze: Vec<Cheat>,
+
+    /// Сохранить последний кадр в PBM (P1)
+    #[arg(long)]
+    pbm: Option<String>,
+
+    /// Сохранить последний кадр в PNG
+    #[arg(long)]
+    png: Option<String>,
+
+    /// Записать кадры в анимированный GIF
+    #[arg(long)]
+    gif: Option<String>,
+
+    /// Какие кадры писать в GIF: START-END или START- (по умолчанию все)
+    #[arg(long, value_parser = screenshot::parse_frame_range, requires = "gif")]
+    gif_frames: Option<RangeInclusive<u64>>,
+
+    /// Масштаб PNG и GIF
+    #[arg(long, default_value_t = 1)]
+    scale: usize,
+
+    /// Палитра PNG и GIF: classic, amber, green, lcd, octo или цвета RRGGBB через запятую
+    #[arg(long, default_value_t = Palette::default())]
+    palette: Palette,
+
+    /// Записать звук в WAV
+    #[arg(long)]
+    wav: Option<String>,
+
+    /// Записать регистры и память в JSON (`-` для stdout)
+    #[arg(long)]
+    state: Option<String>,
+
+    /// Профилировать: отчёт в JSON в файл, таблицы в stderr
+    #[arg(long)]
+    profile: Option<String>,
+
+    /// Символы ассемблера для отчёта (по умолчанию ROM.sym рядом с ROM, если есть)
+    #[arg(long, requires = "profile")]
+    symbols: Option<String>,
+}
+
+fn main() {
+    // clap сам выходит с кодом 2, а он у нас означает таймаут
+    let cli = Cli::try_parse().unwrap_or_else(|e| {
+        let _ = e.print();
+        process::exit(if e.use_stderr() { EXIT_ERROR } else { 0 });
+    });
+    let code = run(&cli).unwrap_or_else(|e| {
+        eprintln!("chip8-headless: {}", e);
+        EXIT_ERROR
+    });
+    process::exit(code);
+}
+
+/// Прогон; возвращает код выхода
+fn run(cli: &Cli) -> Result<i32, String> {
+    let mut timeline = KeyTimeline::new();
+    if let Some(path) = &cli.keys {
+        let text = fs::read_to_string(path).map_err(|e| format!("failed to read '{}': {}", path, e))?;
+        timeline = text.parse().map_err(|e| format!("{}: {}", path, e))?;
+    }
+    for event in &cli.key_events {
+        let parsed: KeyTimeline = event.parse().map_err(|e| format!("--key '{}': {}", event, e))?;
+        timeline.extend(parsed);
+    }
+
+    let rom = fs::read(&cli.rom).map_err(|e| format!("failed to read ROM '{}': {}", cli.rom, e))?;
+    let mut frames = cli.frames.unwrap_or(10 * FRAME_RATE);
+    let mut speed = cli.speed;
+    let mut chip8 = match &cli.replay {
+        Some(path) => {
+            let movie = Movie::read_from(path).map_err(|e| format!("failed to read '{}': {}", path, e))?;
+            frames = cli.frames.unwrap_or(movie.frames());
+            speed = movie.instructions_per_frame as usize;
+            movie.start(&rom).map_err(|e| format!("failed to replay '{}': {}", path, e))?
+        }
+        None => {
+            let mut chip8 = Chip8::new(cli.variant);
+            if let Some(preset) = cli.quirks {
+                chip8 = chip8.with_quirks(preset.quirks());
+            }
+            chip8.set_rng(cli.rng.build());
+            chip8.load(&rom).map_err(|e| format!("failed to load ROM '{}': {}", cli.rom, e))?;
+            chip8
+        }
+    };
+
+    if let Some(path) = &cli.cheats {
+        CheatList::read_from(path)
+            .and_then(|list| chip8.load_cheats(&list))
+            .map_err(|e| format!("failed to load cheats '{}': {}", path, e))?;
+    }
+    for cheat in &cli.freeze {
+        chip8.add_cheat(*cheat);
+    }
+
+    let symbols = match &cli.profile {
+        Some(_) => {
+            chip8.set_profiler(Some(Profiler::new()));
+            profile::load_symbols(cli.symbols.as_deref(), Path::new(&cli.rom))?
+        }
+        None => Default::default(),
+    };
+
+    if let Some(path) = &cli.wav {
+        let sink = WavSink::create(path, audio::SAMPLE_RATE).map_err(|e| format!("failed to create '{}': {}", path, e))?;
+        chip8.add_audio_sink(Box::new(sink));
+    }
+
+    let mut gif = match &cli.gif {
+        Some(path) => Some(GifRecorder::create(path).map_err(|e| format!("failed to create '{}': {}", path, e))?),
+        None => None,
+    };
+    let gif_frames = cli.gif_frames.clone().unwrap_or(0..=u64::MAX);
+    let mut gif_error = None;
+    let outcome = headless::run_with(&mut chip8, &timeline, frames, speed, |frame, chip8| {
+        if let Some(recorder) = &mut gif
+            && gif_frames.contains(&frame)
+            && gif_error.is_none()
+        {
+            let image = Image::from_display(chip8.display(), &cli.palette).scaled(cli.scale, Overlay::None);
+            gif_error = recorder.push(&image, 1).err();
+        }
+    });
+    if let Some(recorder) = gif {
+        let path = cli.gif.as_deref().unwrap_or_default();
+        let result = match gif_error {
+            Some(e) => Err(e),
+            None => recorder.finish().map(drop),
+        };
+        result.map_err(|e| format!("failed to write '{}': {}", path, e))?;
+    }
+    chip8.finish_audio().map_err(|e| format!("failed to write sound: {}", e))?;
+    eprintln!("{}: {}", cli.rom, outcome);
+
+    if let Some(path) = &cli.pbm {
+        fs::write(path, screenshot::to_pbm(chip8.display()))
+            .map_err(|e| format!("failed to write '{}': {}", path, e))?;
+    }
+    if let Some(path) = &cli.png {
+        let file = File::create(path).map_err(|e| format!("failed to create '{}': {}", path, e))?;
+        let image = Image::from_display(chip8.display(), &cli.palette).scaled(cli.scale, Overlay::None);
+        screenshot::write_png(&image, BufWriter::new(file))
+            .map_err(|e| format!("failed to write '{}': {}", path, e))?;
+    }
+    if let Some(path) = &cli.state {
+        let json = headless::state_json(&chip8, &outcome);
+        if path == "-" {
+            print!("{}", json);
+        } else {
+            fs::write(path, json).map_err(|e| format!("failed to write '{}': {}", path, e))?;
+        }
+    }
+    if let Some(path) = &cli.profile
+        && let Some(profiler) = chip8.profiler()
+    {
+        eprint!("{}", profiler.text_report(&chip8, &symbols));
+        fs::write(path, profiler.json_report(&chip8, &symbols))
+            .map_err(|e| format!("failed to write '{}': {}", path, e))?;
+    }
+
+    Ok(outcome.exit_code())
+}
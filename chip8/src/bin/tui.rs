@@ -0,0 +1,261 @@
+//! Фронтенд в терминале: экран полублоками (две строки пикселей в ячейке),
+//! клавиши из stdin в raw-режиме по той же раскладке, что и в окне.
+//! Работает по SSH, где окно не открыть.
+
+use chip8::cheat::{self, Cheat, CheatList};
+use chip8::fault::FaultPolicy;
+use chip8::keymap::{self, HostKey, Keymap};
+use chip8::quirks::Preset;
+use chip8::rng::RngConfig;
+use chip8::runner::Runner;
+use chip8::scheduler::{self, Scheduler, FRAME_RATE};
+use chip8::tui::{self, HeldKeys, HOLD_TIMEOUT};
+use chip8::variant::Variant;
+use chip8::Chip8;
+use clap::Parser;
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{
+    self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, KeyboardEnhancementFlags, ModifierKeyCode,
+    PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+};
+use crossterm::style::Print;
+use crossterm::terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{execute, queue};
+use std::fs;
+use std::io::{self, Stdout, Write};
+use std::process;
+use std::time::{Duration, Instant};
+
+#[derive(Parser)]
+#[command(name = "chip8-tui")]
+#[command(about = "CHIP-8 emulator in the terminal", version)]
+struct Cli {
+    /// ROM файл
+    rom: String,
+
+    /// Профиль совместимости: vip, chip48, schip, xochip
+    #[arg(short, long)]
+    quirks: Option<Preset>,
+
+    /// Вариант машины: classic (4KB) или xochip (64KB)
+    #[arg(long, default_value_t = Variant::Classic)]
+    variant: Variant,
+
+    /// Скорость: инструкций за кадр (60 кадров в секунду)
+    #[arg(long, default_value_t = scheduler::DEFAULT_INSTRUCTIONS_PER_FRAME)]
+    speed: usize,
+
+    /// Глубина перемотки в секундах, 0 - выключить
+    #[arg(long, default_value_t = 10)]
+    rewind: u32,
+
+    /// Реакция на сбой программы: halt, pause, ignore
+    #[arg(long, default_value_t = FaultPolicy::Pause)]
+    on_fault: FaultPolicy,
+
+    /// Случайные числа для CXKK: thread, seed:N (воспроизводимо) или script:AA,BB,... (по кругу)
+    #[arg(long, default_value_t = RngConfig::Thread)]
+    rng: RngConfig,
+
+    /// Список читов (по умолчанию файл ROM в ~/.config/chip8/cheats, если есть)
+    #[arg(long)]
+    cheats: Option<String>,
+
+    /// Заморозить адрес или регистр: 3F0=09, V3=05, I=300 (значения в hex), можно повторять
+    #[arg(long)]
+    freeze: Vec<Cheat>,
+
+    /// Раскладка клавиш в TOML (по умолчанию ~/.config/chip8/keys.toml, если есть)
+    #[arg(long)]
+    keymap: Option<String>,
+}
+
+fn main() {
+    let cli = Cli::parse();
+    if let Err(e) = run(&cli) {
+        eprintln!("chip8-tui: {}", e);
+        process::exit(1);
+    }
+}
+
+fn run(cli: &Cli) -> Result<(), String> {
+    let rom = fs::read(&cli.rom).map_err(|e| format!("failed to read ROM '{}': {}", cli.rom, e))?;
+    let mut chip8 = Chip8::new(cli.variant);
+    if let Some(preset) = cli.quirks {
+        chip8 = chip8.with_quirks(preset.quirks());
+    }
+    chip8.set_rng(cli.rng.build());
+    chip8.load(&rom).map_err(|e| format!("failed to load ROM '{}': {}", cli.rom, e))?;
+    chip8.set_rewind(cli.rewind);
+    if let Some(path) = cheat::list_path(cli.cheats.as_deref(), chip8.rom_hash()) {
+        CheatList::read_from(&path)
+            .and_then(|list| chip8.load_cheats(&list))
+            .map_err(|e| format!("failed to load cheats '{}': {}", path.display(), e))?;
+    }
+    for cheat in &cli.freeze {
+        chip8.add_cheat(*cheat);
+    }
+
+    let keymap = match keymap::config_path(cli.keymap.as_deref()) {
+        Some(path) => Keymap::load(&path, &cli.rom, chip8.rom_hash()).map_err(|e| e.to_string())?,
+        None => Keymap::default(),
+    };
+
+    // Часы запускаются после настройки терминала: запрос его возможностей может идти долго
+    let mut screen = Screen::open().map_err(|e| format!("failed to set up the terminal: {}", e))?;
+    let mut runner = Runner::new(&chip8, Scheduler::real_time(cli.speed), keymap, &cli.rom)
+        .with_fault_policy(cli.on_fault);
+    let mut log = Vec::new();
+    let result = run_loop(&mut chip8, &mut runner, &mut screen, &mut log);
+    drop(screen);
+
+    // Сообщения за сеанс видны после выхода из полноэкранного режима
+    for message in log {
+        println!("{}", message);
+    }
+    println!("Total cycles: {}", chip8.cpu().cycles);
+    result.map_err(|e| format!("terminal error: {}", e))
+}
+
+/// Тот же цикл, что и в окне: ввод, проход Runner, перерисовка по необходимости
+fn run_loop(chip8: &mut Chip8, runner: &mut Runner, screen: &mut Screen, log: &mut Vec<String>) -> io::Result<()> {
+    let frame = Duration::from_secs(1) / FRAME_RATE as u32;
+    let mut keys = HeldKeys::new((!screen.release_events).then_some(HOLD_TIMEOUT));
+    let mut last_frame = Instant::now();
+    let mut status = String::new();
+    let mut message = String::new();
+    let mut size = (0, 0);
+
+    loop {
+        // Ввод ждём не дольше начала следующего кадра
+        let mut timeout = frame.saturating_sub(last_frame.elapsed());
+        while event::poll(timeout)? {
+            timeout = Duration::ZERO;
+            match event::read()? {
+                Event::Key(event) if is_interrupt(&event) => return Ok(()),
+                Event::Key(event) => {
+                    if let Some(key) = host_key(event.code) {
+                        match event.kind {
+                            KeyEventKind::Release => keys.release(key),
+                            _ => keys.press(key, &modifiers(event.modifiers), Instant::now()),
+                        }
+                    }
+                }
+                // После изменения размера терминала рисуем всё заново
+                Event::Resize(..) => size = (0, 0),
+                _ => {}
+            }
+        }
+        last_frame = Instant::now();
+
+        let input = keys.input(last_frame);
+        let running = runner.tick(chip8, &input);
+        let mut messages = runner.take_messages();
+        if !runner.take_frontend_hotkeys().is_empty() {
+            messages.push("Screenshots and GIF recording are not available in the terminal".to_string());
+        }
+        for text in messages {
+            message = text.clone();
+            log.push(text);
+        }
+
+        let new_status = format!("{}  {}", runner.status(chip8), message);
+        let new_size = (chip8.width(), chip8.height());
+        if chip8.needs_redraw() || new_status != status || new_size != size {
+            screen.draw(chip8, &new_status, new_size != size)?;
+            chip8.mark_drawn();
+            status = new_status;
+            size = new_size;
+        }
+        if !running {
+            return Ok(());
+        }
+    }
+}
+
+/// Терминал в raw-режиме на альтернативном экране; восстанавливается при удалении
+struct Screen {
+    out: Stdout,
+    // Терминал сообщает об отпускании клавиш (протокол kitty)
+    release_events: bool,
+}
+
+impl Screen {
+    fn open() -> io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        let mut out = io::stdout();
+        execute!(out, EnterAlternateScreen, Hide)?;
+        let release_events = terminal::supports_keyboard_enhancement().unwrap_or(false);
+        if release_events {
+            execute!(out, PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::REPORT_EVENT_TYPES))?;
+        }
+        Ok(Screen { out, release_events })
+    }
+
+    fn draw(&mut self, chip8: &Chip8, status: &str, clear: bool) -> io::Result<()> {
+        if clear {
+            queue!(self.out, Clear(ClearType::All))?;
+        }
+        let lines = tui::half_blocks(chip8.display());
+        for (row, line) in lines.iter().enumerate() {
+            queue!(self.out, MoveTo(0, row as u16), Print(line))?;
+        }
+        queue!(self.out, MoveTo(0, lines.len() as u16), Clear(ClearType::CurrentLine), Print(status))?;
+        self.out.flush()
+    }
+}
+
+impl Drop for Screen {
+    fn drop(&mut self) {
+        if self.release_events {
+            let _ = execute!(self.out, PopKeyboardEnhancementFlags);
+        }
+        let _ = execute!(self.out, Show, LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/// В raw-режиме Ctrl+C не прерывает процесс - выходим сами при любой раскладке
+fn is_interrupt(event: &KeyEvent) -> bool {
+    event.code == KeyCode::Char('c') && event.modifiers.contains(KeyModifiers::CONTROL)
+}
+
+/// Клавиша терминала в общем виде для раскладки
+fn host_key(code: KeyCode) -> Option<HostKey> {
+    Some(match code {
+        KeyCode::Char(' ') => HostKey::Space,
+        KeyCode::Char(c) => HostKey::Char(c.to_ascii_lowercase()),
+        KeyCode::F(n) => HostKey::F(n),
+        KeyCode::Up => HostKey::Up,
+        KeyCode::Down => HostKey::Down,
+        KeyCode::Left => HostKey::Left,
+        KeyCode::Right => HostKey::Right,
+        KeyCode::Enter => HostKey::Enter,
+        KeyCode::Esc => HostKey::Escape,
+        KeyCode::Backspace => HostKey::Backspace,
+        // Shift+Tab приходит отдельным кодом, Shift остаётся в модификаторах
+        KeyCode::Tab | KeyCode::BackTab => HostKey::Tab,
+        KeyCode::Insert => HostKey::Insert,
+        KeyCode::Delete => HostKey::Delete,
+        KeyCode::Home => HostKey::Home,
+        KeyCode::End => HostKey::End,
+        KeyCode::PageUp => HostKey::PageUp,
+        KeyCode::PageDown => HostKey::PageDown,
+        KeyCode::Modifier(ModifierKeyCode::LeftShift | ModifierKeyCode::RightShift) => HostKey::Shift,
+        KeyCode::Modifier(ModifierKeyCode::LeftControl | ModifierKeyCode::RightControl) => HostKey::Ctrl,
+        KeyCode::Modifier(ModifierKeyCode::LeftAlt | ModifierKeyCode::RightAlt) => HostKey::Alt,
+        _ => return None,
+    })
+}
+
+fn modifiers(modifiers: KeyModifiers) -> Vec<HostKey> {
+    [
+        (KeyModifiers::SHIFT, HostKey::Shift),
+        (KeyModifiers::CONTROL, HostKey::Ctrl),
+        (KeyModifiers::ALT, HostKey::Alt),
+    ]
+    .into_iter()
+    .filter(|&(flag, _)| modifiers.contains(flag))
+    .map(|(_, key)| key)
+    .collect()
+}
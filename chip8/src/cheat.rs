@@ -0,0 +1,334 @@
+//! Читы: поиск значений в памяти с последовательным сужением и заморозка
+//! адресов и регистров каждый кадр. Списки читов хранятся по хешу ROM.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use thiserror::Error;
+
+use crate::cpu::CPU;
+use crate::paths;
+use crate::trace::Register;
+
+/// Ошибка чтения или применения списка читов
+#[derive(Error, Debug)]
+pub enum CheatError {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("line {line}: {message}")]
+    Invalid { line: usize, message: String },
+
+    #[error("Cheat list has no 'rom = HASH' line")]
+    MissingRom,
+
+    #[error("Cheat list was made for another ROM (hash {expected:016X}, loaded ROM {found:016X})")]
+    RomMismatch { expected: u64, found: u64 },
+}
+
+/// Что замораживается
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    Memory(u16),
+    Register(Register),
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Target::Memory(addr) => write!(f, "{:04X}", addr),
+            Target::Register(register) => write!(f, "{}", register),
+        }
+    }
+}
+
+impl FromStr for Target {
+    type Err = String;
+
+    /// Регистр (V0-VF, I, DT, ST) или адрес памяти в hex
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.parse::<Register>() {
+            Ok(Register::Sp) => Err("SP cannot be frozen".to_string()),
+            Ok(register) => Ok(Target::Register(register)),
+            Err(_) => u16::from_str_radix(s.trim_start_matches("0x"), 16)
+                .map(Target::Memory)
+                .map_err(|_| format!("expected a register or a hex address, got '{}'", s)),
+        }
+    }
+}
+
+/// Замороженное значение: записывается заново в конце каждого кадра
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cheat {
+    pub target: Target,
+    pub value: u16,
+}
+
+impl Cheat {
+    /// Чит с проверкой, что значение помещается в цель (I - 16 бит, остальное - байт)
+    pub fn new(target: Target, value: u16) -> Result<Self, String> {
+        let max = match target {
+            Target::Register(Register::I) => u16::MAX,
+            _ => u8::MAX as u16,
+        };
+        if value > max {
+            return Err(format!("value {:X} does not fit into {} (max {:X})", value, target, max));
+        }
+        Ok(Cheat { target, value })
+    }
+
+    /// Записать значение; адреса за концом памяти пропускаются
+    pub fn apply(&self, cpu: &mut CPU) {
+        match self.target {
+            Target::Memory(addr) => {
+                if let Some(byte) = cpu.memory.get_mut(addr as usize) {
+                    *byte = self.value as u8;
+                }
+            }
+            Target::Register(register) => register.write(cpu, self.value),
+        }
+    }
+}
+
+impl fmt::Display for Cheat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.target {
+            Target::Register(Register::I) => write!(f, "{} = {:03X}", self.target, self.value),
+            _ => write!(f, "{} = {:02X}", self.target, self.value),
+        }
+    }
+}
+
+impl FromStr for Cheat {
+    type Err = String;
+
+    /// `3F0=09`, `V3 = 05`, `I=300` (значения в hex)
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (target, value) = s
+            .split_once('=')
+            .ok_or_else(|| format!("bad cheat '{}', expected TARGET=VALUE", s))?;
+        let value = value.trim();
+        let value = u16::from_str_radix(value.trim_start_matches("0x"), 16)
+            .map_err(|_| format!("invalid hex value '{}'", value))?;
+        Cheat::new(target.trim().parse()?, value)
+    }
+}
+
+/// Читы одного ROM. В файле - строка `rom = HASH` и по читу на строку,
+/// `#` - комментарий:
+///
+/// ```text
+/// rom = 0123456789ABCDEF
+/// 03F0 = 09   # жизни
+/// V3 = 05
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheatList {
+    pub rom_hash: u64,
+    pub cheats: Vec<Cheat>,
+}
+
+impl CheatList {
+    pub fn new(rom_hash: u64) -> Self {
+        CheatList { rom_hash, cheats: Vec::new() }
+    }
+
+    pub fn parse(text: &str) -> Result<Self, CheatError> {
+        let mut rom_hash = None;
+        let mut cheats = Vec::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let invalid = |message: String| CheatError::Invalid { line: number + 1, message };
+            match line.split_once('=') {
+                Some((key, hash)) if key.trim().eq_ignore_ascii_case("rom") => {
+                    let hash = hash.trim();
+                    let parsed = u64::from_str_radix(hash, 16)
+                        .map_err(|_| invalid(format!("invalid ROM hash '{}'", hash)))?;
+                    rom_hash = Some(parsed);
+                }
+                _ => cheats.push(line.parse().map_err(invalid)?),
+            }
+        }
+        let rom_hash = rom_hash.ok_or(CheatError::MissingRom)?;
+        Ok(CheatList { rom_hash, cheats })
+    }
+
+    /// Проверить, что список сделан для ROM с хешем `rom_hash`
+    pub fn check_rom(&self, rom_hash: u64) -> Result<(), CheatError> {
+        if self.rom_hash != rom_hash {
+            return Err(CheatError::RomMismatch { expected: self.rom_hash, found: rom_hash });
+        }
+        Ok(())
+    }
+
+    pub fn write_to(&self, path: impl AsRef<Path>) -> Result<(), CheatError> {
+        let path = path.as_ref();
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, self.to_string())?;
+        Ok(())
+    }
+
+    pub fn read_from(path: impl AsRef<Path>) -> Result<Self, CheatError> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+}
+
+impl fmt::Display for CheatList {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "rom = {:016X}", self.rom_hash)?;
+        for cheat in &self.cheats {
+            writeln!(f, "{}", cheat)?;
+        }
+        Ok(())
+    }
+}
+
+/// Файл читов ROM по умолчанию: $XDG_CONFIG_HOME/chip8/cheats/HASH.cheats
+/// или ~/.config/chip8/cheats/HASH.cheats
+pub fn default_path(rom_hash: u64) -> Option<PathBuf> {
+    Some(paths::config_dir()?.join("cheats").join(format!("{:016X}.cheats", rom_hash)))
+}
+
+/// Файл читов: заданный явно или файл ROM по умолчанию, если он есть
+pub fn list_path(explicit: Option<&str>, rom_hash: u64) -> Option<PathBuf> {
+    paths::explicit_or_existing(explicit, default_path(rom_hash))
+}
+
+/// Условие сужения поиска: сравнение со значением на прошлом шаге или с числом
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Filter {
+    Equal(u8),
+    Changed,
+    Unchanged,
+    Increased,
+    Decreased,
+}
+
+impl Filter {
+    pub fn matches(&self, old: u8, new: u8) -> bool {
+        match *self {
+            Filter::Equal(value) => new == value,
+            Filter::Changed => new != old,
+            Filter::Unchanged => new == old,
+            Filter::Increased => new > old,
+            Filter::Decreased => new < old,
+        }
+    }
+}
+
+impl fmt::Display for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Filter::Equal(value) => write!(f, "{:02X}", value),
+            Filter::Changed => f.write_str("changed"),
+            Filter::Unchanged => f.write_str("unchanged"),
+            Filter::Increased => f.write_str("increased"),
+            Filter::Decreased => f.write_str("decreased"),
+        }
+    }
+}
+
+impl FromStr for Filter {
+    type Err = String;
+
+    /// Значение в hex или changed, unchanged, increased, decreased
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "changed" => Ok(Filter::Changed),
+            "unchanged" => Ok(Filter::Unchanged),
+            "increased" | "inc" => Ok(Filter::Increased),
+            "decreased" | "dec" => Ok(Filter::Decreased),
+            value => u8::from_str_radix(value.trim_start_matches("0x"), 16).map(Filter::Equal).map_err(|_| {
+                format!("bad search filter '{}', expected a hex byte, changed, unchanged, increased or decreased", s)
+            }),
+        }
+    }
+}
+
+/// Поиск по памяти: адреса-кандидаты и их значения на прошлом шаге
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Search {
+    candidates: Vec<(u16, u8)>,
+}
+
+impl Search {
+    /// Новый поиск: кандидаты - вся память
+    pub fn new(memory: &[u8]) -> Self {
+        Search { candidates: memory.iter().enumerate().map(|(addr, &value)| (addr as u16, value)).collect() }
+    }
+
+    /// Оставить адреса, где выполняется условие, и запомнить текущие значения.
+    /// Возвращает число оставшихся кандидатов
+    pub fn narrow(&mut self, memory: &[u8], filter: Filter) -> usize {
+        self.candidates.retain_mut(|(addr, old)| match memory.get(*addr as usize) {
+            Some(&new) if filter.matches(*old, new) => {
+                *old = new;
+                true
+            }
+            _ => false,
+        });
+        self.candidates.len()
+    }
+
+    /// Адрес и значение после последнего сужения
+    pub fn candidates(&self) -> &[(u16, u8)] {
+        &self.candidates
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_cheats() {
+        assert_eq!("3F0=09".parse(), Ok(Cheat { target: Target::Memory(0x3F0), value: 9 }));
+        assert_eq!("v3 = 5".parse(), Ok(Cheat { target: Target::Register(Register::V(3)), value: 5 }));
+        assert_eq!("I=0x300".parse::<Cheat>().unwrap().to_string(), "I = 300");
+        assert!("V3=100".parse::<Cheat>().is_err());
+        assert!("SP=1".parse::<Cheat>().is_err());
+        assert!("3F0".parse::<Cheat>().is_err());
+    }
+
+    #[test]
+    fn cheat_list_round_trip() {
+        let text = "# Жизни и таймер\nrom = 00000000DEADBEEF\n3F0 = 09  # lives\n\ndt=3C\n";
+        let list = CheatList::parse(text).unwrap();
+        assert_eq!(list.rom_hash, 0xDEAD_BEEF);
+        assert_eq!(list.cheats.len(), 2);
+        assert_eq!(CheatList::parse(&list.to_string()).unwrap(), list);
+
+        assert!(list.check_rom(0xDEAD_BEEF).is_ok());
+        assert!(matches!(list.check_rom(1), Err(CheatError::RomMismatch { .. })));
+        assert!(matches!(CheatList::parse("3F0 = 09"), Err(CheatError::MissingRom)));
+        assert!(matches!(CheatList::parse("rom = 1\nV3"), Err(CheatError::Invalid { line: 2, .. })));
+    }
+
+    #[test]
+    fn search_narrows_candidates() {
+        let mut memory = vec![0u8; 8];
+        memory[2] = 3;
+        memory[5] = 3;
+        let mut search = Search::new(&memory);
+        assert_eq!(search.narrow(&memory, Filter::Equal(3)), 2);
+
+        // Жизнь потеряна: в одном адресе значение уменьшилось
+        memory[5] = 2;
+        assert_eq!(search.narrow(&memory, Filter::Decreased), 1);
+        assert_eq!(search.candidates(), [(5, 2)]);
+        assert_eq!(search.narrow(&memory, Filter::Unchanged), 1);
+        assert_eq!(search.narrow(&memory, Filter::Changed), 0);
+
+        assert_eq!("inc".parse(), Ok(Filter::Increased));
+        assert_eq!("0A".parse(), Ok(Filter::Equal(10)));
+        assert!("100".parse::<Filter>().is_err());
+    }
+}
@@ -1,8 +1,16 @@
 use std::fs;
-use crate::constants::{MEMORY_SIZE, PROGRAM_START, FONT_SET, FONT_START};
+use std::ops::Range;
+use crate::constants::{PROGRAM_START, FONT_SET, FONT_START, BIG_FONT_SET, BIG_FONT_START, DEFAULT_PITCH};
 use crate::display::Display;
+use crate::fault::Chip8Fault;
 use crate::keyboard::Keyboard;
+use crate::quirks::{IndexIncrement, Quirks};
+use crate::rng::{RandomSource, ThreadRng};
+use crate::profile::Profiler;
+use crate::trace::{RegisterSnapshot, Tracer};
+use crate::variant::Variant;
 
+#[allow(clippy::upper_case_acronyms)]
 pub struct CPU {
     // 16 регистров общего назначения (V0-VF)
     pub registers: [u8; 16],
@@ -14,33 +22,89 @@ pub struct CPU {
     pub stack: [u16; 16],
     // Указатель стека
     pub stack_pointer: u8,
-    // Память 4KB
-    pub memory: [u8; MEMORY_SIZE],
+    // Память: 4KB, у XO-CHIP 64KB
+    pub memory: Vec<u8>,
     // Таймеры 
     pub delay_timer: u8,
     pub sound_timer: u8,
     // Дисплей
     pub display: Display,
     pub keyboard: Keyboard,
+    // FX0A: регистр, в который попадёт клавиша
     pub waiting_for_key: Option<usize>,
+    // FX0A: клавиша, нажатая во время ожидания (ждём её отпускания)
+    pub key_wait_pressed: Option<u8>,
+    // FX0A: клавиши при прошлой проверке, чтобы ловить только новые нажатия
+    pub key_wait_held: [bool; 16],
     pub running: bool,
+    // Особенности поведения разных реализаций
+    pub quirks: Quirks,
+    // DXYN ждёт следующего кадра (quirk display_wait)
+    pub vblank_wait: bool,
+    // Пользовательские флаги RPL (FX75/FX85, SUPER-CHIP)
+    pub rpl_flags: [u8; 16],
+    // Вариант машины (размер памяти и расширения XO-CHIP)
+    pub variant: Variant,
+    // Звуковой буфер XO-CHIP: 128 однобитных сэмплов (F002)
+    pub audio_pattern: [u8; 16],
+    // Загружала ли программа звуковой буфер (F002); иначе звучит обычный сигнал
+    pub pattern_loaded: bool,
+    // Высота звука XO-CHIP (FX3A)
+    pub pitch: u8,
+    // Число выполненных инструкций
+    pub cycles: u64,
+    // Трассировка инструкций, по умолчанию выключена
+    pub tracer: Option<Tracer>,
+    // Профилировщик и покрытие, по умолчанию выключен
+    pub profiler: Option<Profiler>,
+    // Источник случайных чисел для CXKK, по умолчанию системный
+    pub rng: Box<dyn RandomSource>,
+    // Память, записанная последней инструкцией (для точек наблюдения)
+    pub last_write: Option<Range<usize>>,
+    // Адрес выполняемой инструкции (для отчёта о сбоях)
+    instruction_pc: u16,
+}
+
+impl Default for CPU {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl CPU {
     pub fn new() -> Self {
+        Self::with_variant(Variant::Classic)
+    }
+
+    pub fn with_variant(variant: Variant) -> Self {
         let mut cpu = CPU {
             registers: [0; 16],
             index_register: 0,
             program_counter: PROGRAM_START as u16,
             stack: [0; 16],
             stack_pointer: 0,
-            memory: [0; MEMORY_SIZE],
+            memory: vec![0; variant.memory_size()],
             delay_timer: 0,
             sound_timer: 0,
             display: Display::new(),
             keyboard: Keyboard::new(),
             waiting_for_key: None,
+            key_wait_pressed: None,
+            key_wait_held: [false; 16],
             running: true,
+            quirks: Quirks::default(),
+            vblank_wait: false,
+            rpl_flags: [0; 16],
+            variant,
+            audio_pattern: [0; 16],
+            pattern_loaded: false,
+            pitch: DEFAULT_PITCH,
+            cycles: 0,
+            tracer: None,
+            profiler: None,
+            rng: Box::new(ThreadRng),
+            last_write: None,
+            instruction_pc: PROGRAM_START as u16,
         };
         
         // Загружаем шрифты в память
@@ -51,6 +115,7 @@ impl CPU {
     fn load_fonts(&mut self) {
         let font_start = FONT_START;
         self.memory[font_start..font_start + FONT_SET.len()].copy_from_slice(&FONT_SET);
+        self.memory[BIG_FONT_START..BIG_FONT_START + BIG_FONT_SET.len()].copy_from_slice(&BIG_FONT_SET);
     }
 
     pub fn load_rom(&mut self, filename: &str) -> Result<(), String> {
@@ -58,69 +123,124 @@ impl CPU {
         let rom_data = fs::read(filename)
             .map_err(|e| format!("Failed to read ROM file: {}", e))?;
         
+        self.load_bytes(&rom_data)
+    }
+
+    pub fn load_bytes(&mut self, rom_data: &[u8]) -> Result<(), String> {
         // Проверяем что ROM помещается в память
-        if rom_data.len() > (MEMORY_SIZE - PROGRAM_START as usize) {
+        if rom_data.len() > (self.memory.len() - PROGRAM_START) {
             return Err("ROM too large to fit in memory".to_string());
         }
         
         // Копируем ROM в память начиная с 0x200
-        let start = PROGRAM_START as usize;
-        self.memory[start..start + rom_data.len()].copy_from_slice(&rom_data);
+        let start = PROGRAM_START;
+        self.memory[start..start + rom_data.len()].copy_from_slice(rom_data);
         
-        println!("ROM loaded: {} bytes", rom_data.len());
         Ok(())
     }
 
-    fn fetch(&mut self) -> u16 {
+    fn fetch(&mut self) -> Result<u16, Chip8Fault> {
         // Берем два байта из памяти
-        let higher_byte = self.memory[self.program_counter as usize] as u16;
-        let lower_byte = self.memory[(self.program_counter + 1) as usize] as u16;
+        let range = self.memory_range(self.program_counter as usize, 2)?;
+        let higher_byte = self.memory[range.start] as u16;
+        let lower_byte = self.memory[range.start + 1] as u16;
         
         // Объединяем в одну 16-битную инструкцию
         let opcode = (higher_byte << 8) | lower_byte;
         
-        println!("FETCH: PC={:04X}, Opcode={:04X}", self.program_counter, opcode);
-        
         // Переходим к следующей инструкции
-        self.program_counter += 2;
+        self.program_counter = self.program_counter.wrapping_add(2);
         
-        opcode
+        Ok(opcode)
+    }
+
+    /// Отметить для профилировщика, что инструкция читает память как данные
+    fn note_data_read(&mut self, range: &Range<usize>) {
+        if let Some(profiler) = &mut self.profiler {
+            profiler.data_read(range.clone());
+        }
+    }
+
+    /// Запомнить для точек наблюдения, какую память записала инструкция
+    fn note_data_write(&mut self, range: &Range<usize>) {
+        self.last_write = Some(range.clone());
+    }
+
+    /// Диапазон памяти `addr..addr + len` или сбой, если он выходит за её пределы
+    fn memory_range(&self, addr: usize, len: usize) -> Result<Range<usize>, Chip8Fault> {
+        if addr + len > self.memory.len() {
+            return Err(Chip8Fault::MemoryOutOfBounds { pc: self.instruction_pc, addr, len });
+        }
+        Ok(addr..addr + len)
     }
 
+    /// Тик таймеров, вызывается ровно раз за кадр (60 Гц)
     pub fn update_timers(&mut self) {
+        // Начался новый кадр - можно снова рисовать
+        self.vblank_wait = false;
+
         // Обновляем таймеры (60 Гц)
         if self.delay_timer > 0 {
             self.delay_timer -= 1;
         }
+        // Звук играет, пока таймер не ноль (см. audio::Synth)
         if self.sound_timer > 0 {
             self.sound_timer -= 1;
-            if self.sound_timer == 0 {
-                println!("BEEP! (Sound timer reached 0)");
-                // пока заглушка
-            }
         }
     }
     
-    pub fn cycle(&mut self) {
+ 
+    /// Выполнить одну инструкцию
+    ///
+    /// При сбое PC остаётся на сбойной инструкции, а решение (остановиться,
+    /// пропустить её или открыть отладчик) принимает фронтенд.
+    pub fn cycle(&mut self) -> Result<(), Chip8Fault> {
+        self.last_write = None;
         if !self.running {
-            return;
+            return Ok(());
         }
-        //
-        if self.waiting_for_key.is_some() {
-            return;
+        // FX0A: стоим, пока не дождёмся клавиши (таймеры при этом идут)
+        if self.poll_key_wait() {
+            return Ok(());
+        }
+        // Ждём начала кадра после отрисовки
+        if self.vblank_wait {
+            return Ok(());
         }
 
         // FETCH - получаем инструкцию
-        let opcode = self.fetch();
+        let pc = self.program_counter;
+        self.instruction_pc = pc;
+        let opcode = self.fetch()?;
         
+        // Снимок регистров нужен только если инструкция попадёт в трассу
+        let before = match &self.tracer {
+            Some(tracer) if tracer.filter.matches(pc, opcode) => Some(RegisterSnapshot::capture(self)),
+            _ => None,
+        };
+
         // EXECUTE - выполняем инструкцию
-        self.execute(opcode);
-        
-        // Обновляем таймеры
-        self.update_timers();
+        if let Err(fault) = self.execute(opcode) {
+            self.program_counter = pc;
+            return Err(fault);
+        }
+        self.cycles += 1;
+        if let Some(profiler) = &mut self.profiler {
+            profiler.instruction(pc, opcode);
+        }
+
+        if let Some(before) = before {
+            let after = RegisterSnapshot::capture(self);
+            if let Some(tracer) = &mut self.tracer {
+                tracer.trace(self.cycles, pc, opcode, &before, &after);
+            }
+        }
+
+        // Таймеры тикают раз в кадр (60 Гц) - это задача планировщика
+        Ok(())
     }
     
-    fn execute(&mut self, opcode: u16) {
+    fn execute(&mut self, opcode: u16) -> Result<(), Chip8Fault> {
         // Разбиваем опкод на части для удобства декодирования
         let nibbles = (
             (opcode & 0xF000) >> 12,  // Первый ниббл
@@ -135,35 +255,73 @@ impl CPU {
         let y = nibbles.2 as usize;    // Индекс регистра Y
         let n = nibbles.3 as usize;    // Полубайт (4 бита)
 
-        println!("Decoding: {:04X} -> {:X}{:X}{:X}{:X}", opcode, nibbles.0, nibbles.1, nibbles.2, nibbles.3);
-
         match nibbles {
             (0x0, 0x0, 0xE, 0x0) => self.op_00e0(),  // Очстить экран
-            (0x0, 0x0, 0xE, 0xE) => self.op_00ee(),  // Возврат из подпрограммы
+            (0x0, 0x0, 0xE, 0xE) => self.op_00ee()?,  // Возврат из подпрограммы
             (0x1, _, _, _) => self.op_1nnn(nnn),     // Прыжок на адрес NNN
-            (0x2, _, _, _) => self.op_2nnn(nnn),     // Вызов подпрограммы по адресу NNN
-            (0xB, _, _, _) => self.op_bnnn(nnn),     // Прыжок на адрес V0 + NNN
+            (0x2, _, _, _) => self.op_2nnn(nnn)?,     // Вызов подпрограммы по адресу NNN
+            (0xB, _, _, _) => self.op_bnnn(x, nnn),  // Прыжок на адрес V0 + NNN (или VX + XNN)
             (0x6, _, _, _) => self.op_6xkk(x, kk),   // Загрузить значение KK в регистр VX
             (0x7, _, _, _) => self.op_7xkk(x, kk),   // Прибавить KK к регистру VX
             (0xA, _, _, _) => self.op_annn(nnn),     // Установить индексный регистр I = NNN
-            (0xD, _, _, _) => self.op_dxyn(x, y, n), // Нарисовать спрайт в координатах (VX, VY) высотой N
+            (0xD, _, _, _) => self.op_dxyn(x, y, n)?, // Нарисовать спрайт в координатах (VX, VY) высотой N
             (0x3, _, _, _) => self.op_3xkk(x, kk),   // Пропустить следующую инструкцию если VX == KK
             (0x4, _, _, _) => self.op_4xkk(x, kk),   // Пропустить следующую инструкцию если VX != KK
             (0x5, _, _, 0x0) => self.op_5xy0(x, y),  // Пропустить следующую инструкцию если VX == VY
             (0x9, _, _, 0x0) => self.op_9xy0(x, y),  // Пропустить следующую инструкцию если VX != VY
+            (0x8, _, _, 0x0) => self.op_8xy0(x, y),  // VX = VY
+            (0x8, _, _, 0x1) => self.op_8xy1(x, y),  // VX = VX OR VY
+            (0x8, _, _, 0x2) => self.op_8xy2(x, y),  // VX = VX AND VY
+            (0x8, _, _, 0x3) => self.op_8xy3(x, y),  // VX = VX XOR VY
+            (0x8, _, _, 0x4) => self.op_8xy4(x, y),  // VX = VX + VY, VF = перенос
+            (0x8, _, _, 0x5) => self.op_8xy5(x, y),  // VX = VX - VY, VF = нет заёма
+            (0x8, _, _, 0x6) => self.op_8xy6(x, y),  // VX = VX >> 1, VF = выдвинутый бит
+            (0x8, _, _, 0x7) => self.op_8xy7(x, y),  // VX = VY - VX, VF = нет заёма
+            (0x8, _, _, 0xE) => self.op_8xye(x, y),  // VX = VX << 1, VF = выдвинутый бит
+            (0xC, _, _, _) => self.op_cxkk(x, kk),   // VX = случайный байт AND KK
             (0xE, _, 0x9, 0xE) => self.op_ex9e(x),   // Пропустить следующую инструкцию если нажата клавиша из VX
             (0xE, _, 0xA, 0x1) => self.op_exa1(x),   // Пропустить следующую инструкцию если НЕ нажата клавиша из VX
             (0xF, _, 0x0, 0x7) => self.op_fx07(x),   // Загрузить значение таймера задержки в VX
             (0xF, _, 0x1, 0x5) => self.op_fx15(x),   // Установить таймер задержки = VX
             (0xF, _, 0x1, 0x8) => self.op_fx18(x),   // Установить звуковой таймер = VX
+            (0xF, _, 0x1, 0xE) => self.op_fx1e(x),   // I = I + VX
             (0xF, _, 0x2, 0x9) => self.op_fx29(x),   // Установить I на адрес шрифта символа из VX
-            (0xF, _, 0x3, 0x3) => self.op_fx33(x),   // Преобразовать число из VX в BCD и сохранить в память
-            (0xF, _, 0x5, 0x5) => self.op_fx55(x),   // Сохранить регистры V0-VX в память начиная с I
-            (0xF, _, 0x6, 0x5) => self.op_fx65(x),   // Загрузить регистры V0-VX из памяти начиная с I
+            (0xF, _, 0x3, 0x3) => self.op_fx33(x)?,   // Преобразовать число из VX в BCD и сохранить в память
+            (0xF, _, 0x5, 0x5) => self.op_fx55(x)?,   // Сохранить регистры V0-VX в память начиная с I
+            (0xF, _, 0x6, 0x5) => self.op_fx65(x)?,   // Загрузить регистры V0-VX из памяти начиная с I
             (0xF, _, 0x0, 0xA) => self.op_fx0a(x),   // Ожидание нажатия клавиши
-            (0x0, 0x0, 0xF, 0xD) => self.op_00fd(),  // EXIT - остановка программы (кастом)
-            _ => println!("Unknown opcode: {:04X}", opcode),
+            (0x0, 0x0, 0xF, 0xD) => self.op_00fd(),  // EXIT - остановка программы (SUPER-CHIP)
+            (0x0, 0x0, 0xC, _) => self.op_00cn(n),   // Прокрутка вниз на N строк (SUPER-CHIP)
+            (0x0, 0x0, 0xF, 0xB) => self.op_00fb(),  // Прокрутка вправо на 4 пикселя (SUPER-CHIP)
+            (0x0, 0x0, 0xF, 0xC) => self.op_00fc(),  // Прокрутка влево на 4 пикселя (SUPER-CHIP)
+            (0x0, 0x0, 0xF, 0xE) => self.op_00fe(),  // Низкое разрешение 64x32 (SUPER-CHIP)
+            (0x0, 0x0, 0xF, 0xF) => self.op_00ff(),  // Высокое разрешение 128x64 (SUPER-CHIP)
+            (0x0, 0x0, 0xD, _) if self.is_xo() => self.op_00dn(n),           // Прокрутка вверх на N строк (XO-CHIP)
+            (0x5, _, _, 0x2) if self.is_xo() => self.op_5xy2(x, y)?,          // Сохранить VX..VY в память с I (XO-CHIP)
+            (0x5, _, _, 0x3) if self.is_xo() => self.op_5xy3(x, y)?,          // Загрузить VX..VY из памяти с I (XO-CHIP)
+            (0xF, 0x0, 0x0, 0x0) if self.is_xo() => self.op_f000()?,          // I = следующее 16-битное слово (XO-CHIP)
+            (0xF, _, 0x0, 0x1) if self.is_xo() => self.op_fn01(x as u8),     // Выбор плоскостей N (XO-CHIP)
+            (0xF, 0x0, 0x0, 0x2) if self.is_xo() => self.op_f002()?,          // Загрузить звуковой буфер с I (XO-CHIP)
+            (0xF, _, 0x3, 0xA) if self.is_xo() => self.op_fx3a(x),           // Высота звука = VX (XO-CHIP)
+            (0xF, _, 0x3, 0x0) => self.op_fx30(x),   // I = адрес большой цифры из VX (SUPER-CHIP)
+            (0xF, _, 0x7, 0x5) => self.op_fx75(x),   // Сохранить V0-VX во флаги RPL (SUPER-CHIP)
+            (0xF, _, 0x8, 0x5) => self.op_fx85(x),   // Загрузить V0-VX из флагов RPL (SUPER-CHIP)
+            _ => return Err(Chip8Fault::UnknownOpcode { pc: self.instruction_pc, opcode }),
         }
+        Ok(())
+    }
+
+    fn is_xo(&self) -> bool {
+        self.variant == Variant::XoChip
+    }
+
+    /// Пропуск следующей инструкции. В XO-CHIP F000 NNNN занимает 4 байта.
+    fn skip_next(&mut self) {
+        let pc = self.program_counter as usize;
+        let long = self.is_xo()
+            && self.memory.get(pc) == Some(&0xF0)
+            && self.memory.get(pc + 1) == Some(&0x00);
+        self.program_counter = self.program_counter.wrapping_add(if long { 4 } else { 2 });
     }
 
     // === ИНСТРУКЦИИ === //
@@ -174,46 +332,44 @@ impl CPU {
     }
 
     /// 00EE - Возврат из подпрограммы
-    fn op_00ee(&mut self) {
+    fn op_00ee(&mut self) -> Result<(), Chip8Fault> {
         if self.stack_pointer == 0 {
-            println!("Stack underflow!");
-            return;
+            return Err(Chip8Fault::StackUnderflow { pc: self.instruction_pc });
         }
         
         self.stack_pointer -= 1;
         self.program_counter = self.stack[self.stack_pointer as usize];
-        println!("Return to {:04X}", self.program_counter);
+        Ok(())
     }
 
     /// 1NNN - Прыжок на адрес NNN
     fn op_1nnn(&mut self, nnn: u16) {
-        println!("Jump to {:04X}", nnn);
         self.program_counter = nnn;
     }
 
     /// Вызов подпрограммы по адресу NNN
-    fn op_2nnn(&mut self, nnn: u16) {
+    fn op_2nnn(&mut self, nnn: u16) -> Result<(), Chip8Fault> {
         if self.stack_pointer >= 16 {
-            println!("Stack overflow!");
-            return;
+            return Err(Chip8Fault::StackOverflow { pc: self.instruction_pc });
         }
         
         self.stack[self.stack_pointer as usize] = self.program_counter;
         self.stack_pointer += 1;
         self.program_counter = nnn;
-        println!("Call subroutine at {:04X}", nnn);
+        Ok(())
     }
 
     /// BNNN - Прыжок на адрес V0 + NNN
-    fn op_bnnn(&mut self, nnn: u16) {
-        let new_pc = (self.registers[0] as u16) + nnn;
+    ///
+    /// С quirk `jump_uses_vx` это BXNN - прыжок на VX + XNN.
+    fn op_bnnn(&mut self, x: usize, nnn: u16) {
+        let reg = if self.quirks.jump_uses_vx { x } else { 0 };
+        let new_pc = (self.registers[reg] as u16) + nnn;
         self.program_counter = new_pc;
-        println!("Jump to V0 + {:03X} = {:04X}", nnn, new_pc);
     }
 
     /// 6XKK - Загрузить значение KK в регистр VX
     fn op_6xkk(&mut self, x: usize, kk: u8) {
-        println!("Set V[{}] = {:02X}", x, kk);
         self.registers[x] = kk;
     }
 
@@ -221,155 +377,1223 @@ impl CPU {
     fn op_7xkk(&mut self, x: usize, kk: u8) {
         let current = self.registers[x];
         let result = current.wrapping_add(kk);
-        println!("V[{}] = {} + {} = {}", x, current, kk, result);
         self.registers[x] = result;
     }
 
     /// ANNN - Установить индексный регистр I = NNN
     fn op_annn(&mut self, nnn: u16) {
-        println!("Set I = {:04X}", nnn);
         self.index_register = nnn;
     }
 
     /// DXYN - Нарисовать спрайт в координатах (VX, VY) высотой N
-    fn op_dxyn(&mut self, x: usize, y: usize, n: usize) {
+    ///
+    /// DXY0 рисует спрайт 16x16 из 32 байт (SUPER-CHIP).
+    fn op_dxyn(&mut self, x: usize, y: usize, n: usize) -> Result<(), Chip8Fault> {
         let x_coord = self.registers[x];
         let y_coord = self.registers[y];
-        let height = n as u8;
+        let large = n == 0;
+        // Для каждой выбранной плоскости свой спрайт, идут подряд
+        let length = if large { 32 } else { n } * self.display.plane_count();
         
         // Читаем спрайт из памяти
-        let sprite = &self.memory[
-            self.index_register as usize..self.index_register as usize + height as usize
-        ];
-        
-        println!("Draw: ({}, {}), height: {}, sprite: {:?}", 
-                 x_coord, y_coord, height, sprite);
+        let range = self.memory_range(self.index_register as usize, length)?;
+        self.note_data_read(&range);
+        let sprite = &self.memory[range];
         
         // Отрисовываем спрайт
-        let collision = self.display.draw_sprite(x_coord, y_coord, sprite);
+        let clip = self.quirks.clip_sprites;
+        let collision = if large {
+            self.display.draw_large_sprite(x_coord, y_coord, sprite, clip)
+        } else {
+            self.display.draw_sprite(x_coord, y_coord, sprite, clip)
+        };
         
         // Устанавливаем флаг коллизии в VF
         self.registers[0xF] = if collision { 1 } else { 0 };
+
+        if self.quirks.display_wait {
+            self.vblank_wait = true;
+        }
         
         // Показываем экран в консоли для отладки
         //self.display.debug_print();
+        Ok(())
     }
 
     /// 3XKK - Пропустить следующую инструкцию если VX == KK
     fn op_3xkk(&mut self, x: usize, kk: u8) {
         if self.registers[x] == kk {
-            self.program_counter += 2;
+            self.skip_next();
         }
-        println!("Skip if V[{}] == {:02X} -> {}", x, kk, self.registers[x] == kk);
     }
 
     /// 4XKK - Пропустить следующую инструкцию если VX != KK  
     fn op_4xkk(&mut self, x: usize, kk: u8) {
         if self.registers[x] != kk {
-            self.program_counter += 2;
+            self.skip_next();
         }
-        println!("Skip if V[{}] != {:02X} -> {}", x, kk, self.registers[x] != kk);
     }
 
     /// 5XY0 - Пропустить следующую инструкцию если VX == VY
     fn op_5xy0(&mut self, x: usize, y: usize) {
         if self.registers[x] == self.registers[y] {
-            self.program_counter += 2;
+            self.skip_next();
         }
-        println!("Skip if V[{}] == V[{}] -> {}", x, y, self.registers[x] == self.registers[y]);
     }
 
     /// 9XY0 - Пропустить следующую инструкцию если VX != VY
     fn op_9xy0(&mut self, x: usize, y: usize) {
         if self.registers[x] != self.registers[y] {
-            self.program_counter += 2;
+            self.skip_next();
+        }
+    }
+
+    /// 8XY0 - VX = VY
+    fn op_8xy0(&mut self, x: usize, y: usize) {
+        self.registers[x] = self.registers[y];
+    }
+
+    /// 8XY1 - VX = VX OR VY
+    fn op_8xy1(&mut self, x: usize, y: usize) {
+        self.registers[x] |= self.registers[y];
+        self.reset_vf();
+    }
+
+    /// 8XY2 - VX = VX AND VY
+    fn op_8xy2(&mut self, x: usize, y: usize) {
+        self.registers[x] &= self.registers[y];
+        self.reset_vf();
+    }
+
+    /// 8XY3 - VX = VX XOR VY
+    fn op_8xy3(&mut self, x: usize, y: usize) {
+        self.registers[x] ^= self.registers[y];
+        self.reset_vf();
+    }
+
+    /// Quirk `vf_reset`: логические операции обнуляют VF
+    fn reset_vf(&mut self) {
+        if self.quirks.vf_reset {
+            self.registers[0xF] = 0;
+        }
+    }
+
+    /// Источник для сдвигов 8XY6/8XYE с учётом quirk `shift_uses_vy`
+    fn shift_source(&self, x: usize, y: usize) -> u8 {
+        if self.quirks.shift_uses_vy {
+            self.registers[y]
+        } else {
+            self.registers[x]
         }
-        println!("Skip if V[{}] != V[{}] -> {}", x, y, self.registers[x] != self.registers[y]);
+    }
+
+    /// 8XY4 - VX = VX + VY, VF = 1 при переносе
+    ///
+    /// Флаг пишется после результата, поэтому для X = F в VF остаётся флаг.
+    fn op_8xy4(&mut self, x: usize, y: usize) {
+        let (result, carry) = self.registers[x].overflowing_add(self.registers[y]);
+        self.registers[x] = result;
+        self.registers[0xF] = carry as u8;
+    }
+
+    /// 8XY5 - VX = VX - VY, VF = 1 если не было заёма
+    fn op_8xy5(&mut self, x: usize, y: usize) {
+        let (result, borrow) = self.registers[x].overflowing_sub(self.registers[y]);
+        self.registers[x] = result;
+        self.registers[0xF] = !borrow as u8;
+    }
+
+    /// 8XY6 - VX = VX >> 1, VF = младший бит до сдвига
+    fn op_8xy6(&mut self, x: usize, y: usize) {
+        let value = self.shift_source(x, y);
+        self.registers[x] = value >> 1;
+        self.registers[0xF] = value & 0x01;
+    }
+
+    /// 8XY7 - VX = VY - VX, VF = 1 если не было заёма
+    fn op_8xy7(&mut self, x: usize, y: usize) {
+        let (result, borrow) = self.registers[y].overflowing_sub(self.registers[x]);
+        self.registers[x] = result;
+        self.registers[0xF] = !borrow as u8;
+    }
+
+    /// 8XYE - VX = VX << 1, VF = старший бит до сдвига
+    fn op_8xye(&mut self, x: usize, y: usize) {
+        let value = self.shift_source(x, y);
+        self.registers[x] = value << 1;
+        self.registers[0xF] = value >> 7;
+    }
+
+    /// CXKK - VX = случайный байт AND KK
+    fn op_cxkk(&mut self, x: usize, kk: u8) {
+        self.registers[x] = self.rng.next_byte() & kk;
     }
 
     /// EX9E - Пропустить следующую инструкцию если нажата клавиша из VX
     fn op_ex9e(&mut self, x: usize) {
         let key = self.registers[x] & 0x0F;
         if self.keyboard.is_key_pressed(key) {
-            self.program_counter += 2;
+            self.skip_next();
         }
-        println!("Skip if key {} pressed -> {}", key, self.keyboard.is_key_pressed(key));
     }
 
     /// EXA1 - Пропустить следующую инструкцию если НЕ нажата клавиша из VX
     fn op_exa1(&mut self, x: usize) {
         let key = self.registers[x] & 0x0F;
         if !self.keyboard.is_key_pressed(key) {
-            self.program_counter += 2;
+            self.skip_next();
         }
-        println!("Skip if key {} not pressed -> {}", key, !self.keyboard.is_key_pressed(key));
     }
 
     /// FX07 - Загрузить значение таймера задержки в VX
     fn op_fx07(&mut self, x: usize) {
         self.registers[x] = self.delay_timer;
-        println!("V[{}] = delay_timer = {}", x, self.delay_timer);
     }
 
     /// FX15 - Установить таймер задержки = VX
     fn op_fx15(&mut self, x: usize) {
         self.delay_timer = self.registers[x];
-        println!("delay_timer = V[{}] = {}", x, self.delay_timer);
     }
 
     /// FX18 - Установить звуковой таймер = VX
     fn op_fx18(&mut self, x: usize) {
         self.sound_timer = self.registers[x];
-        println!("sound_timer = V[{}] = {}", x, self.sound_timer);
+    }
+
+    /// FX1E - I = I + VX
+    fn op_fx1e(&mut self, x: usize) {
+        self.index_register = self.index_register.wrapping_add(self.registers[x] as u16);
     }
 
     /// FX29 - Установить I на адрес шрифта символа из VX
     fn op_fx29(&mut self, x: usize) {
         let digit = self.registers[x] & 0x0F; // Берем только младшие 4 бита
         self.index_register = (FONT_START as u16) + (digit as u16 * 5);
-        println!("Set I to font character {} -> {:04X}", digit, self.index_register);
     }
 
     /// FX33 - Преобразовать число из VX в BCD и сохранить в память
-    fn op_fx33(&mut self, x: usize) {
+    fn op_fx33(&mut self, x: usize) -> Result<(), Chip8Fault> {
         let value = self.registers[x];
+        let range = self.memory_range(self.index_register as usize, 3)?;
+        self.note_data_write(&range);
+        let start = range.start;
         
         // Разбиваем на сотни, десятки, единицы
-        self.memory[self.index_register as usize] = value / 100;
-        self.memory[self.index_register as usize + 1] = (value % 100) / 10;
-        self.memory[self.index_register as usize + 2] = value % 10;
-        
-        println!("BCD of {} = [{}, {}, {}]", 
-                 value, 
-                 self.memory[self.index_register as usize],
-                 self.memory[self.index_register as usize + 1],
-                 self.memory[self.index_register as usize + 2]);
+        self.memory[start] = value / 100;
+        self.memory[start + 1] = (value % 100) / 10;
+        self.memory[start + 2] = value % 10;
+        Ok(())
     }
 
     /// FX55 - Сохранить регистры V0-VX в память начиная с I
-    fn op_fx55(&mut self, x: usize) {
-        for i in 0..=x {
-            self.memory[self.index_register as usize + i] = self.registers[i];
-        }
-        println!("Store V0..V[{}] to memory at {:04X}", x, self.index_register);
+    fn op_fx55(&mut self, x: usize) -> Result<(), Chip8Fault> {
+        let range = self.memory_range(self.index_register as usize, x + 1)?;
+        self.note_data_write(&range);
+        self.memory[range].copy_from_slice(&self.registers[..=x]);
+        self.increment_index(x);
+        Ok(())
     }
 
     /// FX65 - Загрузить регистры V0-VX из памяти начиная с I
-    fn op_fx65(&mut self, x: usize) {
-        for i in 0..=x {
-            self.registers[i] = self.memory[self.index_register as usize + i];
-        }
-        println!("Load V0..V[{}] from memory at {:04X}", x, self.index_register);
+    fn op_fx65(&mut self, x: usize) -> Result<(), Chip8Fault> {
+        let range = self.memory_range(self.index_register as usize, x + 1)?;
+        self.note_data_read(&range);
+        self.registers[..=x].copy_from_slice(&self.memory[range]);
+        self.increment_index(x);
+        Ok(())
+    }
+
+    /// Сдвиг I после FX55/FX65 с учётом quirk `index_increment`
+    fn increment_index(&mut self, x: usize) {
+        let step = match self.quirks.index_increment {
+            IndexIncrement::Unchanged => 0,
+            IndexIncrement::ByX => x as u16,
+            IndexIncrement::ByXPlusOne => x as u16 + 1,
+        };
+        self.index_register = self.index_register.wrapping_add(step);
     }
 
     /// FX0A - Ожидание нажатия клавиши
     fn op_fx0a(&mut self, x: usize) {
-        println!("Waiting for key press -> V[{}]", x);
         self.waiting_for_key = Some(x);
+        self.key_wait_pressed = None;
+        // Клавиши, зажатые до FX0A, не считаются, пока их не отпустят
+        self.key_wait_held = self.keyboard.keys();
+    }
+
+    /// Проверка клавиатуры во время FX0A. Засчитывается только клавиша,
+    /// нажатая после начала ожидания; с quirk `key_wait_release` ожидание
+    /// заканчивается, когда её отпустят (как на COSMAC VIP).
+    /// Возвращает true, пока ожидание продолжается
+    fn poll_key_wait(&mut self) -> bool {
+        let Some(x) = self.waiting_for_key else {
+            return false;
+        };
+        let keys = self.keyboard.keys();
+
+        let done = match self.key_wait_pressed {
+            Some(key) => (!keys[key as usize]).then_some(key),
+            None => match (0..16u8).find(|&k| keys[k as usize] && !self.key_wait_held[k as usize]) {
+                Some(key) if self.quirks.key_wait_release => {
+                    self.key_wait_pressed = Some(key);
+                    None
+                }
+                pressed => pressed,
+            },
+        };
+        self.key_wait_held = keys;
+
+        if let Some(key) = done {
+            self.registers[x] = key;
+            self.waiting_for_key = None;
+            self.key_wait_pressed = None;
+        }
+        self.waiting_for_key.is_some()
     }
 
+    /// 00FD - Остановка программы
     fn op_00fd(&mut self) {
-        println!("Program exited via EXIT instruction");
         self.running = false;    
     }
-}
\ No newline at end of file
+
+    /// 00CN - Прокрутка экрана вниз на N строк
+    fn op_00cn(&mut self, n: usize) {
+        self.display.scroll_down(n);
+    }
+
+    /// 00FB - Прокрутка экрана вправо на 4 пикселя
+    fn op_00fb(&mut self) {
+        self.display.scroll_right(4);
+    }
+
+    /// 00FC - Прокрутка экрана влево на 4 пикселя
+    fn op_00fc(&mut self) {
+        self.display.scroll_left(4);
+    }
+
+    /// 00FE - Низкое разрешение 64x32
+    fn op_00fe(&mut self) {
+        self.display.set_hires(false);
+    }
+
+    /// 00FF - Высокое разрешение 128x64
+    fn op_00ff(&mut self) {
+        self.display.set_hires(true);
+    }
+
+    /// FX30 - Установить I на адрес большого символа из VX
+    fn op_fx30(&mut self, x: usize) {
+        let digit = self.registers[x] & 0x0F;
+        self.index_register = (BIG_FONT_START as u16) + (digit as u16 * 10);
+    }
+
+    /// 00DN - Прокрутка экрана вверх на N строк
+    fn op_00dn(&mut self, n: usize) {
+        self.display.scroll_up(n);
+    }
+
+    /// Номера регистров от X до Y включительно, в обратном порядке если X > Y
+    fn register_range(x: usize, y: usize) -> Vec<usize> {
+        if x <= y {
+            (x..=y).collect()
+        } else {
+            (y..=x).rev().collect()
+        }
+    }
+
+    /// 5XY2 - Сохранить регистры VX..VY в память начиная с I, I не меняется
+    fn op_5xy2(&mut self, x: usize, y: usize) -> Result<(), Chip8Fault> {
+        let registers = Self::register_range(x, y);
+        let range = self.memory_range(self.index_register as usize, registers.len())?;
+        self.note_data_write(&range);
+        let start = range.start;
+        for (offset, reg) in registers.into_iter().enumerate() {
+            self.memory[start + offset] = self.registers[reg];
+        }
+        Ok(())
+    }
+
+    /// 5XY3 - Загрузить регистры VX..VY из памяти начиная с I, I не меняется
+    fn op_5xy3(&mut self, x: usize, y: usize) -> Result<(), Chip8Fault> {
+        let registers = Self::register_range(x, y);
+        let range = self.memory_range(self.index_register as usize, registers.len())?;
+        self.note_data_read(&range);
+        let start = range.start;
+        for (offset, reg) in registers.into_iter().enumerate() {
+            self.registers[reg] = self.memory[start + offset];
+        }
+        Ok(())
+    }
+
+    /// F000 NNNN - Загрузить в I 16-битный адрес из следующего слова
+    fn op_f000(&mut self) -> Result<(), Chip8Fault> {
+        let pc = self.memory_range(self.program_counter as usize, 2)?.start;
+        self.index_register = ((self.memory[pc] as u16) << 8) | self.memory[pc + 1] as u16;
+        self.program_counter = self.program_counter.wrapping_add(2);
+        Ok(())
+    }
+
+    /// FN01 - Выбрать плоскости для рисования по маске N
+    fn op_fn01(&mut self, n: u8) {
+        self.display.planes = n;
+    }
+
+    /// F002 - Загрузить 16 байт звукового буфера из памяти начиная с I
+    fn op_f002(&mut self) -> Result<(), Chip8Fault> {
+        let range = self.memory_range(self.index_register as usize, 16)?;
+        self.note_data_read(&range);
+        self.audio_pattern.copy_from_slice(&self.memory[range]);
+        self.pattern_loaded = true;
+        Ok(())
+    }
+
+    /// FX3A - Установить высоту звука = VX
+    fn op_fx3a(&mut self, x: usize) {
+        self.pitch = self.registers[x];
+    }
+
+    /// FX75 - Сохранить регистры V0-VX во флаги RPL
+    fn op_fx75(&mut self, x: usize) {
+        self.rpl_flags[..=x].copy_from_slice(&self.registers[..=x]);
+    }
+
+    /// FX85 - Загрузить регистры V0-VX из флагов RPL
+    fn op_fx85(&mut self, x: usize) {
+        self.registers[..=x].copy_from_slice(&self.rpl_flags[..=x]);
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::ScriptedRng;
+
+    /// Процессор нужного варианта с программой из опкодов, записанной с 0x200
+    fn cpu_with(variant: Variant, program: &[u16]) -> CPU {
+        let mut cpu = CPU::with_variant(variant);
+        for (i, opcode) in program.iter().enumerate() {
+            let addr = PROGRAM_START + i * 2;
+            cpu.memory[addr] = (opcode >> 8) as u8;
+            cpu.memory[addr + 1] = (opcode & 0xFF) as u8;
+        }
+        cpu
+    }
+
+    /// Выполнить одну инструкцию из памяти
+    fn run(program: &[u16]) -> CPU {
+        let mut cpu = cpu_with(Variant::Classic, program);
+        cpu.cycle().unwrap();
+        cpu
+    }
+
+    #[test]
+    fn op_00e0_clears_screen() {
+        let mut cpu = cpu_with(Variant::Classic, &[0x00E0]);
+        cpu.display.pixels[3][7] = 1;
+        cpu.cycle().unwrap();
+        assert!(cpu.display.pixels.iter().flatten().all(|&p| p == 0));
+        assert_eq!(cpu.program_counter, 0x202);
+    }
+
+    #[test]
+    fn op_2nnn_and_00ee_call_and_return() {
+        let mut cpu = cpu_with(Variant::Classic, &[0x2300]);
+        cpu.memory[0x300] = 0x00;
+        cpu.memory[0x301] = 0xEE;
+
+        cpu.cycle().unwrap();
+        assert_eq!(cpu.program_counter, 0x300);
+        assert_eq!(cpu.stack_pointer, 1);
+        assert_eq!(cpu.stack[0], 0x202);
+
+        cpu.cycle().unwrap();
+        assert_eq!(cpu.program_counter, 0x202);
+        assert_eq!(cpu.stack_pointer, 0);
+    }
+
+    #[test]
+    fn op_1nnn_jumps() {
+        let cpu = run(&[0x1ABC]);
+        assert_eq!(cpu.program_counter, 0x0ABC);
+    }
+
+    #[test]
+    fn op_3xkk_skips_when_equal() {
+        let mut cpu = cpu_with(Variant::Classic, &[0x3342]);
+        cpu.registers[3] = 0x42;
+        cpu.cycle().unwrap();
+        assert_eq!(cpu.program_counter, 0x204);
+
+        let mut cpu = cpu_with(Variant::Classic, &[0x3342]);
+        cpu.registers[3] = 0x41;
+        cpu.cycle().unwrap();
+        assert_eq!(cpu.program_counter, 0x202);
+    }
+
+    #[test]
+    fn op_4xkk_skips_when_not_equal() {
+        let mut cpu = cpu_with(Variant::Classic, &[0x4342]);
+        cpu.registers[3] = 0x41;
+        cpu.cycle().unwrap();
+        assert_eq!(cpu.program_counter, 0x204);
+
+        let mut cpu = cpu_with(Variant::Classic, &[0x4342]);
+        cpu.registers[3] = 0x42;
+        cpu.cycle().unwrap();
+        assert_eq!(cpu.program_counter, 0x202);
+    }
+
+    #[test]
+    fn op_5xy0_skips_when_registers_equal() {
+        let mut cpu = cpu_with(Variant::Classic, &[0x5120]);
+        cpu.registers[1] = 7;
+        cpu.registers[2] = 7;
+        cpu.cycle().unwrap();
+        assert_eq!(cpu.program_counter, 0x204);
+
+        let mut cpu = cpu_with(Variant::Classic, &[0x5120]);
+        cpu.registers[1] = 7;
+        cpu.registers[2] = 8;
+        cpu.cycle().unwrap();
+        assert_eq!(cpu.program_counter, 0x202);
+    }
+
+    #[test]
+    fn op_6xkk_loads_byte() {
+        let cpu = run(&[0x6A5C]);
+        assert_eq!(cpu.registers[0xA], 0x5C);
+    }
+
+    #[test]
+    fn op_7xkk_adds_without_touching_vf() {
+        let mut cpu = cpu_with(Variant::Classic, &[0x7510]);
+        cpu.registers[5] = 0xF8;
+        cpu.cycle().unwrap();
+        assert_eq!(cpu.registers[5], 0x08);
+        assert_eq!(cpu.registers[0xF], 0);
+    }
+
+    #[test]
+    fn op_8xy0_copies_register() {
+        let mut cpu = cpu_with(Variant::Classic, &[0x8120]);
+        cpu.registers[2] = 0x33;
+        cpu.cycle().unwrap();
+        assert_eq!(cpu.registers[1], 0x33);
+        assert_eq!(cpu.registers[2], 0x33);
+    }
+
+    #[test]
+    fn op_8xy1_or() {
+        let mut cpu = cpu_with(Variant::Classic, &[0x8121]);
+        cpu.registers[1] = 0b1100_0000;
+        cpu.registers[2] = 0b0000_0011;
+        cpu.cycle().unwrap();
+        assert_eq!(cpu.registers[1], 0b1100_0011);
+    }
+
+    #[test]
+    fn op_8xy2_and() {
+        let mut cpu = cpu_with(Variant::Classic, &[0x8122]);
+        cpu.registers[1] = 0b1111_0000;
+        cpu.registers[2] = 0b1010_1010;
+        cpu.cycle().unwrap();
+        assert_eq!(cpu.registers[1], 0b1010_0000);
+    }
+
+    #[test]
+    fn op_8xy3_xor() {
+        let mut cpu = cpu_with(Variant::Classic, &[0x8123]);
+        cpu.registers[1] = 0b1111_0000;
+        cpu.registers[2] = 0b1010_1010;
+        cpu.cycle().unwrap();
+        assert_eq!(cpu.registers[1], 0b0101_1010);
+    }
+
+    #[test]
+    fn op_8xy4_add_with_carry() {
+        let mut cpu = cpu_with(Variant::Classic, &[0x8124]);
+        cpu.registers[1] = 0xF0;
+        cpu.registers[2] = 0x20;
+        cpu.cycle().unwrap();
+        assert_eq!(cpu.registers[1], 0x10);
+        assert_eq!(cpu.registers[0xF], 1);
+
+        let mut cpu = cpu_with(Variant::Classic, &[0x8124]);
+        cpu.registers[1] = 0x10;
+        cpu.registers[2] = 0x20;
+        cpu.registers[0xF] = 1;
+        cpu.cycle().unwrap();
+        assert_eq!(cpu.registers[1], 0x30);
+        assert_eq!(cpu.registers[0xF], 0);
+    }
+
+    #[test]
+    fn op_8xy4_flag_overrides_result_in_vf() {
+        let mut cpu = cpu_with(Variant::Classic, &[0x8F14]);
+        cpu.registers[0xF] = 0xFF;
+        cpu.registers[1] = 0x02;
+        cpu.cycle().unwrap();
+        assert_eq!(cpu.registers[0xF], 1);
+    }
+
+    #[test]
+    fn op_8xy5_sub_sets_not_borrow() {
+        let mut cpu = cpu_with(Variant::Classic, &[0x8125]);
+        cpu.registers[1] = 0x30;
+        cpu.registers[2] = 0x10;
+        cpu.cycle().unwrap();
+        assert_eq!(cpu.registers[1], 0x20);
+        assert_eq!(cpu.registers[0xF], 1);
+
+        let mut cpu = cpu_with(Variant::Classic, &[0x8125]);
+        cpu.registers[1] = 0x10;
+        cpu.registers[2] = 0x30;
+        cpu.cycle().unwrap();
+        assert_eq!(cpu.registers[1], 0xE0);
+        assert_eq!(cpu.registers[0xF], 0);
+    }
+
+    #[test]
+    fn op_8xy5_equal_operands_do_not_borrow() {
+        let mut cpu = cpu_with(Variant::Classic, &[0x8125]);
+        cpu.registers[1] = 0x42;
+        cpu.registers[2] = 0x42;
+        cpu.cycle().unwrap();
+        assert_eq!(cpu.registers[1], 0);
+        assert_eq!(cpu.registers[0xF], 1);
+    }
+
+    #[test]
+    fn op_8xy6_shift_right() {
+        let mut cpu = cpu_with(Variant::Classic, &[0x8126]);
+        cpu.registers[1] = 0b0000_0101;
+        cpu.cycle().unwrap();
+        assert_eq!(cpu.registers[1], 0b0000_0010);
+        assert_eq!(cpu.registers[0xF], 1);
+
+        let mut cpu = cpu_with(Variant::Classic, &[0x8126]);
+        cpu.registers[1] = 0b0000_0100;
+        cpu.cycle().unwrap();
+        assert_eq!(cpu.registers[1], 0b0000_0010);
+        assert_eq!(cpu.registers[0xF], 0);
+    }
+
+    #[test]
+    fn op_8xy7_subn_sets_not_borrow() {
+        let mut cpu = cpu_with(Variant::Classic, &[0x8127]);
+        cpu.registers[1] = 0x10;
+        cpu.registers[2] = 0x30;
+        cpu.cycle().unwrap();
+        assert_eq!(cpu.registers[1], 0x20);
+        assert_eq!(cpu.registers[0xF], 1);
+
+        let mut cpu = cpu_with(Variant::Classic, &[0x8127]);
+        cpu.registers[1] = 0x30;
+        cpu.registers[2] = 0x10;
+        cpu.cycle().unwrap();
+        assert_eq!(cpu.registers[1], 0xE0);
+        assert_eq!(cpu.registers[0xF], 0);
+    }
+
+    #[test]
+    fn op_8xye_shift_left() {
+        let mut cpu = cpu_with(Variant::Classic, &[0x812E]);
+        cpu.registers[1] = 0b1000_0001;
+        cpu.cycle().unwrap();
+        assert_eq!(cpu.registers[1], 0b0000_0010);
+        assert_eq!(cpu.registers[0xF], 1);
+
+        let mut cpu = cpu_with(Variant::Classic, &[0x812E]);
+        cpu.registers[1] = 0b0100_0000;
+        cpu.cycle().unwrap();
+        assert_eq!(cpu.registers[1], 0b1000_0000);
+        assert_eq!(cpu.registers[0xF], 0);
+    }
+
+    #[test]
+    fn op_8xyn_shift_flag_wins_in_vf() {
+        let mut cpu = cpu_with(Variant::Classic, &[0x8F06]);
+        cpu.registers[0xF] = 0b0000_0010;
+        cpu.cycle().unwrap();
+        assert_eq!(cpu.registers[0xF], 0);
+    }
+
+    #[test]
+    fn op_9xy0_skips_when_registers_differ() {
+        let mut cpu = cpu_with(Variant::Classic, &[0x9120]);
+        cpu.registers[1] = 7;
+        cpu.registers[2] = 8;
+        cpu.cycle().unwrap();
+        assert_eq!(cpu.program_counter, 0x204);
+
+        let mut cpu = cpu_with(Variant::Classic, &[0x9120]);
+        cpu.registers[1] = 7;
+        cpu.registers[2] = 7;
+        cpu.cycle().unwrap();
+        assert_eq!(cpu.program_counter, 0x202);
+    }
+
+    #[test]
+    fn op_annn_sets_index() {
+        let cpu = run(&[0xA123]);
+        assert_eq!(cpu.index_register, 0x123);
+    }
+
+    #[test]
+    fn op_bnnn_jumps_with_v0_offset() {
+        let mut cpu = cpu_with(Variant::Classic, &[0xB300]);
+        cpu.registers[0] = 0x10;
+        cpu.cycle().unwrap();
+        assert_eq!(cpu.program_counter, 0x310);
+    }
+
+    #[test]
+    fn op_cxkk_masks_random_byte() {
+        let cpu = run(&[0xC300]);
+        assert_eq!(cpu.registers[3], 0);
+
+        for _ in 0..32 {
+            let cpu = run(&[0xC30F]);
+            assert_eq!(cpu.registers[3] & 0xF0, 0);
+        }
+    }
+
+    #[test]
+    fn op_cxkk_uses_random_source() {
+        let mut cpu = cpu_with(Variant::Classic, &[0xC0F0, 0xC10F, 0xC2FF]);
+        cpu.rng = Box::new(ScriptedRng::new(vec![0xAB, 0xCD]));
+        for _ in 0..3 {
+            cpu.cycle().unwrap();
+        }
+        assert_eq!(cpu.registers[..3], [0xA0, 0x0D, 0xAB]);
+    }
+
+    #[test]
+    fn op_dxyn_draws_and_reports_collision() {
+        let mut cpu = cpu_with(Variant::Classic, &[0xD012, 0xD012]);
+        cpu.index_register = 0x300;
+        cpu.memory[0x300] = 0b1100_0000;
+        cpu.memory[0x301] = 0b0000_0001;
+        cpu.registers[0] = 2;
+        cpu.registers[1] = 4;
+
+        cpu.cycle().unwrap();
+        assert_eq!(cpu.display.pixels[4][2], 1);
+        assert_eq!(cpu.display.pixels[4][3], 1);
+        assert_eq!(cpu.display.pixels[5][9], 1);
+        assert_eq!(cpu.registers[0xF], 0);
+
+        cpu.cycle().unwrap();
+        assert!(cpu.display.pixels.iter().flatten().all(|&p| p == 0));
+        assert_eq!(cpu.registers[0xF], 1);
+    }
+
+    #[test]
+    fn op_ex9e_skips_when_key_pressed() {
+        let mut cpu = cpu_with(Variant::Classic, &[0xE59E]);
+        cpu.registers[5] = 0xA;
+        cpu.keyboard.set_key(0xA, true);
+        cpu.cycle().unwrap();
+        assert_eq!(cpu.program_counter, 0x204);
+
+        let mut cpu = cpu_with(Variant::Classic, &[0xE59E]);
+        cpu.registers[5] = 0xA;
+        cpu.cycle().unwrap();
+        assert_eq!(cpu.program_counter, 0x202);
+    }
+
+    #[test]
+    fn op_exa1_skips_when_key_not_pressed() {
+        let mut cpu = cpu_with(Variant::Classic, &[0xE5A1]);
+        cpu.registers[5] = 0xA;
+        cpu.cycle().unwrap();
+        assert_eq!(cpu.program_counter, 0x204);
+
+        let mut cpu = cpu_with(Variant::Classic, &[0xE5A1]);
+        cpu.registers[5] = 0xA;
+        cpu.keyboard.set_key(0xA, true);
+        cpu.cycle().unwrap();
+        assert_eq!(cpu.program_counter, 0x202);
+    }
+
+    #[test]
+    fn op_fx07_reads_delay_timer() {
+        let mut cpu = cpu_with(Variant::Classic, &[0xF207]);
+        cpu.delay_timer = 0x20;
+        cpu.cycle().unwrap();
+        assert_eq!(cpu.registers[2], 0x20);
+    }
+
+    #[test]
+    fn op_fx0a_waits_for_key() {
+        let mut cpu = cpu_with(Variant::Classic, &[0xF30A, 0x6001]);
+        cpu.cycle().unwrap();
+        assert_eq!(cpu.waiting_for_key, Some(3));
+
+        cpu.cycle().unwrap();
+        assert_eq!(cpu.program_counter, 0x202);
+        assert_eq!(cpu.registers[0], 0);
+    }
+
+    #[test]
+    fn op_fx0a_completes_on_release() {
+        let mut cpu = cpu_with(Variant::Classic, &[0xF30A, 0x6001]);
+        cpu.quirks.key_wait_release = true;
+        cpu.cycle().unwrap();
+
+        cpu.keyboard.set_key(0x7, true);
+        cpu.cycle().unwrap();
+        cpu.cycle().unwrap();
+        assert_eq!(cpu.waiting_for_key, Some(3));
+        assert_eq!(cpu.key_wait_pressed, Some(0x7));
+
+        cpu.keyboard.set_key(0x7, false);
+        cpu.cycle().unwrap();
+        assert_eq!(cpu.waiting_for_key, None);
+        assert_eq!(cpu.registers[3], 0x7);
+        assert_eq!(cpu.registers[0], 1);
+    }
+
+    #[test]
+    fn op_fx0a_ignores_keys_held_before_wait() {
+        let mut cpu = cpu_with(Variant::Classic, &[0xF30A, 0x6001]);
+        cpu.quirks.key_wait_release = false;
+        cpu.keyboard.set_key(0x2, true);
+        cpu.cycle().unwrap();
+        cpu.cycle().unwrap();
+        assert_eq!(cpu.waiting_for_key, Some(3));
+
+        // Отпущенная и снова нажатая клавиша уже считается
+        cpu.keyboard.set_key(0x2, false);
+        cpu.cycle().unwrap();
+        cpu.keyboard.set_key(0x2, true);
+        cpu.cycle().unwrap();
+        assert_eq!(cpu.waiting_for_key, None);
+        assert_eq!(cpu.registers[3], 0x2);
+    }
+
+    #[test]
+    fn op_fx15_sets_delay_timer() {
+        let mut cpu = cpu_with(Variant::Classic, &[0xF215]);
+        cpu.registers[2] = 0x30;
+        cpu.cycle().unwrap();
+        assert_eq!(cpu.delay_timer, 0x30);
+
+        // Таймер уменьшается только с тиком кадра
+        cpu.update_timers();
+        assert_eq!(cpu.delay_timer, 0x2F);
+    }
+
+    #[test]
+    fn op_fx18_sets_sound_timer() {
+        let mut cpu = cpu_with(Variant::Classic, &[0xF218]);
+        cpu.registers[2] = 0x30;
+        cpu.cycle().unwrap();
+        assert_eq!(cpu.sound_timer, 0x30);
+    }
+
+    #[test]
+    fn op_fx1e_adds_to_index() {
+        let mut cpu = cpu_with(Variant::Classic, &[0xF31E]);
+        cpu.index_register = 0x300;
+        cpu.registers[3] = 0x25;
+        cpu.cycle().unwrap();
+        assert_eq!(cpu.index_register, 0x325);
+        assert_eq!(cpu.registers[0xF], 0);
+    }
+
+    #[test]
+    fn op_fx29_points_to_font_glyph() {
+        let mut cpu = cpu_with(Variant::Classic, &[0xF429]);
+        cpu.registers[4] = 0x1B;
+        cpu.cycle().unwrap();
+        assert_eq!(cpu.index_register, (FONT_START + 0xB * 5) as u16);
+    }
+
+    #[test]
+    fn op_fx33_stores_bcd() {
+        let mut cpu = cpu_with(Variant::Classic, &[0xF133]);
+        cpu.registers[1] = 254;
+        cpu.index_register = 0x300;
+        cpu.cycle().unwrap();
+        assert_eq!(&cpu.memory[0x300..0x303], &[2, 5, 4]);
+    }
+
+    #[test]
+    fn op_fx55_stores_registers() {
+        let mut cpu = cpu_with(Variant::Classic, &[0xF255]);
+        cpu.registers[0] = 1;
+        cpu.registers[1] = 2;
+        cpu.registers[2] = 3;
+        cpu.registers[3] = 4;
+        cpu.index_register = 0x300;
+        cpu.cycle().unwrap();
+        assert_eq!(&cpu.memory[0x300..0x304], &[1, 2, 3, 0]);
+        assert_eq!(cpu.index_register, 0x300);
+    }
+
+    #[test]
+    fn op_fx65_loads_registers() {
+        let mut cpu = cpu_with(Variant::Classic, &[0xF265]);
+        cpu.memory[0x300..0x304].copy_from_slice(&[9, 8, 7, 6]);
+        cpu.index_register = 0x300;
+        cpu.cycle().unwrap();
+        assert_eq!(&cpu.registers[0..4], &[9, 8, 7, 0]);
+        assert_eq!(cpu.index_register, 0x300);
+    }
+
+    #[test]
+    fn op_00fd_stops_cpu() {
+        let mut cpu = cpu_with(Variant::Classic, &[0x00FD, 0x6001]);
+        cpu.cycle().unwrap();
+        assert!(!cpu.running);
+        cpu.cycle().unwrap();
+        assert_eq!(cpu.registers[0], 0);
+    }
+
+    #[test]
+    fn quirk_shift_uses_vy() {
+        let mut cpu = cpu_with(Variant::Classic, &[0x8126]);
+        cpu.quirks.shift_uses_vy = true;
+        cpu.registers[1] = 0xFF;
+        cpu.registers[2] = 0b0000_0110;
+        cpu.cycle().unwrap();
+        assert_eq!(cpu.registers[1], 0b0000_0011);
+        assert_eq!(cpu.registers[0xF], 0);
+
+        let mut cpu = cpu_with(Variant::Classic, &[0x812E]);
+        cpu.quirks.shift_uses_vy = true;
+        cpu.registers[2] = 0b1000_0001;
+        cpu.cycle().unwrap();
+        assert_eq!(cpu.registers[1], 0b0000_0010);
+        assert_eq!(cpu.registers[0xF], 1);
+    }
+
+    #[test]
+    fn quirk_index_increment() {
+        for (increment, expected) in [
+            (IndexIncrement::Unchanged, 0x300),
+            (IndexIncrement::ByX, 0x302),
+            (IndexIncrement::ByXPlusOne, 0x303),
+        ] {
+            let mut cpu = cpu_with(Variant::Classic, &[0xF255]);
+            cpu.quirks.index_increment = increment;
+            cpu.index_register = 0x300;
+            cpu.cycle().unwrap();
+            assert_eq!(cpu.index_register, expected);
+
+            let mut cpu = cpu_with(Variant::Classic, &[0xF265]);
+            cpu.quirks.index_increment = increment;
+            cpu.index_register = 0x300;
+            cpu.cycle().unwrap();
+            assert_eq!(cpu.index_register, expected);
+        }
+    }
+
+    #[test]
+    fn quirk_jump_uses_vx() {
+        let mut cpu = cpu_with(Variant::Classic, &[0xB310]);
+        cpu.quirks.jump_uses_vx = true;
+        cpu.registers[0] = 0x01;
+        cpu.registers[3] = 0x20;
+        cpu.cycle().unwrap();
+        assert_eq!(cpu.program_counter, 0x330);
+    }
+
+    #[test]
+    fn quirk_vf_reset() {
+        for opcode in [0x8121, 0x8122, 0x8123] {
+            let mut cpu = cpu_with(Variant::Classic, &[opcode]);
+            cpu.quirks.vf_reset = true;
+            cpu.registers[0xF] = 0x55;
+            cpu.cycle().unwrap();
+            assert_eq!(cpu.registers[0xF], 0);
+
+            let mut cpu = cpu_with(Variant::Classic, &[opcode]);
+            cpu.registers[0xF] = 0x55;
+            cpu.cycle().unwrap();
+            assert_eq!(cpu.registers[0xF], 0x55);
+        }
+    }
+
+    #[test]
+    fn quirk_clip_sprites() {
+        let mut cpu = cpu_with(Variant::Classic, &[0xD011]);
+        cpu.quirks.clip_sprites = true;
+        cpu.index_register = 0x300;
+        cpu.memory[0x300] = 0xFF;
+        cpu.registers[0] = 60;
+        cpu.cycle().unwrap();
+        assert_eq!(cpu.display.pixels[0][63], 1);
+        assert_eq!(cpu.display.pixels[0][0], 0);
+
+        let mut cpu = cpu_with(Variant::Classic, &[0xD011]);
+        cpu.index_register = 0x300;
+        cpu.memory[0x300] = 0xFF;
+        cpu.registers[0] = 60;
+        cpu.cycle().unwrap();
+        assert_eq!(cpu.display.pixels[0][63], 1);
+        assert_eq!(cpu.display.pixels[0][3], 1);
+    }
+
+    #[test]
+    fn quirk_display_wait_blocks_until_next_frame() {
+        let mut cpu = cpu_with(Variant::Classic, &[0xD011, 0x6001]);
+        cpu.quirks.display_wait = true;
+        cpu.index_register = 0x300;
+        cpu.cycle().unwrap();
+        assert!(cpu.vblank_wait);
+
+        // Сколько бы инструкций ни было в кадре - стоим на 6001
+        for _ in 0..10 {
+            cpu.cycle().unwrap();
+        }
+        assert!(cpu.vblank_wait);
+        assert_eq!(cpu.program_counter, 0x202);
+        assert_eq!(cpu.registers[0], 0);
+
+        cpu.update_timers();
+        cpu.cycle().unwrap();
+        assert_eq!(cpu.registers[0], 1);
+    }
+
+    #[test]
+    fn op_00ff_and_00fe_switch_resolution() {
+        let mut cpu = cpu_with(Variant::Classic, &[0x00FF, 0x00FE]);
+        cpu.display.pixels[0][0] = 1;
+        cpu.cycle().unwrap();
+        assert!(cpu.display.hires);
+        assert_eq!((cpu.display.width(), cpu.display.height()), (128, 64));
+        assert_eq!(cpu.display.pixels[0][0], 0);
+
+        cpu.cycle().unwrap();
+        assert!(!cpu.display.hires);
+        assert_eq!((cpu.display.width(), cpu.display.height()), (64, 32));
+    }
+
+    #[test]
+    fn op_00cn_scrolls_down() {
+        let mut cpu = cpu_with(Variant::Classic, &[0x00C3]);
+        cpu.display.pixels[0][5] = 1;
+        cpu.display.pixels[30][5] = 1;
+        cpu.cycle().unwrap();
+        assert_eq!(cpu.display.pixels[0][5], 0);
+        assert_eq!(cpu.display.pixels[3][5], 1);
+        assert_eq!(cpu.display.pixels[31][5], 0);
+    }
+
+    #[test]
+    fn op_00fb_and_00fc_scroll_horizontally() {
+        let mut cpu = cpu_with(Variant::Classic, &[0x00FB, 0x00FC, 0x00FC]);
+        cpu.display.pixels[2][0] = 1;
+        cpu.display.pixels[2][62] = 1;
+
+        cpu.cycle().unwrap();
+        assert_eq!(cpu.display.pixels[2][4], 1);
+        assert_eq!(cpu.display.pixels[2][0], 0);
+        assert_eq!(cpu.display.pixels[2][62], 0);
+
+        cpu.cycle().unwrap();
+        assert_eq!(cpu.display.pixels[2][0], 1);
+
+        cpu.cycle().unwrap();
+        assert!(cpu.display.pixels.iter().flatten().all(|&p| p == 0));
+    }
+
+    #[test]
+    fn op_dxy0_draws_16x16_sprite_in_hires() {
+        let mut cpu = cpu_with(Variant::Classic, &[0x00FF, 0xD010]);
+        cpu.index_register = 0x300;
+        cpu.memory[0x300] = 0x80;
+        cpu.memory[0x301] = 0x01;
+        cpu.memory[0x31E] = 0xFF;
+        cpu.memory[0x31F] = 0xFF;
+        cpu.registers[0] = 100;
+        cpu.registers[1] = 40;
+        cpu.cycle().unwrap();
+        cpu.cycle().unwrap();
+
+        assert_eq!(cpu.display.pixels[40][100], 1);
+        assert_eq!(cpu.display.pixels[40][115], 1);
+        assert_eq!(cpu.display.pixels[40][101], 0);
+        assert!((100..116).all(|x| cpu.display.pixels[55][x] == 1));
+        assert_eq!(cpu.registers[0xF], 0);
+    }
+
+    #[test]
+    fn op_fx30_points_to_big_font_glyph() {
+        let mut cpu = cpu_with(Variant::Classic, &[0xF430]);
+        cpu.registers[4] = 7;
+        cpu.cycle().unwrap();
+        assert_eq!(cpu.index_register, (BIG_FONT_START + 70) as u16);
+        assert_eq!(cpu.memory[cpu.index_register as usize], BIG_FONT_SET[70]);
+    }
+
+    #[test]
+    fn op_fx75_and_fx85_round_trip_rpl_flags() {
+        let mut cpu = cpu_with(Variant::Classic, &[0xF275, 0x6000, 0xF285]);
+        cpu.registers[0..4].copy_from_slice(&[1, 2, 3, 4]);
+        cpu.cycle().unwrap();
+        assert_eq!(&cpu.rpl_flags[0..4], &[1, 2, 3, 0]);
+
+        cpu.cycle().unwrap();
+        cpu.registers[1] = 0;
+        cpu.cycle().unwrap();
+        assert_eq!(&cpu.registers[0..4], &[1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn variant_selects_memory_size() {
+        assert_eq!(CPU::new().memory.len(), 4096);
+        assert_eq!(CPU::with_variant(Variant::XoChip).memory.len(), 0x10000);
+    }
+
+    #[test]
+    fn xo_opcodes_are_unknown_on_classic() {
+        let mut cpu = cpu_with(Variant::Classic, &[0xF000, 0x1234]);
+        assert_eq!(cpu.cycle(), Err(Chip8Fault::UnknownOpcode { pc: 0x200, opcode: 0xF000 }));
+        assert_eq!(cpu.index_register, 0);
+        assert_eq!(cpu.program_counter, 0x200);
+    }
+
+    #[test]
+    fn op_f000_loads_long_index() {
+        let mut cpu = cpu_with(Variant::XoChip, &[0xF000, 0xBEEF]);
+        cpu.cycle().unwrap();
+        assert_eq!(cpu.index_register, 0xBEEF);
+        assert_eq!(cpu.program_counter, 0x204);
+    }
+
+    #[test]
+    fn skip_steps_over_long_instruction() {
+        let mut cpu = cpu_with(Variant::XoChip, &[0x3000, 0xF000, 0x1234, 0x6001]);
+        cpu.cycle().unwrap();
+        assert_eq!(cpu.program_counter, 0x206);
+    }
+
+    #[test]
+    fn op_5xy2_and_5xy3_save_and_load_ranges() {
+        let mut cpu = cpu_with(Variant::XoChip, &[0x5242, 0x5423]);
+        cpu.registers[2..5].copy_from_slice(&[0xA, 0xB, 0xC]);
+        cpu.index_register = 0x1000;
+        cpu.cycle().unwrap();
+        assert_eq!(&cpu.memory[0x1000..0x1003], &[0xA, 0xB, 0xC]);
+        assert_eq!(cpu.index_register, 0x1000);
+
+        // В обратном порядке: V4, V3, V2
+        cpu.cycle().unwrap();
+        assert_eq!(&cpu.registers[2..5], &[0xC, 0xB, 0xA]);
+    }
+
+    #[test]
+    fn op_fn01_draws_on_selected_planes() {
+        let mut cpu = cpu_with(Variant::XoChip, &[0xF301, 0xD011, 0xF201, 0x00E0]);
+        cpu.index_register = 0x300;
+        cpu.memory[0x300] = 0x80;
+        cpu.memory[0x301] = 0x80;
+
+        cpu.cycle().unwrap();
+        cpu.cycle().unwrap();
+        assert_eq!(cpu.display.pixels[0][0], 0b11);
+
+        // 00E0 чистит только выбранную плоскость
+        cpu.cycle().unwrap();
+        cpu.cycle().unwrap();
+        assert_eq!(cpu.display.pixels[0][0], 0b01);
+    }
+
+    #[test]
+    fn op_00dn_scrolls_selected_plane_up() {
+        let mut cpu = cpu_with(Variant::XoChip, &[0xF201, 0x00D2]);
+        cpu.display.pixels[5][1] = 0b11;
+        cpu.cycle().unwrap();
+        cpu.cycle().unwrap();
+        assert_eq!(cpu.display.pixels[5][1], 0b01);
+        assert_eq!(cpu.display.pixels[3][1], 0b10);
+    }
+
+    #[test]
+    fn op_f002_and_fx3a_set_audio() {
+        let mut cpu = cpu_with(Variant::XoChip, &[0xF002, 0xF53A]);
+        cpu.index_register = 0x400;
+        for i in 0..16 {
+            cpu.memory[0x400 + i] = i as u8;
+        }
+        cpu.registers[5] = 112;
+        cpu.cycle().unwrap();
+        cpu.cycle().unwrap();
+        assert_eq!(cpu.audio_pattern[15], 15);
+        assert!(cpu.pattern_loaded);
+        assert_eq!(cpu.pitch, 112);
+    }
+
+    #[test]
+    fn tracer_records_register_diffs() {
+        use crate::trace::{OpcodeClass, Register, TraceFilter, TraceRecord};
+        use std::cell::RefCell;
+        use std::rc::Rc;
+
+        let records = Rc::new(RefCell::new(Vec::<TraceRecord>::new()));
+        let mut cpu = cpu_with(Variant::Classic, &[0x6A2A, 0xA300, 0x1200]);
+        let filter = TraceFilter { addresses: None, classes: vec![OpcodeClass::Alu, OpcodeClass::Index] };
+        cpu.tracer = Some(Tracer::new(filter, Box::new(records.clone())));
+        cpu.cycle().unwrap();
+        cpu.cycle().unwrap();
+        cpu.cycle().unwrap();
+
+        let records = records.borrow();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].pc, 0x200);
+        assert_eq!(records[0].mnemonic, "LD VA, 0x2A");
+        assert_eq!(records[0].changes[0].register, Register::V(0xA));
+        assert_eq!(records[0].changes[0].new, 0x2A);
+        assert_eq!(records[1].changes[0].register, Register::I);
+        assert_eq!(records[1].changes[0].new, 0x300);
+    }
+
+    #[test]
+    fn fault_on_stack_underflow() {
+        let mut cpu = cpu_with(Variant::Classic, &[0x00EE]);
+        assert_eq!(cpu.cycle(), Err(Chip8Fault::StackUnderflow { pc: 0x200 }));
+        assert_eq!(cpu.program_counter, 0x200);
+    }
+
+    #[test]
+    fn fault_on_stack_overflow() {
+        // Подпрограмма, которая бесконечно вызывает сама себя
+        let mut cpu = cpu_with(Variant::Classic, &[0x2200]);
+        for _ in 0..16 {
+            cpu.cycle().unwrap();
+        }
+        assert_eq!(cpu.cycle(), Err(Chip8Fault::StackOverflow { pc: 0x200 }));
+        assert_eq!(cpu.stack_pointer, 16);
+    }
+
+    #[test]
+    fn fault_on_unknown_opcode() {
+        let mut cpu = cpu_with(Variant::Classic, &[0xE1FF]);
+        let fault = cpu.cycle().unwrap_err();
+        assert_eq!(fault, Chip8Fault::UnknownOpcode { pc: 0x200, opcode: 0xE1FF });
+        assert_eq!(fault.to_string(), "Unknown opcode E1FF at 0200");
+    }
+
+    #[test]
+    fn fault_on_memory_out_of_bounds() {
+        for opcode in [0xD015, 0xF233, 0xF355, 0xF365] {
+            let mut cpu = cpu_with(Variant::Classic, &[opcode]);
+            cpu.index_register = 0xFFE;
+            let fault = cpu.cycle().unwrap_err();
+            assert!(matches!(fault, Chip8Fault::MemoryOutOfBounds { pc: 0x200, addr: 0xFFE, .. }));
+            assert_eq!(cpu.program_counter, 0x200);
+        }
+    }
+
+    #[test]
+    fn fault_on_fetch_past_memory_end() {
+        let mut cpu = cpu_with(Variant::Classic, &[0x1FFF]);
+        cpu.cycle().unwrap();
+        assert_eq!(
+            cpu.cycle(),
+            Err(Chip8Fault::MemoryOutOfBounds { pc: 0xFFF, addr: 0xFFF, len: 2 })
+        );
+    }
+}
@@ -0,0 +1,842 @@
+use std::collections::BTreeSet;
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use crate::cheat::{self, Cheat, CheatList, Filter, Search};
+use crate::cpu::CPU;
+use crate::disasm::mnemonic;
+use crate::fault::Chip8Fault;
+use crate::machine::Chip8;
+use crate::trace::Register;
+
+/// Сколько найденных адресов показывать списком
+pub const SEARCH_LIST: usize = 16;
+
+/// Справка по командам отладчика
+pub const HELP: &str = "\
+Commands (addresses and values are hex, counts are decimal):
+  c, continue          resume execution
+  p, pause             pause execution
+  s, step [N]          execute N instructions (default 1)
+  n, next              step over a CALL (2NNN)
+  u, until ADDR        run to address
+  b, break ADDR        set breakpoint
+  d, delete ADDR       remove breakpoint
+  w, watch ADDR [LEN]  stop when an instruction writes memory ADDR..ADDR+LEN
+  w, watch REG OP VAL  stop when condition becomes true (REG: V0-VF, I, SP, DT, ST;
+                       OP: == != < > <= >=)
+  unwatch N            remove watchpoint number N
+  l, list              list breakpoints and watchpoints
+  r, regs              show registers
+  bt, stack            show call stack
+  x ADDR [LEN]         hexdump memory (default 40 bytes)
+  search [VAL|FILTER]  search memory: no argument starts over, VAL keeps addresses
+                       holding VAL, FILTER keeps those changed, unchanged,
+                       increased or decreased since the last search
+  freeze TARGET VAL    hold memory ADDR or V0-VF, I, DT, ST at VAL every frame
+  unfreeze N           remove frozen value number N
+  cheats               list frozen values
+  cheats save [FILE]   save frozen values (default: the ROM's cheat file)
+  cheats load [FILE]   load frozen values (default: the ROM's cheat file)
+  h, help              this help
+  q, quit              exit the emulator";
+
+/// Сравнение в условии точки наблюдения
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compare {
+    Eq,
+    Ne,
+    Lt,
+    Gt,
+    Le,
+    Ge,
+}
+
+impl Compare {
+    pub fn all() -> [Compare; 6] {
+        [Compare::Eq, Compare::Ne, Compare::Lt, Compare::Gt, Compare::Le, Compare::Ge]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Compare::Eq => "==",
+            Compare::Ne => "!=",
+            Compare::Lt => "<",
+            Compare::Gt => ">",
+            Compare::Le => "<=",
+            Compare::Ge => ">=",
+        }
+    }
+
+    pub fn holds(&self, left: u16, right: u16) -> bool {
+        match self {
+            Compare::Eq => left == right,
+            Compare::Ne => left != right,
+            Compare::Lt => left < right,
+            Compare::Gt => left > right,
+            Compare::Le => left <= right,
+            Compare::Ge => left >= right,
+        }
+    }
+}
+
+impl fmt::Display for Compare {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Compare {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Compare::all()
+            .into_iter()
+            .find(|compare| compare.name() == s)
+            .ok_or_else(|| format!("unknown comparison '{}', expected == != < > <= >=", s))
+    }
+}
+
+/// Точка наблюдения
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Watchpoint {
+    /// Запись в память `addr..addr + len`
+    Memory { addr: u16, len: u16 },
+    /// Условие на значение регистра
+    Register { register: Register, compare: Compare, value: u16 },
+}
+
+impl fmt::Display for Watchpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Watchpoint::Memory { addr, len: 1 } => write!(f, "memory {:04X}", addr),
+            Watchpoint::Memory { addr, len } => {
+                write!(f, "memory {:04X}-{:04X}", addr, addr.wrapping_add(len - 1))
+            }
+            Watchpoint::Register { register, compare, value } => {
+                write!(f, "{} {} {:X}", register, compare, value)
+            }
+        }
+    }
+}
+
+/// Команда отладчика, как её ввёл пользователь
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    Continue,
+    Pause,
+    Step(usize),
+    Next,
+    RunTo(u16),
+    Break(u16),
+    Delete(u16),
+    Watch(Watchpoint),
+    Unwatch(usize),
+    List,
+    Registers,
+    Stack,
+    Memory { addr: u16, len: u16 },
+    /// Новый поиск (None) или сужение текущего
+    Search(Option<Filter>),
+    Freeze(Cheat),
+    Unfreeze(usize),
+    Cheats,
+    SaveCheats(Option<String>),
+    LoadCheats(Option<String>),
+    Help,
+    Quit,
+}
+
+/// Шестнадцатеричное число, `0x` необязателен
+fn parse_hex(s: &str) -> Result<u16, String> {
+    u16::from_str_radix(s.trim_start_matches("0x"), 16)
+        .map_err(|e| format!("invalid hex number '{}': {}", s, e))
+}
+
+impl FromStr for Command {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let words: Vec<&str> = s.split_whitespace().collect();
+        let Some((&name, args)) = words.split_first() else {
+            return Err("empty command".to_string());
+        };
+        let arg = |i: usize| {
+            args.get(i)
+                .copied()
+                .ok_or_else(|| format!("'{}' needs more arguments, see 'help'", name))
+        };
+
+        let command = match name {
+            "c" | "continue" => Command::Continue,
+            "p" | "pause" => Command::Pause,
+            "s" | "step" => match args.first() {
+                Some(count) => Command::Step(
+                    count.parse().map_err(|_| format!("invalid step count '{}'", count))?,
+                ),
+                None => Command::Step(1),
+            },
+            "n" | "next" => Command::Next,
+            "u" | "until" => Command::RunTo(parse_hex(arg(0)?)?),
+            "b" | "break" => Command::Break(parse_hex(arg(0)?)?),
+            "d" | "delete" => Command::Delete(parse_hex(arg(0)?)?),
+            "w" | "watch" => {
+                let target = arg(0)?;
+                if args.len() == 3 {
+                    Command::Watch(Watchpoint::Register {
+                        register: target.parse()?,
+                        compare: arg(1)?.parse()?,
+                        value: parse_hex(arg(2)?)?,
+                    })
+                } else {
+                    let len = args.get(1).map_or(Ok(1), |len| parse_hex(len))?;
+                    if len == 0 {
+                        return Err("watch length must be at least 1".to_string());
+                    }
+                    Command::Watch(Watchpoint::Memory { addr: parse_hex(target)?, len })
+                }
+            }
+            "unwatch" => Command::Unwatch(
+                arg(0)?.parse().map_err(|_| format!("invalid watchpoint number '{}'", args[0]))?,
+            ),
+            "l" | "list" => Command::List,
+            "r" | "regs" => Command::Registers,
+            "bt" | "stack" => Command::Stack,
+            "x" => Command::Memory {
+                addr: parse_hex(arg(0)?)?,
+                len: args.get(1).map_or(Ok(0x40), |len| parse_hex(len))?,
+            },
+            "search" => Command::Search(args.first().map(|filter| filter.parse()).transpose()?),
+            "freeze" => Command::Freeze(format!("{}={}", arg(0)?, arg(1)?).parse()?),
+            "unfreeze" => Command::Unfreeze(
+                arg(0)?.parse().map_err(|_| format!("invalid frozen value number '{}'", args[0]))?,
+            ),
+            "cheats" => match args.first().copied() {
+                None => Command::Cheats,
+                Some("save") => Command::SaveCheats(args.get(1).map(|path| path.to_string())),
+                Some("load") => Command::LoadCheats(args.get(1).map(|path| path.to_string())),
+                Some(other) => return Err(format!("unknown cheats command '{}', expected save or load", other)),
+            },
+            "h" | "help" => Command::Help,
+            "q" | "quit" => Command::Quit,
+            _ => return Err(format!("unknown command '{}', see 'help'", name)),
+        };
+        Ok(command)
+    }
+}
+
+/// Почему выполнение остановилось
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Stop {
+    Breakpoint(u16),
+    /// Достигнут адрес из `next`/`until`
+    Reached(u16),
+    /// Сработала точка наблюдения; `pc` - инструкция, которая её вызвала
+    Watch { index: usize, pc: u16, detail: String },
+    Fault(Chip8Fault),
+    /// Программа завершилась (00FD)
+    Halted,
+    /// FX0A ждёт нажатия клавиши для VX - шаг не сдвинет PC, пока её не нажмут
+    WaitingForKey(usize),
+}
+
+impl fmt::Display for Stop {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Stop::Breakpoint(pc) => write!(f, "Breakpoint at {:04X}", pc),
+            Stop::Reached(pc) => write!(f, "Reached {:04X}", pc),
+            Stop::Watch { index, pc, detail } => {
+                write!(f, "Watchpoint {} hit by instruction at {:04X}: {}", index, pc, detail)
+            }
+            Stop::Fault(fault) => write!(f, "Fault: {}", fault),
+            Stop::Halted => f.write_str("Program exited"),
+            Stop::WaitingForKey(x) => write!(f, "Waiting for a key press into V{:X} (FX0A)", x),
+        }
+    }
+}
+
+/// Точка наблюдения с запомненным прошлым состоянием
+#[derive(Debug, Clone)]
+struct Watch {
+    point: Watchpoint,
+    // Содержимое памяти на прошлом шаге
+    memory: Vec<u8>,
+    // Выполнялось ли условие на прошлом шаге (срабатываем только на переходе)
+    was_true: bool,
+}
+
+impl Watch {
+    fn new(point: Watchpoint, chip8: &Chip8) -> Self {
+        let mut watch = Watch { point, memory: Vec::new(), was_true: false };
+        watch.check(chip8);
+        watch
+    }
+
+    /// Обновить состояние; описание изменения, если точка сработала
+    fn check(&mut self, chip8: &Chip8) -> Option<String> {
+        match self.point {
+            Watchpoint::Memory { addr, len } => {
+                // Срабатываем на саму запись, даже если значение не изменилось
+                let (start, end) = (addr as usize, addr as usize + len as usize);
+                let now = chip8.read_memory(start, len as usize);
+                let written = chip8
+                    .cpu()
+                    .last_write
+                    .as_ref()
+                    .filter(|write| write.start < end && start < write.end)
+                    .map(|write| write.start.max(start));
+                let hit = written.and_then(|written| {
+                    let i = written - start;
+                    let new = *now.get(i)?;
+                    let old = self.memory.get(i).copied().unwrap_or(new);
+                    Some(format!("[{:04X}] {:02X} -> {:02X}", written, old, new))
+                });
+                self.memory.clear();
+                self.memory.extend_from_slice(now);
+                hit
+            }
+            Watchpoint::Register { register, compare, value } => {
+                let current = register.read(chip8.cpu());
+                let is_true = compare.holds(current, value);
+                let hit = is_true && !self.was_true;
+                self.was_true = is_true;
+                hit.then(|| format!("{} = {:X}", register, current))
+            }
+        }
+    }
+}
+
+/// Временная остановка для `next` и `until`
+#[derive(Debug, Clone, Copy)]
+struct Target {
+    addr: u16,
+    // Для step-over: остановиться только вернувшись на этот уровень стека
+    max_depth: Option<u8>,
+}
+
+/// Интерактивный отладчик поверх машины
+#[derive(Debug, Clone)]
+pub struct Debugger {
+    pub paused: bool,
+    breakpoints: BTreeSet<u16>,
+    watches: Vec<Watch>,
+    target: Option<Target>,
+    // Поиск по памяти между командами search
+    search: Option<Search>,
+}
+
+impl Default for Debugger {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Debugger {
+    /// Отладчик стартует на паузе, чтобы успеть расставить точки останова
+    pub fn new() -> Self {
+        Debugger {
+            paused: true,
+            breakpoints: BTreeSet::new(),
+            watches: Vec::new(),
+            target: None,
+            search: None,
+        }
+    }
+
+    pub fn breakpoints(&self) -> impl Iterator<Item = u16> + '_ {
+        self.breakpoints.iter().copied()
+    }
+
+    pub fn add_breakpoint(&mut self, addr: u16) {
+        self.breakpoints.insert(addr);
+    }
+
+    pub fn remove_breakpoint(&mut self, addr: u16) -> bool {
+        self.breakpoints.remove(&addr)
+    }
+
+    pub fn add_watchpoint(&mut self, point: Watchpoint, chip8: &Chip8) {
+        self.watches.push(Watch::new(point, chip8));
+    }
+
+    /// Выполнить команду; возвращает текст для пользователя
+    pub fn execute(&mut self, command: Command, chip8: &mut Chip8) -> String {
+        match command {
+            Command::Continue => {
+                self.paused = false;
+                "Continuing".to_string()
+            }
+            Command::Pause => {
+                self.paused = true;
+                self.target = None;
+                format!("Paused\n{}", location(chip8))
+            }
+            Command::Step(count) => {
+                self.paused = true;
+                self.target = None;
+                let stop = (0..count).find_map(|_| self.single_step(chip8));
+                match stop {
+                    Some(stop) => format!("{}\n{}", stop, location(chip8)),
+                    None => location(chip8),
+                }
+            }
+            Command::Next => {
+                let cpu = chip8.cpu();
+                let pc = cpu.program_counter;
+                let opcode = opcode_at(chip8, pc);
+                if opcode & 0xF000 == 0x2000 {
+                    self.target = Some(Target {
+                        addr: pc.wrapping_add(2),
+                        max_depth: Some(cpu.stack_pointer),
+                    });
+                    self.paused = false;
+                    format!("Stepping over call to {:03X}", opcode & 0x0FFF)
+                } else {
+                    self.execute(Command::Step(1), chip8)
+                }
+            }
+            Command::RunTo(addr) => {
+                self.target = Some(Target { addr, max_depth: None });
+                self.paused = false;
+                format!("Running to {:04X}", addr)
+            }
+            Command::Break(addr) => {
+                self.add_breakpoint(addr);
+                format!("Breakpoint at {:04X}", addr)
+            }
+            Command::Delete(addr) => {
+                if self.remove_breakpoint(addr) {
+                    format!("Deleted breakpoint at {:04X}", addr)
+                } else {
+                    format!("No breakpoint at {:04X}", addr)
+                }
+            }
+            Command::Watch(point) => {
+                let text = format!("Watchpoint {}: {}", self.watches.len(), point);
+                self.add_watchpoint(point, chip8);
+                text
+            }
+            Command::Unwatch(index) => {
+                if index < self.watches.len() {
+                    let watch = self.watches.remove(index);
+                    format!("Removed watchpoint {}: {}", index, watch.point)
+                } else {
+                    format!("No watchpoint {}", index)
+                }
+            }
+            Command::List => {
+                let mut lines: Vec<String> = self
+                    .breakpoints
+                    .iter()
+                    .map(|addr| format!("break {:04X}", addr))
+                    .collect();
+                for (i, watch) in self.watches.iter().enumerate() {
+                    lines.push(format!("watch {}: {}", i, watch.point));
+                }
+                if lines.is_empty() {
+                    "No breakpoints or watchpoints".to_string()
+                } else {
+                    lines.join("\n")
+                }
+            }
+            Command::Registers => registers(chip8.cpu()),
+            Command::Stack => stack(chip8.cpu()),
+            Command::Memory { addr, len } => {
+                hexdump(chip8.read_memory(addr as usize, len as usize), addr as usize)
+            }
+            Command::Search(filter) => self.search(filter, chip8),
+            Command::Freeze(cheat) => {
+                chip8.add_cheat(cheat);
+                format!("Frozen {}", cheat)
+            }
+            Command::Unfreeze(index) => match chip8.remove_cheat(index) {
+                Some(cheat) => format!("Unfrozen {}", cheat),
+                None => format!("No frozen value {}", index),
+            },
+            Command::Cheats => {
+                let lines: Vec<String> =
+                    chip8.cheats().iter().enumerate().map(|(i, cheat)| format!("{}: {}", i, cheat)).collect();
+                if lines.is_empty() {
+                    "No frozen values".to_string()
+                } else {
+                    lines.join("\n")
+                }
+            }
+            Command::SaveCheats(path) => {
+                let Some(path) = path.map(PathBuf::from).or_else(|| cheat::default_path(chip8.rom_hash())) else {
+                    return "No config directory for the cheat file, give a file name".to_string();
+                };
+                match chip8.cheat_list().write_to(&path) {
+                    Ok(()) => format!("Saved {} cheats to '{}'", chip8.cheats().len(), path.display()),
+                    Err(e) => format!("Failed to save '{}': {}", path.display(), e),
+                }
+            }
+            Command::LoadCheats(path) => {
+                let Some(path) = path.map(PathBuf::from).or_else(|| cheat::default_path(chip8.rom_hash())) else {
+                    return "No config directory for the cheat file, give a file name".to_string();
+                };
+                match CheatList::read_from(&path).and_then(|list| chip8.load_cheats(&list).map(|()| list)) {
+                    Ok(list) => format!("Loaded {} cheats from '{}'", list.cheats.len(), path.display()),
+                    Err(e) => format!("Failed to load '{}': {}", path.display(), e),
+                }
+            }
+            Command::Help => HELP.to_string(),
+            Command::Quit => String::new(),
+        }
+    }
+
+    /// Начать поиск или сузить его; значение без поиска начинает новый
+    fn search(&mut self, filter: Option<Filter>, chip8: &Chip8) -> String {
+        let memory = &chip8.cpu().memory;
+        let search = match (filter, &mut self.search) {
+            (None, _) => {
+                let search = self.search.insert(Search::new(memory));
+                return format!("Search started: {} addresses", search.candidates().len());
+            }
+            (Some(Filter::Equal(_)), None) => self.search.insert(Search::new(memory)),
+            (Some(_), None) => return "No search in progress, start one with 'search'".to_string(),
+            (Some(_), Some(search)) => search,
+        };
+        let found = search.narrow(memory, filter.expect("filter is set"));
+        match found {
+            0 => "No addresses left, start over with 'search'".to_string(),
+            n if n <= SEARCH_LIST => {
+                let lines: Vec<String> =
+                    search.candidates().iter().map(|(addr, value)| format!("{:04X} = {:02X}", addr, value)).collect();
+                format!("{} addresses:\n{}", n, lines.join("\n"))
+            }
+            n => format!("{} addresses", n),
+        }
+    }
+
+    /// Выполнить одну инструкцию и проверить все условия остановки
+    pub fn step(&mut self, chip8: &mut Chip8) -> Option<Stop> {
+        if !chip8.is_running() {
+            return Some(Stop::Halted);
+        }
+
+        let pc = chip8.cpu().program_counter;
+        if let Err(fault) = chip8.step(1) {
+            return Some(Stop::Fault(fault));
+        }
+
+        for (index, watch) in self.watches.iter_mut().enumerate() {
+            if let Some(detail) = watch.check(chip8) {
+                return Some(Stop::Watch { index, pc, detail });
+            }
+        }
+
+        let cpu = chip8.cpu();
+        let new_pc = cpu.program_counter;
+        if let Some(target) = self.target
+            && target.addr == new_pc
+            && target.max_depth.is_none_or(|depth| cpu.stack_pointer <= depth)
+        {
+            self.target = None;
+            return Some(Stop::Reached(new_pc));
+        }
+        if self.breakpoints.contains(&new_pc) {
+            return Some(Stop::Breakpoint(new_pc));
+        }
+        if !cpu.running {
+            return Some(Stop::Halted);
+        }
+        None
+    }
+
+    /// Шаг по команде пользователя. Если DXYN ждёт следующего кадра (quirk
+    /// display_wait), кадр сначала завершается, иначе шаг стоял бы на месте;
+    /// ожидание клавиши FX0A возвращается как остановка с объяснением
+    pub fn single_step(&mut self, chip8: &mut Chip8) -> Option<Stop> {
+        if chip8.cpu().vblank_wait {
+            self.end_frame(chip8);
+        }
+        let stop = self.step(chip8);
+        match (stop, chip8.cpu().waiting_for_key) {
+            (None, Some(x)) => Some(Stop::WaitingForKey(x)),
+            (stop, _) => stop,
+        }
+    }
+
+    /// Кадр под отладчиком: до `instructions` инструкций, затем конец кадра.
+    /// На остановке кадр прерывается, отладчик встаёт на паузу.
+    pub fn run_frame(&mut self, chip8: &mut Chip8, instructions: usize) -> Option<Stop> {
+        if self.paused {
+            return None;
+        }
+        for _ in 0..instructions {
+            if let Some(stop) = self.step(chip8) {
+                self.paused = true;
+                self.target = None;
+                return Some(stop);
+            }
+        }
+        self.end_frame(chip8);
+        None
+    }
+
+    fn end_frame(&mut self, chip8: &mut Chip8) {
+        chip8.end_frame();
+        // Читы и конец кадра меняют память и регистры вне инструкций -
+        // не приписываем это следующей инструкции
+        for watch in &mut self.watches {
+            watch.check(chip8);
+        }
+    }
+}
+
+fn opcode_at(chip8: &Chip8, addr: u16) -> u16 {
+    match chip8.read_memory(addr as usize, 2) {
+        [high, low] => u16::from_be_bytes([*high, *low]),
+        _ => 0,
+    }
+}
+
+/// Текущая инструкция: `0204: 6003  LD V0, 0x03`
+pub fn location(chip8: &Chip8) -> String {
+    let pc = chip8.cpu().program_counter;
+    let opcode = opcode_at(chip8, pc);
+    format!("{:04X}: {:04X}  {}", pc, opcode, mnemonic(opcode))
+}
+
+/// Все регистры, таймеры и I
+pub fn registers(cpu: &CPU) -> String {
+    let v: Vec<String> = cpu
+        .registers
+        .iter()
+        .enumerate()
+        .map(|(i, value)| format!("V{:X}={:02X}", i, value))
+        .collect();
+    format!(
+        "{}\n{}\nPC={:04X} I={:04X} SP={:02X} DT={:02X} ST={:02X}",
+        v[..8].join(" "),
+        v[8..].join(" "),
+        cpu.program_counter,
+        cpu.index_register,
+        cpu.stack_pointer,
+        cpu.delay_timer,
+        cpu.sound_timer
+    )
+}
+
+/// Стек вызовов, вершина первой
+pub fn stack(cpu: &CPU) -> String {
+    if cpu.stack_pointer == 0 {
+        return "Stack is empty".to_string();
+    }
+    cpu.stack[..cpu.stack_pointer as usize]
+        .iter()
+        .enumerate()
+        .rev()
+        .map(|(depth, addr)| format!("#{} {:04X}", depth, addr))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Дамп памяти по 16 байт в строке, `start` - адрес первого байта
+pub fn hexdump(data: &[u8], start: usize) -> String {
+    data.chunks(16)
+        .enumerate()
+        .map(|(row, chunk)| {
+            let hex: Vec<String> = chunk.iter().map(|b| format!("{:02X}", b)).collect();
+            let ascii: String = chunk
+                .iter()
+                .map(|&b| if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' })
+                .collect();
+            format!("{:04X}: {:<47}  |{}|", start + row * 16, hex.join(" "), ascii)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn machine(rom: &[u8]) -> Chip8 {
+        let mut chip8 = Chip8::default();
+        chip8.load(rom).unwrap();
+        chip8
+    }
+
+    #[test]
+    fn parses_commands() {
+        assert_eq!("s".parse(), Ok(Command::Step(1)));
+        assert_eq!("step 10".parse(), Ok(Command::Step(10)));
+        assert_eq!("b 0x20A".parse(), Ok(Command::Break(0x20A)));
+        assert_eq!(
+            "w 300 4".parse(),
+            Ok(Command::Watch(Watchpoint::Memory { addr: 0x300, len: 4 }))
+        );
+        assert_eq!(
+            "watch v3 >= 1F".parse(),
+            Ok(Command::Watch(Watchpoint::Register {
+                register: Register::V(3),
+                compare: Compare::Ge,
+                value: 0x1F,
+            }))
+        );
+        assert_eq!("x 200".parse(), Ok(Command::Memory { addr: 0x200, len: 0x40 }));
+        assert!("b".parse::<Command>().is_err());
+        assert!("jump 200".parse::<Command>().is_err());
+    }
+
+    #[test]
+    fn stops_at_breakpoint() {
+        // 200: V0 = 1; 202: V1 = 2; 204: V2 = 3; 206: JP 206
+        let mut chip8 = machine(&[0x60, 0x01, 0x61, 0x02, 0x62, 0x03, 0x12, 0x06]);
+        let mut debugger = Debugger::new();
+        debugger.execute(Command::Break(0x204), &mut chip8);
+        debugger.execute(Command::Continue, &mut chip8);
+
+        assert_eq!(debugger.run_frame(&mut chip8, 10), Some(Stop::Breakpoint(0x204)));
+        assert!(debugger.paused);
+        assert_eq!(chip8.cpu().registers[1], 2);
+        assert_eq!(chip8.cpu().registers[2], 0);
+    }
+
+    #[test]
+    fn step_over_skips_the_call() {
+        // 200: CALL 206; 202: V1 = 1; 204: JP 204; 206: V0 = 5; 208: RET
+        let mut chip8 = machine(&[0x22, 0x06, 0x61, 0x01, 0x12, 0x04, 0x60, 0x05, 0x00, 0xEE]);
+        let mut debugger = Debugger::new();
+        debugger.execute(Command::Next, &mut chip8);
+
+        assert_eq!(debugger.run_frame(&mut chip8, 10), Some(Stop::Reached(0x202)));
+        assert_eq!(chip8.cpu().registers[0], 5);
+        assert_eq!(chip8.cpu().stack_pointer, 0);
+    }
+
+    #[test]
+    fn memory_watchpoint_reports_writer() {
+        // 200: I = 300; 202: V0 = 7; 204: LD [I], V0; 206: JP 206
+        let mut chip8 = machine(&[0xA3, 0x00, 0x60, 0x07, 0xF0, 0x55, 0x12, 0x06]);
+        let mut debugger = Debugger::new();
+        debugger.execute(Command::Watch(Watchpoint::Memory { addr: 0x300, len: 1 }), &mut chip8);
+        debugger.execute(Command::Continue, &mut chip8);
+
+        let stop = debugger.run_frame(&mut chip8, 10).unwrap();
+        assert_eq!(
+            stop,
+            Stop::Watch { index: 0, pc: 0x204, detail: "[0300] 00 -> 07".to_string() }
+        );
+    }
+
+    #[test]
+    fn memory_watchpoint_fires_on_writes_not_changes() {
+        // 200: I = 300; 202: LD [I], V0 (тот же ноль); 204: V1 += 1; 206: JP 204
+        let mut chip8 = machine(&[0xA3, 0x00, 0xF0, 0x55, 0x71, 0x01, 0x12, 0x04]);
+        let mut debugger = Debugger::new();
+        debugger.execute(Command::Watch(Watchpoint::Memory { addr: 0x2FF, len: 2 }), &mut chip8);
+        debugger.execute(Command::Continue, &mut chip8);
+
+        let stop = debugger.run_frame(&mut chip8, 10).unwrap();
+        assert_eq!(
+            stop,
+            Stop::Watch { index: 0, pc: 0x202, detail: "[0300] 00 -> 00".to_string() }
+        );
+
+        // Заморозка меняет память вне инструкций - цикл V1 += 1 не виноват
+        debugger.execute("freeze 300 9".parse().unwrap(), &mut chip8);
+        debugger.execute(Command::Continue, &mut chip8);
+        assert_eq!(debugger.run_frame(&mut chip8, 10), None);
+        assert_eq!(debugger.run_frame(&mut chip8, 10), None);
+        assert_eq!(chip8.read_memory(0x300, 1), [9]);
+    }
+
+    #[test]
+    fn step_ends_the_frame_a_draw_waits_for() {
+        use crate::quirks::Preset;
+
+        // 200: DRW V0, V0, 1; 202: V1 = 1; 204: V2 = 2
+        let mut chip8 = Chip8::default().with_quirks(Preset::Vip.quirks());
+        chip8.load(&[0xD0, 0x01, 0x61, 0x01, 0x62, 0x02]).unwrap();
+        let mut debugger = Debugger::new();
+        debugger.execute(Command::Step(1), &mut chip8);
+        assert!(chip8.cpu().vblank_wait);
+
+        debugger.execute(Command::Step(1), &mut chip8);
+        assert_eq!(chip8.cpu().program_counter, 0x204);
+        assert_eq!(chip8.cpu().registers[1], 1);
+        assert_eq!(chip8.frame(), 1);
+    }
+
+    #[test]
+    fn step_reports_key_wait() {
+        // 200: LD V3, K; 202: JP 202
+        let mut chip8 = machine(&[0xF3, 0x0A, 0x12, 0x02]);
+        let mut debugger = Debugger::new();
+        let output = debugger.execute(Command::Step(5), &mut chip8);
+        assert!(output.starts_with("Waiting for a key press into V3"), "{}", output);
+
+        chip8.set_key(0x7, true);
+        debugger.execute(Command::Step(1), &mut chip8);
+        chip8.set_key(0x7, false);
+        assert_eq!(debugger.single_step(&mut chip8), None);
+        assert_eq!(chip8.cpu().registers[3], 0x7);
+        assert_eq!(chip8.cpu().program_counter, 0x202);
+    }
+
+    #[test]
+    fn register_watchpoint_fires_on_transition() {
+        // 200: V0 += 1; 202: JP 200
+        let mut chip8 = machine(&[0x70, 0x01, 0x12, 0x00]);
+        let mut debugger = Debugger::new();
+        let point = Watchpoint::Register { register: Register::V(0), compare: Compare::Ge, value: 3 };
+        debugger.execute(Command::Watch(point), &mut chip8);
+        debugger.execute(Command::Continue, &mut chip8);
+
+        assert!(matches!(debugger.run_frame(&mut chip8, 20), Some(Stop::Watch { .. })));
+        assert_eq!(chip8.cpu().registers[0], 3);
+
+        // Условие всё ещё истинно - повторно не срабатывает
+        debugger.execute(Command::Continue, &mut chip8);
+        assert_eq!(debugger.run_frame(&mut chip8, 20), None);
+    }
+
+    #[test]
+    fn search_narrows_and_freeze_holds() {
+        // 200: V0 = 3; 202: I = 300; 204: LD [I], V0; 206: V0 -= 1; 208: LD [I], V0; 20A: JP 20A
+        let mut chip8 = machine(&[0x60, 0x03, 0xA3, 0x00, 0xF0, 0x55, 0x70, 0xFF, 0xF0, 0x55, 0x12, 0x0A]);
+        let mut debugger = Debugger::new();
+        assert!(debugger.execute("search decreased".parse().unwrap(), &mut chip8).starts_with("No search"));
+        debugger.execute(Command::Step(3), &mut chip8);
+        // Тройки есть и в шрифте, поэтому адресов много
+        let output = debugger.execute("search 3".parse().unwrap(), &mut chip8);
+        assert!(output.ends_with(" addresses"), "{}", output);
+
+        debugger.execute(Command::Step(2), &mut chip8);
+        let output = debugger.execute("search decreased".parse().unwrap(), &mut chip8);
+        assert_eq!(output, "1 addresses:\n0300 = 02");
+
+        assert_eq!(debugger.execute("freeze 300 63".parse().unwrap(), &mut chip8), "Frozen 0300 = 63");
+        assert_eq!(chip8.read_memory(0x300, 1), [0x63]);
+        assert_eq!(debugger.execute(Command::Cheats, &mut chip8), "0: 0300 = 63");
+        assert!("freeze SP 1".parse::<Command>().is_err());
+        assert!("cheats list".parse::<Command>().is_err());
+        assert_eq!("cheats save a.cheats".parse(), Ok(Command::SaveCheats(Some("a.cheats".to_string()))));
+    }
+
+    #[test]
+    fn step_reports_faults() {
+        let mut chip8 = machine(&[0x00, 0xEE]);
+        let mut debugger = Debugger::new();
+        let output = debugger.execute(Command::Step(1), &mut chip8);
+        assert!(output.starts_with("Fault: Stack underflow at 0200"));
+    }
+
+    #[test]
+    fn formats_state() {
+        let mut cpu = CPU::new();
+        cpu.registers[0xF] = 1;
+        cpu.stack[0] = 0x202;
+        cpu.stack_pointer = 1;
+        assert!(registers(&cpu).contains("VF=01"));
+        assert_eq!(stack(&cpu), "#0 0202");
+        assert_eq!(
+            hexdump(b"AB\x00", 0x300),
+            format!("0300: {:<47}  |AB.|", "41 42 00")
+        );
+    }
+}
@@ -0,0 +1,412 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use crate::constants::PROGRAM_START;
+use crate::variant::Variant;
+
+/// Сколько байт данных выводится в одной строке `db`
+const DATA_PER_LINE: usize = 8;
+
+/// Мнемоника инструкции в синтаксисе Cowgod
+pub fn mnemonic(opcode: u16) -> String {
+    format_instruction(opcode, |addr| format!("0x{:03X}", addr))
+}
+
+/// Мнемоника, где адреса NNN выводятся через `address` (например, как метки)
+pub fn format_instruction(opcode: u16, address: impl Fn(u16) -> String) -> String {
+    let nnn = opcode & 0x0FFF;
+    let kk = opcode & 0x00FF;
+    let x = (opcode >> 8) & 0xF;
+    let y = (opcode >> 4) & 0xF;
+    let n = opcode & 0xF;
+
+    match (opcode >> 12, x, y, n) {
+        (0x0, 0x0, 0xE, 0x0) => "CLS".to_string(),
+        (0x0, 0x0, 0xE, 0xE) => "RET".to_string(),
+        (0x0, 0x0, 0xC, _) => format!("SCD {}", n),
+        (0x0, 0x0, 0xD, _) => format!("SCU {}", n),
+        (0x0, 0x0, 0xF, 0xB) => "SCR".to_string(),
+        (0x0, 0x0, 0xF, 0xC) => "SCL".to_string(),
+        (0x0, 0x0, 0xF, 0xD) => "EXIT".to_string(),
+        (0x0, 0x0, 0xF, 0xE) => "LOW".to_string(),
+        (0x0, 0x0, 0xF, 0xF) => "HIGH".to_string(),
+        (0x0, _, _, _) => format!("SYS 0x{:03X}", nnn),
+        (0x1, _, _, _) => format!("JP {}", address(nnn)),
+        (0x2, _, _, _) => format!("CALL {}", address(nnn)),
+        (0x3, _, _, _) => format!("SE V{:X}, 0x{:02X}", x, kk),
+        (0x4, _, _, _) => format!("SNE V{:X}, 0x{:02X}", x, kk),
+        (0x5, _, _, 0x0) => format!("SE V{:X}, V{:X}", x, y),
+        (0x5, _, _, 0x2) => format!("SAVE V{:X}, V{:X}", x, y),
+        (0x5, _, _, 0x3) => format!("LOAD V{:X}, V{:X}", x, y),
+        (0x6, _, _, _) => format!("LD V{:X}, 0x{:02X}", x, kk),
+        (0x7, _, _, _) => format!("ADD V{:X}, 0x{:02X}", x, kk),
+        (0x8, _, _, 0x0) => format!("LD V{:X}, V{:X}", x, y),
+        (0x8, _, _, 0x1) => format!("OR V{:X}, V{:X}", x, y),
+        (0x8, _, _, 0x2) => format!("AND V{:X}, V{:X}", x, y),
+        (0x8, _, _, 0x3) => format!("XOR V{:X}, V{:X}", x, y),
+        (0x8, _, _, 0x4) => format!("ADD V{:X}, V{:X}", x, y),
+        (0x8, _, _, 0x5) => format!("SUB V{:X}, V{:X}", x, y),
+        (0x8, _, _, 0x6) => format!("SHR V{:X}, V{:X}", x, y),
+        (0x8, _, _, 0x7) => format!("SUBN V{:X}, V{:X}", x, y),
+        (0x8, _, _, 0xE) => format!("SHL V{:X}, V{:X}", x, y),
+        (0x9, _, _, 0x0) => format!("SNE V{:X}, V{:X}", x, y),
+        (0xA, _, _, _) => format!("LD I, {}", address(nnn)),
+        (0xB, _, _, _) => format!("JP V0, {}", address(nnn)),
+        (0xC, _, _, _) => format!("RND V{:X}, 0x{:02X}", x, kk),
+        (0xD, _, _, _) => format!("DRW V{:X}, V{:X}, {}", x, y, n),
+        (0xE, _, 0x9, 0xE) => format!("SKP V{:X}", x),
+        (0xE, _, 0xA, 0x1) => format!("SKNP V{:X}", x),
+        (0xF, 0x0, 0x0, 0x0) => "LD I, LONG".to_string(),
+        (0xF, _, 0x0, 0x1) => format!("PLANE {}", x),
+        (0xF, 0x0, 0x0, 0x2) => "AUDIO".to_string(),
+        (0xF, _, 0x0, 0x7) => format!("LD V{:X}, DT", x),
+        (0xF, _, 0x0, 0xA) => format!("LD V{:X}, K", x),
+        (0xF, _, 0x1, 0x5) => format!("LD DT, V{:X}", x),
+        (0xF, _, 0x1, 0x8) => format!("LD ST, V{:X}", x),
+        (0xF, _, 0x1, 0xE) => format!("ADD I, V{:X}", x),
+        (0xF, _, 0x2, 0x9) => format!("LD F, V{:X}", x),
+        (0xF, _, 0x3, 0x0) => format!("LD HF, V{:X}", x),
+        (0xF, _, 0x3, 0x3) => format!("LD B, V{:X}", x),
+        (0xF, _, 0x3, 0xA) => format!("PITCH V{:X}", x),
+        (0xF, _, 0x5, 0x5) => format!("LD [I], V{:X}", x),
+        (0xF, _, 0x6, 0x5) => format!("LD V{:X}, [I]", x),
+        (0xF, _, 0x7, 0x5) => format!("LD R, V{:X}", x),
+        (0xF, _, 0x8, 0x5) => format!("LD V{:X}, R", x),
+        _ => format!("DW 0x{:04X}", opcode),
+    }
+}
+
+/// Инструкции, которые есть только в XO-CHIP
+fn is_xo_only(opcode: u16) -> bool {
+    matches!(
+        (opcode >> 12, opcode & 0x000F, opcode & 0x0FFF),
+        (0x0, _, 0x0D0..=0x0DF) | (0x5, 0x2 | 0x3, _) | (0xF, _, 0x000 | 0x002)
+    ) || (opcode & 0xF0FF) == 0xF001
+        || (opcode & 0xF0FF) == 0xF03A
+}
+
+/// Известна ли инструкция выбранному варианту машины
+fn is_valid(opcode: u16, variant: Variant) -> bool {
+    if is_xo_only(opcode) {
+        return variant == Variant::XoChip;
+    }
+    !format_instruction(opcode, |_| String::new()).starts_with("DW")
+}
+
+/// Строка листинга
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Line {
+    Label(String),
+    Instruction { addr: u16, bytes: Vec<u8>, text: String },
+    Data { addr: u16, bytes: Vec<u8> },
+}
+
+/// Результат дизассемблирования: код отделён от данных, у переходов есть метки
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Disassembly {
+    pub lines: Vec<Line>,
+}
+
+impl Disassembly {
+    /// Адреса, до которых дошёл анализ потока управления
+    pub fn code_addresses(&self) -> impl Iterator<Item = u16> + '_ {
+        self.lines.iter().filter_map(|line| match line {
+            Line::Instruction { addr, .. } => Some(*addr),
+            _ => None,
+        })
+    }
+}
+
+impl fmt::Display for Disassembly {
+    /// Листинг, пригодный для повторной сборки; адрес и байты - в комментарии
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for line in &self.lines {
+            match line {
+                Line::Label(name) => writeln!(f, "{}:", name)?,
+                Line::Instruction { addr, bytes, text } => {
+                    let hex: String = bytes.iter().map(|b| format!("{:02X}", b)).collect();
+                    writeln!(f, "    {:<24} ; {:04X}: {}", text, addr, hex)?;
+                }
+                Line::Data { addr, bytes } => {
+                    let list: Vec<String> = bytes.iter().map(|b| format!("0x{:02X}", b)).collect();
+                    writeln!(f, "    {:<24} ; {:04X}", format!("db {}", list.join(", ")), addr)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Что известно об адресе, на который ссылается программа
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Reference {
+    // Порядок важен: более "сильный" вид ссылки побеждает при совпадении адресов
+    Data,
+    Jump,
+    Call,
+}
+
+struct Analyzer<'a> {
+    rom: &'a [u8],
+    variant: Variant,
+    // Начало инструкции -> её длина
+    code: BTreeMap<u16, u16>,
+    references: BTreeMap<u16, Reference>,
+}
+
+impl<'a> Analyzer<'a> {
+    fn end(&self) -> usize {
+        PROGRAM_START + self.rom.len()
+    }
+
+    fn opcode_at(&self, addr: u16) -> Option<u16> {
+        let offset = (addr as usize).checked_sub(PROGRAM_START)?;
+        let bytes = self.rom.get(offset..offset + 2)?;
+        Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    /// Длина инструкции по адресу: F000 NNNN в XO-CHIP занимает 4 байта
+    fn length_at(&self, addr: u16) -> u16 {
+        if self.variant == Variant::XoChip && self.opcode_at(addr) == Some(0xF000) {
+            4
+        } else {
+            2
+        }
+    }
+
+    fn reference(&mut self, addr: u16, kind: Reference) {
+        let entry = self.references.entry(addr).or_insert(kind);
+        *entry = (*entry).max(kind);
+    }
+
+    /// Обход всех путей исполнения от точки входа
+    fn trace_code(&mut self) {
+        let mut pending = vec![PROGRAM_START as u16];
+        while let Some(addr) = pending.pop() {
+            if self.code.contains_key(&addr) {
+                continue;
+            }
+            let Some(opcode) = self.opcode_at(addr) else {
+                continue;
+            };
+            if !is_valid(opcode, self.variant) {
+                continue;
+            }
+            let len = self.length_at(addr);
+            if addr as usize + len as usize > self.end() {
+                continue;
+            }
+            self.code.insert(addr, len);
+
+            let next = addr.wrapping_add(len);
+            let nnn = opcode & 0x0FFF;
+            match opcode >> 12 {
+                0x0 if opcode == 0x00EE || opcode == 0x00FD => {}
+                0x1 => {
+                    self.reference(nnn, Reference::Jump);
+                    pending.push(nnn);
+                }
+                0x2 => {
+                    self.reference(nnn, Reference::Call);
+                    pending.push(nnn);
+                    pending.push(next);
+                }
+                0x3 | 0x4 | 0x5 | 0x9 | 0xE => {
+                    pending.push(next);
+                    pending.push(next.wrapping_add(self.length_at(next)));
+                }
+                0xA => {
+                    self.reference(nnn, Reference::Data);
+                    pending.push(next);
+                }
+                // Вычисляемый переход: цель неизвестна, помечаем только базу
+                0xB => self.reference(nnn, Reference::Jump),
+                0xF if opcode == 0xF000 => {
+                    if let Some(target) = self.opcode_at(addr + 2) {
+                        self.reference(target, Reference::Data);
+                    }
+                    pending.push(next);
+                }
+                _ => pending.push(next),
+            }
+        }
+    }
+
+    /// Разложить ROM на инструкции и блоки данных
+    fn layout(&self) -> Vec<(u16, Option<u16>)> {
+        // (адрес, Some(длина инструкции) | None для байта данных)
+        let mut items = Vec::new();
+        let mut addr = PROGRAM_START;
+        while addr < self.end() {
+            match self.code.get(&(addr as u16)) {
+                Some(&len) => {
+                    items.push((addr as u16, Some(len)));
+                    addr += len as usize;
+                }
+                None => {
+                    items.push((addr as u16, None));
+                    addr += 1;
+                }
+            }
+        }
+        items
+    }
+}
+
+/// Имя метки по виду ссылки
+fn label_name(addr: u16, kind: Reference) -> String {
+    match kind {
+        Reference::Call => format!("sub_{:04X}", addr),
+        Reference::Jump => format!("L{:04X}", addr),
+        Reference::Data => format!("data_{:04X}", addr),
+    }
+}
+
+/// Дизассемблировать ROM, загруженный с адреса 0x200
+pub fn disassemble(rom: &[u8], variant: Variant) -> Disassembly {
+    let mut analyzer = Analyzer {
+        rom,
+        variant,
+        code: BTreeMap::new(),
+        references: BTreeMap::new(),
+    };
+    analyzer.trace_code();
+    let items = analyzer.layout();
+
+    // Метку получает только адрес, с которого начинается строка листинга
+    let starts: BTreeSet<u16> = items.iter().map(|&(addr, _)| addr).collect();
+    let labels: BTreeMap<u16, String> = analyzer
+        .references
+        .iter()
+        .filter(|(addr, _)| starts.contains(addr))
+        .map(|(&addr, &kind)| (addr, label_name(addr, kind)))
+        .collect();
+    let address = |addr: u16| labels.get(&addr).cloned().unwrap_or_else(|| format!("0x{:03X}", addr));
+
+    let mut lines = Vec::new();
+    let mut data: Option<(u16, Vec<u8>)> = None;
+    for (addr, kind) in items {
+        let label = labels.get(&addr);
+        let flush = kind.is_some()
+            || label.is_some()
+            || data.as_ref().is_some_and(|(_, bytes)| bytes.len() == DATA_PER_LINE);
+        if flush && let Some((start, bytes)) = data.take() {
+            lines.push(Line::Data { addr: start, bytes });
+        }
+        if let Some(name) = label {
+            lines.push(Line::Label(name.clone()));
+        }
+
+        let offset = addr as usize - PROGRAM_START;
+        match kind {
+            Some(len) => {
+                let bytes = rom[offset..offset + len as usize].to_vec();
+                let opcode = u16::from_be_bytes([bytes[0], bytes[1]]);
+                let text = if len == 4 {
+                    format!("LD I, LONG {}", address(u16::from_be_bytes([bytes[2], bytes[3]])))
+                } else {
+                    format_instruction(opcode, address)
+                };
+                lines.push(Line::Instruction { addr, bytes, text });
+            }
+            None => data.get_or_insert_with(|| (addr, Vec::new())).1.push(rom[offset]),
+        }
+    }
+    if let Some((addr, bytes)) = data {
+        lines.push(Line::Data { addr, bytes });
+    }
+
+    Disassembly { lines }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(disassembly: &Disassembly) -> Vec<String> {
+        disassembly
+            .lines
+            .iter()
+            .map(|line| match line {
+                Line::Label(name) => format!("{}:", name),
+                Line::Instruction { text, .. } => text.clone(),
+                Line::Data { bytes, .. } => format!("db {:02X?}", bytes),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn mnemonics_cover_all_variants() {
+        assert_eq!(mnemonic(0x00E0), "CLS");
+        assert_eq!(mnemonic(0x1234), "JP 0x234");
+        assert_eq!(mnemonic(0x8AB6), "SHR VA, VB");
+        assert_eq!(mnemonic(0xD125), "DRW V1, V2, 5");
+        assert_eq!(mnemonic(0x00C4), "SCD 4");
+        assert_eq!(mnemonic(0xF330), "LD HF, V3");
+        assert_eq!(mnemonic(0x5232), "SAVE V2, V3");
+        assert_eq!(mnemonic(0xF201), "PLANE 2");
+        assert_eq!(mnemonic(0xE1FF), "DW 0xE1FF");
+    }
+
+    #[test]
+    fn separates_code_from_sprite_data() {
+        let rom = [
+            0xA2, 0x08, // 200: LD I, sprite
+            0xD0, 0x12, // 202: DRW V0, V1, 2
+            0x22, 0x0A, // 204: CALL sub
+            0x12, 0x06, // 206: JP 206
+            0xFF, 0x81, // 208: спрайт
+            0x00, 0xEE, // 20A: RET
+        ];
+        let disassembly = disassemble(&rom, Variant::Classic);
+        assert_eq!(
+            text(&disassembly),
+            [
+                "LD I, data_0208",
+                "DRW V0, V1, 2",
+                "CALL sub_020A",
+                "L0206:",
+                "JP L0206",
+                "data_0208:",
+                "db [FF, 81]",
+                "sub_020A:",
+                "RET",
+            ]
+        );
+        assert_eq!(disassembly.code_addresses().collect::<Vec<_>>(), [0x200, 0x202, 0x204, 0x206, 0x20A]);
+    }
+
+    #[test]
+    fn skips_follow_both_paths() {
+        // 200: SE V0, 0; 202: JP 208; 204: JP 206; 206: RET; 208: EXIT
+        let rom = [0x30, 0x00, 0x12, 0x08, 0x12, 0x06, 0x00, 0xEE, 0x00, 0xFD, 0x12, 0x34];
+        let disassembly = disassemble(&rom, Variant::Classic);
+        let code: Vec<u16> = disassembly.code_addresses().collect();
+        assert_eq!(code, [0x200, 0x202, 0x204, 0x206, 0x208]);
+        assert!(matches!(disassembly.lines.last(), Some(Line::Data { addr: 0x20A, .. })));
+    }
+
+    #[test]
+    fn xo_long_index_is_one_instruction() {
+        // 200: LD I, LONG 0x0206; 204: EXIT; 206: данные
+        let rom = [0xF0, 0x00, 0x02, 0x06, 0x00, 0xFD, 0xAA];
+        let disassembly = disassemble(&rom, Variant::XoChip);
+        assert_eq!(
+            text(&disassembly),
+            ["LD I, LONG data_0206", "EXIT", "data_0206:", "db [AA]"]
+        );
+
+        // В классическом варианте это не код
+        let disassembly = disassemble(&rom, Variant::Classic);
+        assert_eq!(disassembly.code_addresses().count(), 0);
+    }
+
+    #[test]
+    fn listing_has_addresses_in_comments() {
+        let listing = disassemble(&[0x00, 0xE0, 0x12, 0x02], Variant::Classic).to_string();
+        assert_eq!(
+            listing,
+            format!("    {:<24} ; 0200: 00E0\nL0202:\n    {:<24} ; 0202: 1202\n", "CLS", "JP L0202")
+        );
+    }
+}
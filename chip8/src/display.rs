@@ -1,85 +1,200 @@
-use crate::constants::{SCREEN_WIDTH, SCREEN_HEIGHT};
+use crate::constants::{SCREEN_WIDTH, SCREEN_HEIGHT, HIRES_WIDTH, HIRES_HEIGHT, MAX_PLANES};
+use crate::video::Palette;
 
 pub struct Display {
-    // Пиксели экрана: true = включен, false = выключен
-    pub pixels: [[bool; SCREEN_WIDTH]; SCREEN_HEIGHT],
+    // Пиксели экрана: каждый бит - одна плоскость (XO-CHIP), 0 = выключен
+    // Память рассчитана на hi-res, в lo-res используется левый верхний угол
+    pub pixels: [[u8; HIRES_WIDTH]; HIRES_HEIGHT],
+    // Режим высокого разрешения SUPER-CHIP (128x64)
+    pub hires: bool,
+    // Выбранные плоскости для рисования (FN01), по умолчанию только первая
+    pub planes: u8,
     // Флаг что экран нужно перерисовать
     pub needs_redraw: bool,
 }
 
+impl Default for Display {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Display {
     pub fn new() -> Self {
         Display {
-            pixels: [[false; SCREEN_WIDTH]; SCREEN_HEIGHT],
+            pixels: [[0; HIRES_WIDTH]; HIRES_HEIGHT],
+            hires: false,
+            planes: 1,
             needs_redraw: true, // Первый кадр нужно нарисовать
         }
     }
 
-    /// Очистка экрана
+    /// Текущая ширина экрана
+    pub fn width(&self) -> usize {
+        if self.hires { HIRES_WIDTH } else { SCREEN_WIDTH }
+    }
+
+    /// Текущая высота экрана
+    pub fn height(&self) -> usize {
+        if self.hires { HIRES_HEIGHT } else { SCREEN_HEIGHT }
+    }
+
+    /// Очистка выбранных плоскостей
     pub fn clear(&mut self) {
-        for y in 0..SCREEN_HEIGHT {
-            for x in 0..SCREEN_WIDTH {
-                self.pixels[y][x] = false;
+        let keep = !self.planes;
+        for row in self.pixels.iter_mut() {
+            for pixel in row.iter_mut() {
+                *pixel &= keep;
             }
         }
         self.needs_redraw = true;
     }
 
+    /// Переключение разрешения (00FE/00FF), экран очищается целиком
+    pub fn set_hires(&mut self, hires: bool) {
+        self.hires = hires;
+        self.pixels = [[0; HIRES_WIDTH]; HIRES_HEIGHT];
+        self.needs_redraw = true;
+    }
+
+    /// Число выбранных плоскостей - столько спрайтов подряд читает DXYN
+    pub fn plane_count(&self) -> usize {
+        self.planes.count_ones() as usize
+    }
+
     /// Отрисовка спрайта
     /// Возвращает true если были коллизии (пиксели перезаписывались)
-    pub fn draw_sprite(&mut self, x: u8, y: u8, sprite: &[u8]) -> bool {
+    ///
+    /// Начальная точка всегда заворачивается, а части спрайта за краем
+    /// экрана либо обрезаются (`clip`), либо переносятся на другую сторону.
+    /// При нескольких выбранных плоскостях данные идут подряд, по плоскости на кусок.
+    pub fn draw_sprite(&mut self, x: u8, y: u8, sprite: &[u8], clip: bool) -> bool {
+        let rows: Vec<u16> = sprite.iter().map(|&byte| (byte as u16) << 8).collect();
+        self.draw_planes(x, y, &rows, 8, clip)
+    }
+
+    /// Отрисовка спрайта 16x16 для DXY0 (SUPER-CHIP), по два байта на строку
+    pub fn draw_large_sprite(&mut self, x: u8, y: u8, sprite: &[u8], clip: bool) -> bool {
+        let rows: Vec<u16> = sprite
+            .chunks(2)
+            .map(|pair| ((pair[0] as u16) << 8) | pair.get(1).copied().unwrap_or(0) as u16)
+            .collect();
+        self.draw_planes(x, y, &rows, 16, clip)
+    }
+
+    /// Делит строки спрайта между выбранными плоскостями
+    fn draw_planes(&mut self, x: u8, y: u8, rows: &[u16], sprite_width: usize, clip: bool) -> bool {
+        let count = self.plane_count();
+        let mut collision = false;
+        if count == 0 {
+            return collision;
+        }
+
+        let per_plane = rows.len() / count;
+        let mut chunks = rows.chunks(per_plane.max(1));
+        for plane in 0..MAX_PLANES {
+            let mask = 1 << plane;
+            if self.planes & mask == 0 {
+                continue;
+            }
+            if let Some(chunk) = chunks.next() {
+                collision |= self.draw_rows(x, y, chunk, sprite_width, clip, mask);
+            }
+        }
+
+        self.needs_redraw = true;
+        collision
+    }
+
+    /// Общая отрисовка: строки выровнены по старшему биту, ширина до 16 пикселей
+    fn draw_rows(&mut self, x: u8, y: u8, rows: &[u16], sprite_width: usize, clip: bool, mask: u8) -> bool {
+        let (width, height) = (self.width(), self.height());
         let mut collision = false;
-        let x = x as usize % SCREEN_WIDTH;
-        let y = y as usize % SCREEN_HEIGHT;
+        let x = x as usize % width;
+        let y = y as usize % height;
 
-        for (row, &byte) in sprite.iter().enumerate() {
-            let y_pos = (y + row) % SCREEN_HEIGHT;
+        for (row, &bits) in rows.iter().enumerate() {
+            if clip && y + row >= height {
+                break;
+            }
+            let y_pos = (y + row) % height;
+
+            for bit in 0..sprite_width {
+                if clip && x + bit >= width {
+                    break;
+                }
+                let x_pos = (x + bit) % width;
+                let sprite_pixel = (bits >> (15 - bit)) & 1 == 1;
 
-            for bit in 0..8 {
-                let x_pos = (x + bit) % SCREEN_WIDTH;
-                let sprite_pixel = (byte >> (7 - bit)) & 1 == 1;
-                
                 if sprite_pixel {
                     let current_pixel = &mut self.pixels[y_pos][x_pos];
-                    if *current_pixel {
+                    if *current_pixel & mask != 0 {
                         collision = true;
                     }
-                    *current_pixel ^= true;
+                    *current_pixel ^= mask;
                 }
             }
         }
 
-        self.needs_redraw = true;
         collision
     }
 
-    /// Конвертируем пиксели CHIP-8 в буфер для minifb
-    pub fn to_buffer(&self) -> Vec<u32> {
-        let mut buffer = vec![0; SCREEN_WIDTH * SCREEN_HEIGHT];
-        
-        for y in 0..SCREEN_HEIGHT {
-            for x in 0..SCREEN_WIDTH {
-                let index = y * SCREEN_WIDTH + x;
-                // Белый цвет для включенных пикселей, черный для выключенных
-                buffer[index] = if self.pixels[y][x] { 0xFFFFFF } else { 0x000000 };
+    /// Сдвиг выбранных плоскостей: новое значение пикселя (x, y) берётся
+    /// из `source(x, y)`, остальные плоскости не трогаются
+    fn scroll(&mut self, source: impl Fn(usize, usize) -> Option<(usize, usize)>) {
+        let (width, height) = (self.width(), self.height());
+        let old = self.pixels;
+        let mask = self.planes;
+
+        for y in 0..height {
+            for x in 0..width {
+                let moved = source(x, y).map_or(0, |(sx, sy)| old[sy][sx]);
+                self.pixels[y][x] = (old[y][x] & !mask) | (moved & mask);
             }
         }
-        
-        buffer
+        self.needs_redraw = true;
+    }
+
+    /// 00CN - прокрутка вниз на N строк
+    pub fn scroll_down(&mut self, n: usize) {
+        self.scroll(|x, y| y.checked_sub(n).map(|sy| (x, sy)));
+    }
+
+    /// 00DN - прокрутка вверх на N строк (XO-CHIP)
+    pub fn scroll_up(&mut self, n: usize) {
+        let height = self.height();
+        self.scroll(|x, y| (y + n < height).then_some((x, y + n)));
+    }
+
+    /// 00FB - прокрутка вправо на N пикселей
+    pub fn scroll_right(&mut self, n: usize) {
+        self.scroll(|x, y| x.checked_sub(n).map(|sx| (sx, y)));
+    }
+
+    /// 00FC - прокрутка влево на N пикселей
+    pub fn scroll_left(&mut self, n: usize) {
+        let width = self.width();
+        self.scroll(|x, y| (x + n < width).then_some((x + n, y)));
+    }
+
+    /// Конвертируем пиксели CHIP-8 в буфер 0RGB (палитра по умолчанию)
+    pub fn to_buffer(&self) -> Vec<u32> {
+        Palette::default().render(self)
     }
 
     /// Отладочный вывод экрана в консоль
     pub fn debug_print(&self) {
-        println!("┌{}┐", "─".repeat(SCREEN_WIDTH));
-        
-        for y in 0..SCREEN_HEIGHT {
+        let (width, height) = (self.width(), self.height());
+        println!("┌{}┐", "─".repeat(width));
+
+        for y in 0..height {
             print!("│");
-            for x in 0..SCREEN_WIDTH {
-                print!("{}", if self.pixels[y][x] { "█" } else { " " });
+            for x in 0..width {
+                print!("{}", if self.pixels[y][x] != 0 { "█" } else { " " });
             }
             println!("│");
         }
-        
-        println!("└{}┘", "─".repeat(SCREEN_WIDTH));
+
+        println!("└{}┘", "─".repeat(width));
     }
-}
\ No newline at end of file
+}
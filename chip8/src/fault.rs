@@ -0,0 +1,74 @@
+use std::fmt;
+use std::str::FromStr;
+
+use thiserror::Error;
+
+/// Ошибка выполнения программы; `pc` - адрес сбойной инструкции
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Chip8Fault {
+    #[error("Stack overflow at {pc:04X}")]
+    StackOverflow { pc: u16 },
+
+    #[error("Stack underflow at {pc:04X}")]
+    StackUnderflow { pc: u16 },
+
+    #[error("Unknown opcode {opcode:04X} at {pc:04X}")]
+    UnknownOpcode { pc: u16, opcode: u16 },
+
+    #[error("Memory access out of bounds at {pc:04X}: {len} bytes from {addr:04X}")]
+    MemoryOutOfBounds { pc: u16, addr: usize, len: usize },
+}
+
+impl Chip8Fault {
+    pub fn pc(&self) -> u16 {
+        match *self {
+            Chip8Fault::StackOverflow { pc }
+            | Chip8Fault::StackUnderflow { pc }
+            | Chip8Fault::UnknownOpcode { pc, .. }
+            | Chip8Fault::MemoryOutOfBounds { pc, .. } => pc,
+        }
+    }
+}
+
+/// Что фронтенд делает при сбое
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FaultPolicy {
+    /// Остановить эмуляцию
+    Halt,
+    /// Приостановить на сбойной инструкции
+    #[default]
+    Pause,
+    /// Пропустить сбойную инструкцию и продолжить
+    Ignore,
+}
+
+impl FaultPolicy {
+    pub fn all() -> [FaultPolicy; 3] {
+        [FaultPolicy::Halt, FaultPolicy::Pause, FaultPolicy::Ignore]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            FaultPolicy::Halt => "halt",
+            FaultPolicy::Pause => "pause",
+            FaultPolicy::Ignore => "ignore",
+        }
+    }
+}
+
+impl fmt::Display for FaultPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for FaultPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        FaultPolicy::all()
+            .into_iter()
+            .find(|policy| policy.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| format!("unknown fault policy '{}', expected halt, pause or ignore", s))
+    }
+}
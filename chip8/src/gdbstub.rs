@@ -0,0 +1,636 @@
+use std::io::{self, ErrorKind, Read, Write};
+
+use crate::debugger::{Debugger, Stop};
+use crate::fault::Chip8Fault;
+use crate::machine::Chip8;
+
+/// Описание регистров для GDB (qXfer:features:read:target.xml)
+const TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <feature name="org.chip8.core">
+    <reg name="v0" bitsize="8" regnum="0"/>
+    <reg name="v1" bitsize="8"/>
+    <reg name="v2" bitsize="8"/>
+    <reg name="v3" bitsize="8"/>
+    <reg name="v4" bitsize="8"/>
+    <reg name="v5" bitsize="8"/>
+    <reg name="v6" bitsize="8"/>
+    <reg name="v7" bitsize="8"/>
+    <reg name="v8" bitsize="8"/>
+    <reg name="v9" bitsize="8"/>
+    <reg name="va" bitsize="8"/>
+    <reg name="vb" bitsize="8"/>
+    <reg name="vc" bitsize="8"/>
+    <reg name="vd" bitsize="8"/>
+    <reg name="ve" bitsize="8"/>
+    <reg name="vf" bitsize="8"/>
+    <reg name="i" bitsize="16" type="data_ptr"/>
+    <reg name="pc" bitsize="16" type="code_ptr"/>
+    <reg name="sp" bitsize="8"/>
+    <reg name="dt" bitsize="8"/>
+    <reg name="st" bitsize="8"/>
+  </feature>
+</target>
+"#;
+
+/// Номера регистров: 0-15 V0-VF, затем I, PC, SP, DT, ST
+const REG_I: usize = 16;
+const REG_PC: usize = 17;
+const REG_SP: usize = 18;
+const REG_DT: usize = 19;
+const REG_ST: usize = 20;
+const REGISTER_COUNT: usize = 21;
+
+// Сигналы в ответах об остановке
+const SIGINT: u8 = 2;
+const SIGILL: u8 = 4;
+const SIGTRAP: u8 = 5;
+const SIGSEGV: u8 = 11;
+
+/// Состояние сеанса после обработки входящих данных
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Session {
+    Active,
+    /// Отладчик отключился (D или закрыл соединение), эмуляция продолжается
+    Detached,
+    /// Отладчик попросил завершить программу (k)
+    Killed,
+}
+
+/// Разобранный кусок входного потока
+enum Incoming {
+    Packet(String),
+    Interrupt,
+    BadChecksum,
+}
+
+/// Сервер протокола GDB Remote Serial Protocol поверх любого потока.
+/// Поток должен быть неблокирующим: `WouldBlock` означает "данных пока нет"
+/// при чтении и "буфер отправки полон" при записи.
+pub struct GdbStub<S: Read + Write> {
+    stream: S,
+    debugger: Debugger,
+    // Принятые, но ещё не разобранные байты
+    input: Vec<u8>,
+    // Ответы, которые поток ещё не принял; дописываются при следующем poll
+    output: Vec<u8>,
+}
+
+impl<S: Read + Write> GdbStub<S> {
+    pub fn new(stream: S) -> Self {
+        GdbStub {
+            stream,
+            debugger: Debugger::new(),
+            input: Vec::new(),
+            output: Vec::new(),
+        }
+    }
+
+    /// Выполняется ли программа (после `c`)
+    pub fn is_running(&self) -> bool {
+        !self.debugger.paused
+    }
+
+    /// Прочитать всё, что прислал клиент, и ответить на пакеты
+    pub fn poll(&mut self, chip8: &mut Chip8) -> io::Result<Session> {
+        let mut buf = [0; 1024];
+        loop {
+            match self.stream.read(&mut buf) {
+                Ok(0) => return Ok(Session::Detached),
+                Ok(n) => self.input.extend_from_slice(&buf[..n]),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        }
+
+        while let Some(incoming) = self.next_incoming() {
+            match incoming {
+                Incoming::Interrupt => {
+                    if self.is_running() {
+                        self.debugger.paused = true;
+                        self.send(&format!("S{:02x}", SIGINT))?;
+                    }
+                }
+                Incoming::BadChecksum => self.output.push(b'-'),
+                Incoming::Packet(packet) => {
+                    self.output.push(b'+');
+                    let session = self.handle(&packet, chip8)?;
+                    if session != Session::Active {
+                        self.write_output()?;
+                        return Ok(session);
+                    }
+                }
+            }
+        }
+        self.write_output()?;
+        Ok(Session::Active)
+    }
+
+    /// Кадр эмуляции, пока клиент дал команду `c`; об остановке сообщает клиенту
+    pub fn run_frame(&mut self, chip8: &mut Chip8, instructions: usize) -> io::Result<Option<Stop>> {
+        let stop = self.debugger.run_frame(chip8, instructions);
+        if let Some(stop) = &stop {
+            self.send(&stop_reply(stop))?;
+        }
+        self.write_output()?;
+        Ok(stop)
+    }
+
+    /// Отдать потоку сколько он примет; остаток ждёт следующего вызова
+    fn write_output(&mut self) -> io::Result<()> {
+        while !self.output.is_empty() {
+            match self.stream.write(&self.output) {
+                Ok(0) => return Err(ErrorKind::WriteZero.into()),
+                Ok(n) => {
+                    self.output.drain(..n);
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(()),
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        match self.stream.flush() {
+            Err(e) if e.kind() == ErrorKind::WouldBlock => Ok(()),
+            result => result,
+        }
+    }
+
+    fn next_incoming(&mut self) -> Option<Incoming> {
+        loop {
+            match *self.input.first()? {
+                0x03 => {
+                    self.input.remove(0);
+                    return Some(Incoming::Interrupt);
+                }
+                b'$' => break,
+                // Подтверждения '+'/'-' и мусор между пакетами пропускаем
+                _ => {
+                    self.input.remove(0);
+                }
+            }
+        }
+
+        let hash = self.input.iter().position(|&b| b == b'#')?;
+        if self.input.len() < hash + 3 {
+            return None;
+        }
+        let packet: Vec<u8> = self.input.drain(..hash + 3).collect();
+        let body = &packet[1..hash];
+        let checksum = std::str::from_utf8(&packet[hash + 1..])
+            .ok()
+            .and_then(|s| u8::from_str_radix(s, 16).ok());
+        if checksum != Some(checksum_of(body)) {
+            return Some(Incoming::BadChecksum);
+        }
+        Some(Incoming::Packet(String::from_utf8_lossy(body).into_owned()))
+    }
+
+    fn send(&mut self, data: &str) -> io::Result<()> {
+        write!(self.output, "${}#{:02x}", data, checksum_of(data.as_bytes()))
+    }
+
+    fn handle(&mut self, packet: &str, chip8: &mut Chip8) -> io::Result<Session> {
+        let reply = match packet.as_bytes().first() {
+            Some(b'?') => format!("S{:02x}", SIGTRAP),
+            Some(b'g') => read_registers(chip8),
+            Some(b'G') => write_registers(chip8, &packet[1..]),
+            Some(b'p') => usize::from_str_radix(&packet[1..], 16)
+                .ok()
+                .filter(|&reg| reg < REGISTER_COUNT)
+                .map_or_else(|| "E01".to_string(), |reg| encode_register(chip8, reg)),
+            Some(b'P') => write_register(chip8, &packet[1..]),
+            Some(b'm') => read_memory(chip8, &packet[1..]),
+            Some(b'M') => write_memory(chip8, &packet[1..]),
+            Some(b'Z') | Some(b'z') => self.breakpoint(packet),
+            Some(b's') => {
+                self.resume_at(chip8, &packet[1..]);
+                self.debugger.paused = true;
+                match self.debugger.single_step(chip8) {
+                    Some(stop @ (Stop::Fault(_) | Stop::Halted)) => stop_reply(&stop),
+                    Some(stop @ Stop::WaitingForKey(_)) => {
+                        // Объяснение для консоли GDB, иначе stepi молча стоит на месте
+                        self.send(&format!("O{}", encode_hex(format!("{}\n", stop).as_bytes())))?;
+                        stop_reply(&stop)
+                    }
+                    _ => format!("S{:02x}", SIGTRAP),
+                }
+            }
+            Some(b'c') => {
+                self.resume_at(chip8, &packet[1..]);
+                self.debugger.paused = false;
+                // Ответ придёт, когда программа остановится
+                return Ok(Session::Active);
+            }
+            Some(b'k') => return Ok(Session::Killed),
+            Some(b'D') => {
+                self.send("OK")?;
+                return Ok(Session::Detached);
+            }
+            Some(b'H') => "OK".to_string(),
+            _ => query(packet),
+        };
+        self.send(&reply)?;
+        Ok(Session::Active)
+    }
+
+    /// `c ADDR` / `s ADDR` продолжают с указанного адреса
+    fn resume_at(&mut self, chip8: &mut Chip8, addr: &str) {
+        if let Ok(addr) = u16::from_str_radix(addr, 16) {
+            chip8.cpu_mut().program_counter = addr;
+        }
+    }
+
+    /// Z0,ADDR,KIND / z0,ADDR,KIND - программные точки останова
+    fn breakpoint(&mut self, packet: &str) -> String {
+        let mut parts = packet[1..].split(',');
+        let (Some("0"), Some(addr)) = (parts.next(), parts.next()) else {
+            // Аппаратные точки и точки наблюдения не поддерживаются
+            return String::new();
+        };
+        let Ok(addr) = u16::from_str_radix(addr, 16) else {
+            return "E01".to_string();
+        };
+        if packet.starts_with('Z') {
+            self.debugger.add_breakpoint(addr);
+        } else {
+            self.debugger.remove_breakpoint(addr);
+        }
+        "OK".to_string()
+    }
+}
+
+fn checksum_of(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |sum, &b| sum.wrapping_add(b))
+}
+
+fn stop_reply(stop: &Stop) -> String {
+    let signal = match stop {
+        Stop::Fault(Chip8Fault::UnknownOpcode { .. }) => SIGILL,
+        Stop::Fault(_) => SIGSEGV,
+        Stop::Halted => return "W00".to_string(),
+        Stop::Breakpoint(_) | Stop::Reached(_) | Stop::Watch { .. } | Stop::WaitingForKey(_) => SIGTRAP,
+    };
+    format!("S{:02x}", signal)
+}
+
+/// Ответы на запросы q*/v*; пустой ответ означает "не поддерживается"
+fn query(packet: &str) -> String {
+    if packet.starts_with("qSupported") {
+        "PacketSize=1000;qXfer:features:read+".to_string()
+    } else if packet == "qAttached" {
+        "1".to_string()
+    } else if packet == "qC" {
+        "QC1".to_string()
+    } else if packet == "qfThreadInfo" {
+        "m1".to_string()
+    } else if packet == "qsThreadInfo" {
+        "l".to_string()
+    } else if let Some(range) = packet.strip_prefix("qXfer:features:read:target.xml:") {
+        match parse_range(range) {
+            Some((offset, len)) => {
+                let data = TARGET_XML.as_bytes();
+                let start = offset.min(data.len());
+                let end = (start + len).min(data.len());
+                let chunk = String::from_utf8_lossy(&data[start..end]);
+                let more = if end < data.len() { 'm' } else { 'l' };
+                format!("{}{}", more, chunk)
+            }
+            None => "E01".to_string(),
+        }
+    } else {
+        String::new()
+    }
+}
+
+/// `ADDR,LEN` в шестнадцатеричном виде
+fn parse_range(s: &str) -> Option<(usize, usize)> {
+    let (addr, len) = s.split_once(',')?;
+    Some((usize::from_str_radix(addr, 16).ok()?, usize::from_str_radix(len, 16).ok()?))
+}
+
+fn decode_hex(s: &str) -> Option<Vec<u8>> {
+    if !s.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+fn encode_hex(data: &[u8]) -> String {
+    data.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Значение регистра в порядке байт little-endian
+fn encode_register(chip8: &Chip8, reg: usize) -> String {
+    let cpu = chip8.cpu();
+    match reg {
+        0..=15 => encode_hex(&[cpu.registers[reg]]),
+        REG_I => encode_hex(&cpu.index_register.to_le_bytes()),
+        REG_PC => encode_hex(&cpu.program_counter.to_le_bytes()),
+        REG_SP => encode_hex(&[cpu.stack_pointer]),
+        REG_DT => encode_hex(&[cpu.delay_timer]),
+        REG_ST => encode_hex(&[cpu.sound_timer]),
+        _ => unreachable!("register {} out of range", reg),
+    }
+}
+
+fn register_size(reg: usize) -> usize {
+    if reg == REG_I || reg == REG_PC { 2 } else { 1 }
+}
+
+fn set_register(chip8: &mut Chip8, reg: usize, bytes: &[u8]) {
+    let cpu = chip8.cpu_mut();
+    let word = || u16::from_le_bytes([bytes[0], bytes[1]]);
+    match reg {
+        0..=15 => cpu.registers[reg] = bytes[0],
+        REG_I => cpu.index_register = word(),
+        REG_PC => cpu.program_counter = word(),
+        // Указатель стека не может выйти за размер стека
+        REG_SP => cpu.stack_pointer = bytes[0].min(cpu.stack.len() as u8),
+        REG_DT => cpu.delay_timer = bytes[0],
+        REG_ST => cpu.sound_timer = bytes[0],
+        _ => unreachable!("register {} out of range", reg),
+    }
+}
+
+fn read_registers(chip8: &Chip8) -> String {
+    (0..REGISTER_COUNT).map(|reg| encode_register(chip8, reg)).collect()
+}
+
+fn write_registers(chip8: &mut Chip8, hex: &str) -> String {
+    let Some(bytes) = decode_hex(hex) else {
+        return "E01".to_string();
+    };
+    let total: usize = (0..REGISTER_COUNT).map(register_size).sum();
+    if bytes.len() != total {
+        return "E01".to_string();
+    }
+    let mut offset = 0;
+    for reg in 0..REGISTER_COUNT {
+        let size = register_size(reg);
+        set_register(chip8, reg, &bytes[offset..offset + size]);
+        offset += size;
+    }
+    "OK".to_string()
+}
+
+/// P<reg>=<value>
+fn write_register(chip8: &mut Chip8, args: &str) -> String {
+    let parsed = args.split_once('=').and_then(|(reg, value)| {
+        let reg = usize::from_str_radix(reg, 16).ok().filter(|&reg| reg < REGISTER_COUNT)?;
+        let bytes = decode_hex(value).filter(|bytes| bytes.len() == register_size(reg))?;
+        Some((reg, bytes))
+    });
+    match parsed {
+        Some((reg, bytes)) => {
+            set_register(chip8, reg, &bytes);
+            "OK".to_string()
+        }
+        None => "E01".to_string(),
+    }
+}
+
+/// m<addr>,<len>
+fn read_memory(chip8: &Chip8, args: &str) -> String {
+    match parse_range(args) {
+        Some((addr, len)) if addr.saturating_add(len) <= chip8.cpu().memory.len() => {
+            encode_hex(chip8.read_memory(addr, len))
+        }
+        _ => "E01".to_string(),
+    }
+}
+
+/// M<addr>,<len>:<data>
+fn write_memory(chip8: &mut Chip8, args: &str) -> String {
+    let parsed = args.split_once(':').and_then(|(range, data)| {
+        let (addr, len) = parse_range(range)?;
+        let data = decode_hex(data).filter(|data| data.len() == len)?;
+        Some((addr, data))
+    });
+    match parsed {
+        Some((addr, data)) if addr.saturating_add(data.len()) <= chip8.cpu().memory.len() => {
+            chip8.write_memory(addr, &data);
+            "OK".to_string()
+        }
+        _ => "E01".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{TcpListener, TcpStream};
+    use std::thread;
+    use std::time::Duration;
+
+    /// Простейший клиент: отправить пакет и дождаться ответа
+    struct Client {
+        stream: TcpStream,
+    }
+
+    impl Client {
+        fn request(&mut self, data: &str) -> String {
+            write!(self.stream, "${}#{:02x}", data, checksum_of(data.as_bytes())).unwrap();
+            self.reply()
+        }
+
+        fn reply(&mut self) -> String {
+            let mut packet = Vec::new();
+            let mut byte = [0];
+            loop {
+                self.stream.read_exact(&mut byte).unwrap();
+                match byte[0] {
+                    b'+' if packet.is_empty() => continue,
+                    b'#' => break,
+                    b => packet.push(b),
+                }
+            }
+            let mut checksum = [0; 2];
+            self.stream.read_exact(&mut checksum).unwrap();
+            self.stream.write_all(b"+").unwrap();
+            String::from_utf8(packet[1..].to_vec()).unwrap()
+        }
+    }
+
+    /// Запустить сценарий клиента в отдельном потоке, а сервер - здесь
+    fn session(rom: &[u8], script: impl FnOnce(&mut Client) + Send + 'static) -> Chip8 {
+        session_with(Chip8::default(), rom, script)
+    }
+
+    fn session_with(mut chip8: Chip8, rom: &[u8], script: impl FnOnce(&mut Client) + Send + 'static) -> Chip8 {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let client = thread::spawn(move || {
+            let mut client = Client { stream: TcpStream::connect(addr).unwrap() };
+            script(&mut client);
+            client.request("D");
+        });
+
+        let (stream, _) = listener.accept().unwrap();
+        stream.set_nonblocking(true).unwrap();
+        chip8.load(rom).unwrap();
+        let mut stub = GdbStub::new(stream);
+        while stub.poll(&mut chip8).unwrap() == Session::Active {
+            stub.run_frame(&mut chip8, 10).unwrap();
+            thread::sleep(Duration::from_millis(1));
+        }
+        client.join().unwrap();
+        chip8
+    }
+
+    #[test]
+    fn reads_and_writes_registers_and_memory() {
+        let chip8 = session(&[0x60, 0x2A], |client| {
+            assert_eq!(client.request("qSupported:swbreak+"), "PacketSize=1000;qXfer:features:read+");
+            assert_eq!(client.request("?"), "S05");
+
+            let registers = client.request("g");
+            assert_eq!(registers.len(), 46);
+            // PC = 0x0200 в little-endian
+            assert_eq!(&registers[36..40], "0002");
+
+            assert_eq!(client.request("s"), "S05");
+            assert_eq!(client.request("p0"), "2a");
+            assert_eq!(client.request("P10=0003"), "OK");
+            assert_eq!(client.request("p10"), "0003");
+
+            assert_eq!(client.request("M300,2:beef"), "OK");
+            assert_eq!(client.request("m300,3"), "beef00");
+            assert_eq!(client.request("mfff,2"), "E01");
+        });
+        assert_eq!(chip8.cpu().index_register, 0x300);
+        assert_eq!(chip8.read_memory(0x300, 2), &[0xBE, 0xEF]);
+    }
+
+    #[test]
+    fn continue_stops_at_breakpoint() {
+        // 200: V0 += 1; 202: JP 200
+        let chip8 = session(&[0x70, 0x01, 0x12, 0x00], |client| {
+            assert_eq!(client.request("Z0,202,2"), "OK");
+            assert_eq!(client.request("c"), "S05");
+            assert_eq!(client.request("p11"), "0202");
+            assert_eq!(client.request("c"), "S05");
+            assert_eq!(client.request("p0"), "02");
+            assert_eq!(client.request("z0,202,2"), "OK");
+        });
+        assert_eq!(chip8.cpu().registers[0], 2);
+    }
+
+    #[test]
+    fn reports_faults_and_interrupts() {
+        session(&[0x00, 0xEE], |client| {
+            assert_eq!(client.request("c"), "S0b");
+        });
+
+        // Бесконечный цикл прерывается Ctrl-C (0x03)
+        session(&[0x12, 0x00], |client| {
+            write!(client.stream, "$c#63").unwrap();
+            thread::sleep(Duration::from_millis(20));
+            client.stream.write_all(&[0x03]).unwrap();
+            assert_eq!(client.reply(), "S02");
+        });
+    }
+
+    #[test]
+    fn stepi_moves_past_a_draw_waiting_for_vblank() {
+        use crate::quirks::Preset;
+
+        // 200: DRW V0, V0, 1; 202: V1 = 1; 204: JP 204
+        let chip8 = Chip8::default().with_quirks(Preset::Vip.quirks());
+        let chip8 = session_with(chip8, &[0xD0, 0x01, 0x61, 0x01, 0x12, 0x04], |client| {
+            assert_eq!(client.request("s"), "S05");
+            assert_eq!(client.request("s"), "S05");
+            assert_eq!(client.request("p11"), "0402");
+        });
+        assert_eq!(chip8.cpu().registers[1], 1);
+    }
+
+    #[test]
+    fn stepi_explains_key_wait() {
+        session(&[0xF3, 0x0A], |client| {
+            let console = client.request("s");
+            let text = decode_hex(&console[1..]).unwrap();
+            assert_eq!(String::from_utf8(text).unwrap(), "Waiting for a key press into V3 (FX0A)\n");
+            assert_eq!(client.reply(), "S05");
+        });
+    }
+
+    #[test]
+    fn serves_target_description() {
+        session(&[], |client| {
+            let reply = client.request("qXfer:features:read:target.xml:0,1000");
+            assert!(reply.starts_with("l<?xml"));
+            assert!(reply.contains("name=\"pc\""));
+        });
+    }
+
+    #[test]
+    fn rejects_bad_checksum() {
+        session(&[], |client| {
+            client.stream.write_all(b"$g#00").unwrap();
+            let mut byte = [0];
+            client.stream.read_exact(&mut byte).unwrap();
+            assert_eq!(byte[0], b'-');
+        });
+    }
+
+    /// Поток с крошечным буфером отправки: принимает не больше `window`
+    /// байт, затем отвечает `WouldBlock`, пока буфер не освободят
+    struct Congested {
+        input: Vec<u8>,
+        sent: Vec<u8>,
+        window: usize,
+        room: usize,
+    }
+
+    impl Read for Congested {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.input.is_empty() {
+                return Err(ErrorKind::WouldBlock.into());
+            }
+            let n = buf.len().min(self.input.len());
+            buf[..n].copy_from_slice(&self.input[..n]);
+            self.input.drain(..n);
+            Ok(n)
+        }
+    }
+
+    impl Write for Congested {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if self.room == 0 {
+                return Err(ErrorKind::WouldBlock.into());
+            }
+            let n = buf.len().min(self.room);
+            self.sent.extend_from_slice(&buf[..n]);
+            self.room -= n;
+            Ok(n)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn queues_replies_while_stream_would_block() {
+        let mut chip8 = Chip8::default();
+        chip8.write_memory(0x300, &[0xAB; 0x100]);
+        let request = "m300,100";
+        let input = format!("${}#{:02x}", request, checksum_of(request.as_bytes())).into_bytes();
+        let mut stub = GdbStub::new(Congested { input, sent: Vec::new(), window: 64, room: 64 });
+
+        // Ответ не влезает в буфер за раз - это не обрыв соединения
+        for _ in 0..20 {
+            assert_eq!(stub.poll(&mut chip8).unwrap(), Session::Active);
+            stub.stream.room = stub.stream.window;
+        }
+
+        let reply = "ab".repeat(0x100);
+        let expected = format!("+${}#{:02x}", reply, checksum_of(reply.as_bytes()));
+        assert_eq!(String::from_utf8(stub.stream.sent.clone()).unwrap(), expected);
+    }
+}
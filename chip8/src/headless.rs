@@ -0,0 +1,301 @@
+use std::fmt;
+use std::fmt::Write as _;
+use std::str::FromStr;
+
+use crate::fault::Chip8Fault;
+use crate::Chip8;
+
+/// Коды выхода безоконного запуска
+pub const EXIT_HALT: i32 = 0;
+pub const EXIT_ERROR: i32 = 1;
+pub const EXIT_TIMEOUT: i32 = 2;
+pub const EXIT_FAULT: i32 = 3;
+
+/// Нажатие или отпускание клавиши в начале кадра `frame`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyEvent {
+    pub frame: u64,
+    pub key: u8,
+    pub pressed: bool,
+}
+
+/// Сценарий нажатий клавиш по кадрам.
+///
+/// По событию на строку (или через `;`), `#` - комментарий:
+///
+/// ```text
+/// 30 down 5      # нажать 5 на кадре 30
+/// 40 up 5        # отпустить на кадре 40
+/// 60 tap A 3     # нажать A на кадре 60 и отпустить через 3 кадра (по умолчанию 1)
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KeyTimeline {
+    // Отсортированы по кадру, порядок внутри кадра сохраняется
+    events: Vec<KeyEvent>,
+}
+
+impl KeyTimeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn events(&self) -> &[KeyEvent] {
+        &self.events
+    }
+
+    pub fn push(&mut self, event: KeyEvent) {
+        let at = self.events.partition_point(|e| e.frame <= event.frame);
+        self.events.insert(at, event);
+    }
+
+    /// Добавить события из другого сценария
+    pub fn extend(&mut self, other: KeyTimeline) {
+        for event in other.events {
+            self.push(event);
+        }
+    }
+
+    /// Кадр последнего события
+    pub fn last_frame(&self) -> Option<u64> {
+        self.events.last().map(|e| e.frame)
+    }
+
+    /// Применить события кадра `frame`
+    pub fn apply(&self, frame: u64, chip8: &mut Chip8) {
+        let start = self.events.partition_point(|e| e.frame < frame);
+        for event in self.events[start..].iter().take_while(|e| e.frame == frame) {
+            chip8.set_key(event.key, event.pressed);
+        }
+    }
+}
+
+impl FromStr for KeyTimeline {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut timeline = KeyTimeline::new();
+        for (number, line) in s.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("");
+            for entry in line.split(';') {
+                let words: Vec<&str> = entry.split_whitespace().collect();
+                if words.is_empty() {
+                    continue;
+                }
+                parse_event(&words, &mut timeline).map_err(|e| format!("line {}: {}", number + 1, e))?;
+            }
+        }
+        Ok(timeline)
+    }
+}
+
+fn parse_event(words: &[&str], timeline: &mut KeyTimeline) -> Result<(), String> {
+    let [frame, action, key, rest @ ..] = words else {
+        return Err(format!("expected 'FRAME down|up|tap KEY', got '{}'", words.join(" ")));
+    };
+    let frame: u64 = frame.parse().map_err(|_| format!("bad frame number '{}'", frame))?;
+    let key = u8::from_str_radix(key, 16)
+        .ok()
+        .filter(|&k| k < 16)
+        .ok_or_else(|| format!("bad key '{}', expected 0-F", key))?;
+
+    match (action.to_ascii_lowercase().as_str(), rest) {
+        ("down", []) => timeline.push(KeyEvent { frame, key, pressed: true }),
+        ("up", []) => timeline.push(KeyEvent { frame, key, pressed: false }),
+        ("tap", [] | [_]) => {
+            let hold: u64 = match rest.first() {
+                Some(n) => n.parse().ok().filter(|&n| n > 0).ok_or_else(|| format!("bad hold length '{}'", n))?,
+                None => 1,
+            };
+            timeline.push(KeyEvent { frame, key, pressed: true });
+            timeline.push(KeyEvent { frame: frame + hold, key, pressed: false });
+        }
+        ("down" | "up" | "tap", _) => return Err(format!("too many arguments for '{}'", action)),
+        _ => return Err(format!("unknown action '{}', expected down, up or tap", action)),
+    }
+    Ok(())
+}
+
+/// Чем закончился прогон
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// Программа остановилась сама (00FD) на кадре `frame`
+    Halted { frame: u64 },
+    /// Отработали все `frames` кадров
+    Timeout { frames: u64 },
+    /// Сбой на кадре `frame`
+    Fault { frame: u64, fault: Chip8Fault },
+}
+
+impl Outcome {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Outcome::Halted { .. } => "halt",
+            Outcome::Timeout { .. } => "timeout",
+            Outcome::Fault { .. } => "fault",
+        }
+    }
+
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Outcome::Halted { .. } => EXIT_HALT,
+            Outcome::Timeout { .. } => EXIT_TIMEOUT,
+            Outcome::Fault { .. } => EXIT_FAULT,
+        }
+    }
+
+    /// Сколько кадров было выполнено (кадр остановки считается)
+    pub fn frames(&self) -> u64 {
+        match *self {
+            Outcome::Halted { frame } | Outcome::Fault { frame, .. } => frame + 1,
+            Outcome::Timeout { frames } => frames,
+        }
+    }
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Outcome::Halted { frame } => write!(f, "halted on frame {}", frame),
+            Outcome::Timeout { frames } => write!(f, "still running after {} frames", frames),
+            Outcome::Fault { frame, fault } => write!(f, "fault on frame {}: {}", frame, fault),
+        }
+    }
+}
+
+/// Прогнать до `frames` кадров по `instructions` инструкций, подавая нажатия из сценария
+pub fn run(chip8: &mut Chip8, timeline: &KeyTimeline, frames: u64, instructions: usize) -> Outcome {
+    run_with(chip8, timeline, frames, instructions, |_, _| {})
+}
+
+/// То же, что [`run`], но после каждого выполненного кадра вызывает
+/// `on_frame(номер кадра, машина)` - например, для записи GIF
+pub fn run_with(
+    chip8: &mut Chip8,
+    timeline: &KeyTimeline,
+    frames: u64,
+    instructions: usize,
+    mut on_frame: impl FnMut(u64, &Chip8),
+) -> Outcome {
+    for frame in 0..frames {
+        timeline.apply(frame, chip8);
+        if let Err(fault) = chip8.run_frame(instructions) {
+            return Outcome::Fault { frame, fault };
+        }
+        on_frame(frame, chip8);
+        if !chip8.is_running() {
+            return Outcome::Halted { frame };
+        }
+    }
+    Outcome::Timeout { frames }
+}
+
+/// Итоговое состояние машины в JSON: регистры, стек, таймеры и память в hex
+pub fn state_json(chip8: &Chip8, outcome: &Outcome) -> String {
+    let cpu = chip8.cpu();
+    let fault = match outcome {
+        Outcome::Fault { fault, .. } => format!("\"{}\"", fault),
+        _ => "null".to_string(),
+    };
+    let memory = cpu.memory.iter().fold(String::with_capacity(cpu.memory.len() * 2), |mut out, byte| {
+        let _ = write!(out, "{:02x}", byte);
+        out
+    });
+
+    let mut out = String::new();
+    out.push_str("{\n");
+    let _ = writeln!(out, "  \"outcome\": \"{}\",", outcome.name());
+    let _ = writeln!(out, "  \"frames\": {},", outcome.frames());
+    let _ = writeln!(out, "  \"fault\": {},", fault);
+    let _ = writeln!(out, "  \"cycles\": {},", cpu.cycles);
+    let _ = writeln!(out, "  \"pc\": {},", cpu.program_counter);
+    let _ = writeln!(out, "  \"i\": {},", cpu.index_register);
+    let _ = writeln!(out, "  \"v\": [{}],", join(&cpu.registers));
+    let _ = writeln!(out, "  \"sp\": {},", cpu.stack_pointer);
+    let _ = writeln!(out, "  \"stack\": [{}],", join(&cpu.stack[..cpu.stack_pointer as usize]));
+    let _ = writeln!(out, "  \"dt\": {},", cpu.delay_timer);
+    let _ = writeln!(out, "  \"st\": {},", cpu.sound_timer);
+    let _ = writeln!(out, "  \"hires\": {},", cpu.display.hires);
+    let _ = writeln!(out, "  \"memory\": \"{}\"", memory);
+    out.push_str("}\n");
+    out
+}
+
+fn join<T: fmt::Display>(values: &[T]) -> String {
+    values.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::variant::Variant;
+
+    fn machine(program: &[u8]) -> Chip8 {
+        let mut chip8 = Chip8::new(Variant::Classic);
+        chip8.load(program).unwrap();
+        chip8
+    }
+
+    #[test]
+    fn parses_timeline() {
+        let timeline: KeyTimeline = "# start\n30 down 5\n40 up 5 # release\n10 tap a 3; 12 down F".parse().unwrap();
+        let frames: Vec<(u64, u8, bool)> = timeline.events().iter().map(|e| (e.frame, e.key, e.pressed)).collect();
+        assert_eq!(
+            frames,
+            [(10, 0xA, true), (12, 0xF, true), (13, 0xA, false), (30, 5, true), (40, 5, false)]
+        );
+        assert_eq!(timeline.last_frame(), Some(40));
+    }
+
+    #[test]
+    fn rejects_bad_timeline() {
+        assert!("10 press 5".parse::<KeyTimeline>().unwrap_err().starts_with("line 1:"));
+        assert!("x down 5".parse::<KeyTimeline>().is_err());
+        assert!("1 down 10".parse::<KeyTimeline>().is_err());
+        assert!("\n1 tap 1 0".parse::<KeyTimeline>().unwrap_err().starts_with("line 2:"));
+    }
+
+    #[test]
+    fn stops_on_halt_timeout_and_fault() {
+        // 00FD после двух инструкций
+        let mut chip8 = machine(&[0x60, 0x01, 0x70, 0x01, 0x00, 0xFD]);
+        assert_eq!(run(&mut chip8, &KeyTimeline::new(), 10, 10), Outcome::Halted { frame: 0 });
+        assert_eq!(chip8.cpu().registers[0], 2);
+
+        // Бесконечный цикл
+        let mut chip8 = machine(&[0x12, 0x00]);
+        let outcome = run(&mut chip8, &KeyTimeline::new(), 5, 10);
+        assert_eq!(outcome, Outcome::Timeout { frames: 5 });
+        assert_eq!(outcome.exit_code(), EXIT_TIMEOUT);
+
+        // RET с пустым стеком
+        let mut chip8 = machine(&[0x00, 0xEE]);
+        let outcome = run(&mut chip8, &KeyTimeline::new(), 5, 10);
+        assert!(matches!(outcome, Outcome::Fault { frame: 0, fault: Chip8Fault::StackUnderflow { .. } }));
+        assert_eq!(outcome.exit_code(), EXIT_FAULT);
+    }
+
+    #[test]
+    fn timeline_drives_keys() {
+        // Крутимся, пока не нажата клавиша 5, затем останавливаемся
+        let mut chip8 = machine(&[0x61, 0x05, 0xE1, 0x9E, 0x12, 0x02, 0x00, 0xFD]);
+        let timeline: KeyTimeline = "3 down 5".parse().unwrap();
+        assert_eq!(run(&mut chip8, &timeline, 10, 10), Outcome::Halted { frame: 3 });
+
+        // Обработчик видит каждый кадр, включая последний
+        let mut chip8 = machine(&[0x61, 0x05, 0xE1, 0x9E, 0x12, 0x02, 0x00, 0xFD]);
+        let mut seen = Vec::new();
+        run_with(&mut chip8, &timeline, 10, 10, |frame, chip8| seen.push((frame, chip8.frame())));
+        assert_eq!(seen, [(0, 1), (1, 2), (2, 3), (3, 4)]);
+    }
+
+    #[test]
+    fn state_json_has_registers() {
+        let mut chip8 = machine(&[0x6A, 0x2A, 0x00, 0xFD]);
+        let outcome = run(&mut chip8, &KeyTimeline::new(), 1, 10);
+        let json = state_json(&chip8, &outcome);
+        assert!(json.contains("\"outcome\": \"halt\""));
+        assert!(json.contains("\"v\": [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 42, 0, 0, 0, 0, 0]"));
+        assert!(json.contains("\"stack\": []"));
+        assert!(json.contains("\"fault\": null"));
+    }
+}
@@ -0,0 +1,551 @@
+//! Раскладка: какие клавиши хоста нажимают клавиши CHIP-8 и горячие клавиши
+//! эмулятора. Не зависит от оконной системы - фронтенд только переводит
+//! свои коды клавиш в [`HostKey`].
+
+use std::fmt;
+use std::str::FromStr;
+
+/// Клавиша хоста
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HostKey {
+    /// Буква (строчная), цифра или знак препинания
+    Char(char),
+    /// Функциональная клавиша F1-F24
+    F(u8),
+    Up,
+    Down,
+    Left,
+    Right,
+    Space,
+    Enter,
+    Escape,
+    Backspace,
+    Tab,
+    Insert,
+    Delete,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+    Shift,
+    Ctrl,
+    Alt,
+}
+
+// Имена клавиш в конфиге (без учёта регистра); первое имя - основное
+const NAMED_KEYS: [(&str, HostKey); 21] = [
+    ("Up", HostKey::Up),
+    ("Down", HostKey::Down),
+    ("Left", HostKey::Left),
+    ("Right", HostKey::Right),
+    ("Space", HostKey::Space),
+    ("Enter", HostKey::Enter),
+    ("Return", HostKey::Enter),
+    ("Escape", HostKey::Escape),
+    ("Esc", HostKey::Escape),
+    ("Backspace", HostKey::Backspace),
+    ("Tab", HostKey::Tab),
+    ("Insert", HostKey::Insert),
+    ("Delete", HostKey::Delete),
+    ("Home", HostKey::Home),
+    ("End", HostKey::End),
+    ("PageUp", HostKey::PageUp),
+    ("PageDown", HostKey::PageDown),
+    ("Shift", HostKey::Shift),
+    ("Ctrl", HostKey::Ctrl),
+    ("Control", HostKey::Ctrl),
+    ("Alt", HostKey::Alt),
+];
+
+impl HostKey {
+    /// Модификатор (Shift, Ctrl, Alt)
+    pub fn is_modifier(&self) -> bool {
+        matches!(self, HostKey::Shift | HostKey::Ctrl | HostKey::Alt)
+    }
+}
+
+impl fmt::Display for HostKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HostKey::Char(c) => write!(f, "{}", c.to_ascii_uppercase()),
+            HostKey::F(n) => write!(f, "F{}", n),
+            key => {
+                let (name, _) = NAMED_KEYS.iter().find(|(_, k)| k == key).expect("every named key has a name");
+                f.write_str(name)
+            }
+        }
+    }
+}
+
+impl FromStr for HostKey {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut chars = s.chars();
+        if let (Some(c), None) = (chars.next(), chars.next())
+            && !c.is_whitespace()
+        {
+            return Ok(HostKey::Char(c.to_ascii_lowercase()));
+        }
+        if let Some(n) = s.strip_prefix(['F', 'f']).and_then(|n| n.parse::<u8>().ok())
+            && (1..=24).contains(&n)
+        {
+            return Ok(HostKey::F(n));
+        }
+        NAMED_KEYS
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(s))
+            .map(|&(_, key)| key)
+            .ok_or_else(|| format!("unknown key '{}'", s))
+    }
+}
+
+/// Клавиша с модификаторами, например `Shift+F1`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct KeyCombo {
+    pub key: HostKey,
+    pub shift: bool,
+    pub ctrl: bool,
+    pub alt: bool,
+}
+
+impl KeyCombo {
+    pub fn new(key: HostKey) -> Self {
+        KeyCombo { key, shift: false, ctrl: false, alt: false }
+    }
+
+    pub fn shift(mut self) -> Self {
+        self.shift = true;
+        self
+    }
+
+    /// Модификаторы совпадают с зажатыми в точности: F1 не срабатывает при зажатом Shift
+    fn modifiers_match(&self, held: &[HostKey]) -> bool {
+        self.shift == held.contains(&HostKey::Shift)
+            && self.ctrl == held.contains(&HostKey::Ctrl)
+            && self.alt == held.contains(&HostKey::Alt)
+    }
+}
+
+impl fmt::Display for KeyCombo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (on, name) in [(self.ctrl, "Ctrl"), (self.alt, "Alt"), (self.shift, "Shift")] {
+            if on {
+                write!(f, "{}+", name)?;
+            }
+        }
+        write!(f, "{}", self.key)
+    }
+}
+
+impl FromStr for KeyCombo {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // Сама клавиша '+' пишется как "+" или "Shift++"
+        let (modifiers, key) = match s.strip_suffix("++") {
+            Some(modifiers) => (modifiers, "+"),
+            None if s == "+" => ("", "+"),
+            None => s.rsplit_once('+').unwrap_or(("", s)),
+        };
+        let mut combo = KeyCombo::new(key.trim().parse()?);
+        for modifier in modifiers.split('+').filter(|m| !m.is_empty()) {
+            match modifier.trim().parse()? {
+                HostKey::Shift => combo.shift = true,
+                HostKey::Ctrl => combo.ctrl = true,
+                HostKey::Alt => combo.alt = true,
+                other => return Err(format!("'{}' is not a modifier in '{}'", other, s)),
+            }
+        }
+        Ok(combo)
+    }
+}
+
+/// Действие эмулятора
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Hotkey {
+    Quit,
+    /// Пауза и продолжение
+    Pause,
+    /// Перезапуск ROM
+    Reset,
+    /// Перемотка назад, пока клавиша зажата
+    Rewind,
+    /// Ускорение, пока клавиша зажата
+    SpeedUp,
+    /// Снимок экрана в PNG
+    Screenshot,
+    /// Начать или закончить запись GIF
+    RecordGif,
+    /// Сохранение в слот 1-9
+    SaveState(u8),
+    /// Загрузка из слота 1-9
+    LoadState(u8),
+}
+
+/// Число слотов сохранений
+pub const STATE_SLOTS: u8 = 9;
+
+impl Hotkey {
+    /// Имя в секции `[hotkeys]`; для слотов - общее имя списка
+    pub fn name(&self) -> &'static str {
+        match self {
+            Hotkey::Quit => "quit",
+            Hotkey::Pause => "pause",
+            Hotkey::Reset => "reset",
+            Hotkey::Rewind => "rewind",
+            Hotkey::SpeedUp => "speed_up",
+            Hotkey::Screenshot => "screenshot",
+            Hotkey::RecordGif => "record_gif",
+            Hotkey::SaveState(_) => "save_state",
+            Hotkey::LoadState(_) => "load_state",
+        }
+    }
+}
+
+/// Привязки клавиш хоста к клавишам CHIP-8 и к действиям эмулятора
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Keymap {
+    // Для каждой клавиши CHIP-8 - любые из этих клавиш хоста
+    pub keys: [Vec<HostKey>; 16],
+    // Горячие клавиши; у одного действия может быть несколько сочетаний
+    pub hotkeys: Vec<(KeyCombo, Hotkey)>,
+}
+
+impl Default for Keymap {
+    /// Классическая раскладка:
+    /// CHIP-8:  1 2 3 C   ->   PC: 1 2 3 4
+    ///          4 5 6 D          Q W E R
+    ///          7 8 9 E          A S D F
+    ///          A 0 B F          Z X C V
+    ///
+    /// Escape - выход, P - пауза, F12 - сброс, Backspace - перемотка,
+    /// Tab - ускорение, F10 - снимок экрана, F11 - запись GIF,
+    /// F1-F9 - загрузка слота, Shift+F1-F9 - сохранение
+    fn default() -> Self {
+        const LAYOUT: [(u8, char); 16] = [
+            (0x1, '1'), (0x2, '2'), (0x3, '3'), (0xC, '4'),
+            (0x4, 'q'), (0x5, 'w'), (0x6, 'e'), (0xD, 'r'),
+            (0x7, 'a'), (0x8, 's'), (0x9, 'd'), (0xE, 'f'),
+            (0xA, 'z'), (0x0, 'x'), (0xB, 'c'), (0xF, 'v'),
+        ];
+        let mut keys: [Vec<HostKey>; 16] = Default::default();
+        for (chip8_key, host) in LAYOUT {
+            keys[chip8_key as usize].push(HostKey::Char(host));
+        }
+
+        let mut hotkeys = vec![
+            (KeyCombo::new(HostKey::Escape), Hotkey::Quit),
+            (KeyCombo::new(HostKey::Char('p')), Hotkey::Pause),
+            (KeyCombo::new(HostKey::F(12)), Hotkey::Reset),
+            (KeyCombo::new(HostKey::Backspace), Hotkey::Rewind),
+            (KeyCombo::new(HostKey::Tab), Hotkey::SpeedUp),
+            (KeyCombo::new(HostKey::F(10)), Hotkey::Screenshot),
+            (KeyCombo::new(HostKey::F(11)), Hotkey::RecordGif),
+        ];
+        for slot in 1..=STATE_SLOTS {
+            hotkeys.push((KeyCombo::new(HostKey::F(slot)), Hotkey::LoadState(slot)));
+            hotkeys.push((KeyCombo::new(HostKey::F(slot)).shift(), Hotkey::SaveState(slot)));
+        }
+
+        Keymap { keys, hotkeys }
+    }
+}
+
+impl Keymap {
+    /// Состояние клавиш CHIP-8 по зажатым клавишам хоста (модификаторы не мешают)
+    pub fn chip8_keys(&self, held: &[HostKey]) -> [bool; 16] {
+        std::array::from_fn(|key| self.keys[key].iter().any(|host| held.contains(host)))
+    }
+
+    /// Действия, сработавшие от только что нажатых клавиш
+    pub fn hotkeys_pressed(&self, pressed: &[HostKey], held: &[HostKey]) -> Vec<Hotkey> {
+        self.hotkeys
+            .iter()
+            .filter(|(combo, _)| pressed.contains(&combo.key) && combo.modifiers_match(held))
+            .map(|&(_, hotkey)| hotkey)
+            .collect()
+    }
+
+    /// Зажато ли сочетание для действия (перемотка, ускорение)
+    pub fn is_held(&self, hotkey: Hotkey, held: &[HostKey]) -> bool {
+        self.hotkeys
+            .iter()
+            .any(|(combo, h)| *h == hotkey && held.contains(&combo.key) && combo.modifiers_match(held))
+    }
+
+    /// Сочетания для действия, для подсказок
+    pub fn combos(&self, hotkey: Hotkey) -> Vec<KeyCombo> {
+        self.hotkeys.iter().filter(|(_, h)| *h == hotkey).map(|&(combo, _)| combo).collect()
+    }
+}
+
+#[cfg(feature = "keymap")]
+pub use config::{config_path, default_path, KeymapError};
+
+/// Загрузка раскладки из TOML:
+///
+/// ```toml
+/// [keys]                       # клавиша CHIP-8 = клавиши хоста
+/// 5 = ["W", "Up"]
+///
+/// [hotkeys]
+/// pause = ["P", "Space"]
+/// save_state = ["Shift+F1", "Shift+F2"]   # по слотам
+///
+/// [rom."pong.ch8".keys]        # для одного ROM (имя файла или хеш в hex)
+/// 1 = ["W"]
+/// ```
+#[cfg(feature = "keymap")]
+mod config {
+    use std::fs;
+    use std::io;
+    use std::path::{Path, PathBuf};
+
+    use thiserror::Error;
+    use toml::{Table, Value};
+
+    use super::{Hotkey, KeyCombo, Keymap, STATE_SLOTS};
+    use crate::paths;
+
+    #[derive(Error, Debug)]
+    pub enum KeymapError {
+        #[error("{path}: {source}")]
+        Io {
+            path: String,
+            #[source]
+            source: io::Error,
+        },
+
+        #[error("{file}: {message}")]
+        Invalid { file: String, message: String },
+    }
+
+    /// Файл раскладки по умолчанию: $XDG_CONFIG_HOME/chip8/keys.toml или ~/.config/chip8/keys.toml
+    pub fn default_path() -> Option<PathBuf> {
+        Some(paths::config_dir()?.join("keys.toml"))
+    }
+
+    /// Файл раскладки: заданный явно или файл по умолчанию, если он есть
+    pub fn config_path(explicit: Option<&str>) -> Option<PathBuf> {
+        paths::explicit_or_existing(explicit, default_path())
+    }
+
+    impl Keymap {
+        /// Раскладка из файла поверх стандартной, с учётом секций для ROM
+        /// (по имени файла из `rom_path` или по `rom_hash`)
+        pub fn load(path: impl AsRef<Path>, rom_path: &str, rom_hash: u64) -> Result<Self, KeymapError> {
+            let path = path.as_ref();
+            let rom_name = Path::new(rom_path).file_name().and_then(|name| name.to_str()).unwrap_or(rom_path);
+            let text = fs::read_to_string(path).map_err(|source| KeymapError::Io {
+                path: path.display().to_string(),
+                source,
+            })?;
+            Self::from_toml(&text, rom_name, rom_hash).map_err(|message| KeymapError::Invalid {
+                file: path.display().to_string(),
+                message,
+            })
+        }
+
+        pub fn from_toml(text: &str, rom_name: &str, rom_hash: u64) -> Result<Self, String> {
+            let table: Table = text.parse().map_err(|e: toml::de::Error| e.message().to_string())?;
+            let mut keymap = Keymap::default();
+            keymap.apply(&table, "")?;
+
+            if let Some(roms) = table.get("rom") {
+                let roms = roms.as_table().ok_or("'rom' must be a table")?;
+                let hash = format!("{:016X}", rom_hash);
+                // Секции других ROM тоже проверяем, чтобы ошибка не ждала нужного ROM
+                let mut unused = Keymap::default();
+                for (name, section) in roms {
+                    let section = section.as_table().ok_or_else(|| format!("rom.\"{}\" must be a table", name))?;
+                    let target = if name.eq_ignore_ascii_case(rom_name) || name.eq_ignore_ascii_case(&hash) {
+                        &mut keymap
+                    } else {
+                        &mut unused
+                    };
+                    target.apply(section, &format!("rom.\"{}\".", name))?;
+                }
+            }
+            Ok(keymap)
+        }
+
+        /// Секции `keys` и `hotkeys`; `prefix` - путь для сообщений об ошибках
+        fn apply(&mut self, table: &Table, prefix: &str) -> Result<(), String> {
+            // Секции `rom` бывают только на верхнем уровне
+            let known = |name: &str| name == "keys" || name == "hotkeys" || (prefix.is_empty() && name == "rom");
+            if let Some(name) = table.keys().find(|name| !known(name)) {
+                return Err(format!("unknown section '{}{}'", prefix, name));
+            }
+
+            if let Some(keys) = table.get("keys") {
+                let keys = keys.as_table().ok_or_else(|| format!("'{}keys' must be a table", prefix))?;
+                for (name, value) in keys {
+                    let key = u8::from_str_radix(name, 16)
+                        .ok()
+                        .filter(|&k| k < 16)
+                        .ok_or_else(|| format!("{}keys: '{}' is not a CHIP-8 key 0-F", prefix, name))?;
+                    self.keys[key as usize] = strings(value)
+                        .and_then(|names| names.iter().map(|n| n.parse()).collect())
+                        .map_err(|e| format!("{}keys.{}: {}", prefix, name, e))?;
+                }
+            }
+
+            if let Some(hotkeys) = table.get("hotkeys") {
+                let hotkeys = hotkeys.as_table().ok_or_else(|| format!("'{}hotkeys' must be a table", prefix))?;
+                for (name, value) in hotkeys {
+                    let combos: Vec<KeyCombo> = strings(value)
+                        .and_then(|names| names.iter().map(|n| n.parse()).collect())
+                        .map_err(|e| format!("{}hotkeys.{}: {}", prefix, name, e))?;
+                    let bound: Vec<(KeyCombo, Hotkey)> = match name.as_str() {
+                        "save_state" | "load_state" => {
+                            if combos.len() > STATE_SLOTS as usize {
+                                return Err(format!("{}hotkeys.{}: at most {} slots", prefix, name, STATE_SLOTS));
+                            }
+                            let slot = |i: usize| i as u8 + 1;
+                            combos
+                                .into_iter()
+                                .enumerate()
+                                .map(|(i, combo)| match name.as_str() {
+                                    "save_state" => (combo, Hotkey::SaveState(slot(i))),
+                                    _ => (combo, Hotkey::LoadState(slot(i))),
+                                })
+                                .collect()
+                        }
+                        _ => {
+                            let hotkey = [
+                                Hotkey::Quit,
+                                Hotkey::Pause,
+                                Hotkey::Reset,
+                                Hotkey::Rewind,
+                                Hotkey::SpeedUp,
+                                Hotkey::Screenshot,
+                                Hotkey::RecordGif,
+                            ]
+                            .into_iter()
+                                .find(|h| h.name() == name)
+                                .ok_or_else(|| format!("{}hotkeys: unknown action '{}'", prefix, name))?;
+                            combos.into_iter().map(|combo| (combo, hotkey)).collect()
+                        }
+                    };
+                    // Заменяем все прежние сочетания этого действия
+                    self.hotkeys.retain(|(_, h)| h.name() != name);
+                    self.hotkeys.extend(bound);
+                }
+            }
+            Ok(())
+        }
+    }
+
+    /// Строка или массив строк
+    fn strings(value: &Value) -> Result<Vec<String>, String> {
+        match value {
+            Value::String(s) => Ok(vec![s.clone()]),
+            Value::Array(items) => items
+                .iter()
+                .map(|item| item.as_str().map(str::to_string).ok_or_else(|| "expected a key name".to_string()))
+                .collect(),
+            _ => Err("expected a key name or a list of key names".to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_key_names() {
+        assert_eq!("q".parse(), Ok(HostKey::Char('q')));
+        assert_eq!("Q".parse(), Ok(HostKey::Char('q')));
+        assert_eq!("f10".parse(), Ok(HostKey::F(10)));
+        assert_eq!("esc".parse(), Ok(HostKey::Escape));
+        assert_eq!("PageDown".parse(), Ok(HostKey::PageDown));
+        assert!("F30".parse::<HostKey>().is_err());
+        assert!("Hyper".parse::<HostKey>().is_err());
+        assert_eq!(HostKey::Char('q').to_string(), "Q");
+
+        let combo: KeyCombo = "Ctrl+Shift+F1".parse().unwrap();
+        assert_eq!(combo, KeyCombo { key: HostKey::F(1), shift: true, ctrl: true, alt: false });
+        assert_eq!(combo.to_string(), "Ctrl+Shift+F1");
+        assert_eq!("Shift++".parse(), Ok(KeyCombo::new(HostKey::Char('+')).shift()));
+        assert!("Q+F1".parse::<KeyCombo>().is_err());
+    }
+
+    #[test]
+    fn default_layout() {
+        let keymap = Keymap::default();
+        let keys = keymap.chip8_keys(&[HostKey::Char('x'), HostKey::Char('4'), HostKey::Shift]);
+        assert!(keys[0x0] && keys[0xC]);
+        assert_eq!(keys.iter().filter(|&&k| k).count(), 2);
+    }
+
+    #[test]
+    fn hotkeys_need_exact_modifiers() {
+        let keymap = Keymap::default();
+        let f1 = [HostKey::F(1)];
+        assert_eq!(keymap.hotkeys_pressed(&f1, &f1), [Hotkey::LoadState(1)]);
+        assert_eq!(keymap.hotkeys_pressed(&f1, &[HostKey::F(1), HostKey::Shift]), [Hotkey::SaveState(1)]);
+        assert!(keymap.is_held(Hotkey::Rewind, &[HostKey::Backspace]));
+        assert!(!keymap.is_held(Hotkey::Rewind, &[HostKey::Backspace, HostKey::Ctrl]));
+    }
+
+    #[cfg(feature = "keymap")]
+    #[test]
+    fn config_overrides_keys_and_hotkeys() {
+        let text = r#"
+            [keys]
+            5 = ["W", "Up"]
+            c = "Space"
+
+            [hotkeys]
+            pause = ["P", "Enter"]
+            save_state = ["Ctrl+S"]
+
+            [rom."PONG.ch8".keys]
+            1 = ["W"]
+
+            [rom."00000000DEADBEEF".hotkeys]
+            quit = []
+        "#;
+        let keymap = Keymap::from_toml(text, "pong.ch8", 0xDEAD_BEEF).unwrap();
+        assert_eq!(keymap.keys[5], [HostKey::Char('w'), HostKey::Up]);
+        assert_eq!(keymap.keys[0xC], [HostKey::Space]);
+        assert_eq!(keymap.keys[1], [HostKey::Char('w')]);
+        assert_eq!(keymap.keys[2], [HostKey::Char('2')]);
+        assert_eq!(keymap.combos(Hotkey::Pause).len(), 2);
+        assert!(keymap.combos(Hotkey::SaveState(2)).is_empty());
+        assert_eq!(keymap.combos(Hotkey::SaveState(1)), ["Ctrl+S".parse().unwrap()]);
+        assert_eq!(keymap.combos(Hotkey::LoadState(9)), [KeyCombo::new(HostKey::F(9))]);
+        assert!(keymap.combos(Hotkey::Quit).is_empty());
+
+        // Для другого ROM действуют только общие секции
+        let other = Keymap::from_toml(text, "tetris.ch8", 1).unwrap();
+        assert_eq!(other.keys[1], [HostKey::Char('1')]);
+        assert_eq!(other.combos(Hotkey::Quit).len(), 1);
+    }
+
+    #[cfg(feature = "keymap")]
+    #[test]
+    fn config_errors_name_the_entry() {
+        let error = |text: &str| Keymap::from_toml(text, "", 0).unwrap_err();
+        assert_eq!(error("[keys]\nG = \"Q\""), "keys: 'G' is not a CHIP-8 key 0-F");
+        assert_eq!(error("[keys]\n1 = \"Hyper\""), "keys.1: unknown key 'Hyper'");
+        assert_eq!(error("[hotkeys]\nfly = \"F\""), "hotkeys: unknown action 'fly'");
+        assert_eq!(error("[rom.\"a\".keys]\n1 = 5"), "rom.\"a\".keys.1: expected a key name or a list of key names");
+        assert_eq!(error("[sound]"), "unknown section 'sound'");
+        assert_eq!(error("[rom.\"a\".rom]"), "unknown section 'rom.\"a\".rom'");
+        assert!(error("[keys").contains("invalid"));
+    }
+
+    #[cfg(feature = "keymap")]
+    #[test]
+    fn example_config_parses() {
+        let example = include_str!("../keys.example.toml");
+        let keymap = Keymap::from_toml(example, "", 0).unwrap();
+        let default = Keymap::default();
+        assert_eq!(keymap.keys, default.keys);
+        assert_eq!(keymap.hotkeys.len(), default.hotkeys.len());
+        assert!(default.hotkeys.iter().all(|binding| keymap.hotkeys.contains(binding)));
+    }
+}
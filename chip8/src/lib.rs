@@ -0,0 +1,34 @@
+//! Эмулятор CHIP-8 / SUPER-CHIP / XO-CHIP без зависимостей от оконной системы.
+//!
+//! Фронтенды (окно minifb, тесты, инструменты) работают через [`Chip8`].
+
+pub mod asm;
+pub mod audio;
+pub mod cheat;
+pub mod constants;
+pub mod cpu;
+pub mod debugger;
+pub mod disasm;
+pub mod display;
+pub mod fault;
+pub mod gdbstub;
+pub mod headless;
+pub mod keyboard;
+pub mod keymap;
+pub mod machine;
+pub mod movie;
+pub mod paths;
+pub mod profile;
+pub mod quirks;
+pub mod rewind;
+pub mod rng;
+pub mod runner;
+pub mod savestate;
+pub mod scheduler;
+pub mod screenshot;
+pub mod trace;
+pub mod tui;
+pub mod variant;
+pub mod video;
+
+pub use machine::Chip8;
@@ -0,0 +1,585 @@
+use std::io;
+use std::mem;
+
+use crate::audio::{Audio, AudioSink};
+use crate::cheat::{Cheat, CheatError, CheatList};
+use crate::cpu::CPU;
+use crate::display::Display;
+use crate::fault::Chip8Fault;
+use crate::movie::{Movie, Player};
+use crate::profile::Profiler;
+use crate::quirks::Quirks;
+use crate::rng::{RandomSource, SeededRng, ThreadRng};
+use crate::rewind::Rewind;
+use crate::savestate::{self, SaveState, SaveStateError};
+use crate::trace::Tracer;
+use crate::variant::Variant;
+
+/// Машина CHIP-8 для встраивания во фронтенды
+pub struct Chip8 {
+    cpu: CPU,
+    // Загруженный ROM, нужен для сброса
+    rom: Vec<u8>,
+    // Хеш загруженного ROM, записывается в сохранения
+    rom_hash: u64,
+    // Буфер перемотки, пополняется в конце каждого кадра
+    rewind: Option<Rewind>,
+    // Звук, генерируется в конце каждого кадра
+    audio: Audio,
+    // Запись ввода по кадрам
+    recording: Option<Movie>,
+    // Воспроизведение записи; пока оно идёт, ввод с клавиатуры игнорируется
+    playback: Option<Player>,
+    // Сколько кадров выполнено с создания машины
+    frames: u64,
+    // Замороженные адреса и регистры, применяются в конце каждого кадра
+    cheats: Vec<Cheat>,
+}
+
+impl Default for Chip8 {
+    fn default() -> Self {
+        Self::new(Variant::Classic)
+    }
+}
+
+impl Chip8 {
+    pub fn new(variant: Variant) -> Self {
+        Chip8 {
+            cpu: CPU::with_variant(variant),
+            rom: Vec::new(),
+            rom_hash: savestate::rom_hash(&[]),
+            rewind: None,
+            audio: Audio::new(),
+            recording: None,
+            playback: None,
+            frames: 0,
+            cheats: Vec::new(),
+        }
+    }
+
+    pub fn with_quirks(mut self, quirks: Quirks) -> Self {
+        self.cpu.quirks = quirks;
+        self
+    }
+
+    /// Включить или выключить трассировку инструкций
+    pub fn set_tracer(&mut self, tracer: Option<Tracer>) {
+        self.cpu.tracer = tracer;
+    }
+
+    /// Включить или выключить профилировщик
+    pub fn set_profiler(&mut self, profiler: Option<Profiler>) {
+        self.cpu.profiler = profiler;
+    }
+
+    pub fn profiler(&self) -> Option<&Profiler> {
+        self.cpu.profiler.as_ref()
+    }
+
+    /// Включить перемотку на `seconds` секунд; 0 выключает её
+    pub fn set_rewind(&mut self, seconds: u32) {
+        self.rewind = (seconds > 0).then(|| Rewind::new(seconds));
+    }
+
+    /// Подключить вывод звука; приёмников может быть несколько (динамик и WAV)
+    pub fn add_audio_sink(&mut self, sink: Box<dyn AudioSink>) {
+        self.audio.add_sink(sink);
+    }
+
+    pub fn audio_mut(&mut self) -> &mut Audio {
+        &mut self.audio
+    }
+
+    /// Отключить звук, дописав файлы; возвращает первую ошибку вывода
+    pub fn finish_audio(&mut self) -> io::Result<()> {
+        self.audio.finish()
+    }
+
+    /// Источник случайных чисел для CXKK
+    pub fn set_rng(&mut self, rng: Box<dyn RandomSource>) {
+        self.cpu.rng = rng;
+    }
+
+    /// Начать запись ввода. Запись воспроизводима, только если начата
+    /// сразу после загрузки ROM: в ней хранятся настройки и seed, а не снимок.
+    /// Невоспроизводимый источник случайных чисел заменяется на SeededRng.
+    pub fn start_recording(&mut self, instructions_per_frame: usize) {
+        let seed = match self.cpu.rng.seed() {
+            Some(seed) => seed,
+            None => {
+                let seed = rand::random();
+                self.set_rng(Box::new(SeededRng::new(seed)));
+                seed
+            }
+        };
+        self.recording = Some(Movie::new(self, instructions_per_frame, seed));
+    }
+
+    pub fn stop_recording(&mut self) -> Option<Movie> {
+        self.recording.take()
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    /// Воспроизводить клавиши из записи вместо живого ввода
+    pub fn play(&mut self, movie: Movie) {
+        let mut player = Player::new(movie);
+        if let Some(keys) = player.next_keys() {
+            self.cpu.keyboard.set_keys(&keys);
+            self.playback = Some(player);
+        }
+    }
+
+    pub fn stop_replay(&mut self) {
+        self.playback = None;
+    }
+
+    pub fn is_replaying(&self) -> bool {
+        self.playback.is_some()
+    }
+
+    pub fn replay(&self) -> Option<&Player> {
+        self.playback.as_ref()
+    }
+
+    pub fn rewind(&self) -> Option<&Rewind> {
+        self.rewind.as_ref()
+    }
+
+    /// Вернуться на кадр назад. false, если буфер пуст или перемотка выключена
+    pub fn rewind_frame(&mut self) -> bool {
+        match self.rewind.as_mut().and_then(Rewind::pop) {
+            Some(state) => {
+                state.apply(&mut self.cpu);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Загрузить программу в память с адреса 0x200
+    pub fn load(&mut self, rom: &[u8]) -> Result<(), String> {
+        self.cpu.load_bytes(rom)?;
+        self.rom = rom.to_vec();
+        self.rom_hash = savestate::rom_hash(rom);
+        Ok(())
+    }
+
+    /// Перезапустить загруженный ROM: чистая память, регистры и экран.
+    /// Вариант, профиль, трассировка, профилировщик и источник случайных
+    /// чисел сохраняются, буфер перемотки очищается.
+    pub fn reset(&mut self) {
+        let mut cpu = CPU::with_variant(self.cpu.variant);
+        cpu.quirks = self.cpu.quirks;
+        cpu.tracer = self.cpu.tracer.take();
+        cpu.profiler = self.cpu.profiler.take();
+        if let Some(profiler) = &mut cpu.profiler {
+            profiler.end_calls();
+        }
+        cpu.rng = mem::replace(&mut self.cpu.rng, Box::new(ThreadRng));
+        cpu.load_bytes(&self.rom).expect("ROM was loaded into the same variant");
+        self.cpu = cpu;
+        if let Some(rewind) = &mut self.rewind {
+            rewind.clear();
+        }
+    }
+
+    /// Загруженный ROM
+    pub fn rom(&self) -> &[u8] {
+        &self.rom
+    }
+
+    pub fn rom_hash(&self) -> u64 {
+        self.rom_hash
+    }
+
+    /// Снимок всей машины
+    pub fn save_state(&self) -> SaveState {
+        SaveState::capture(&self.cpu, self.rom_hash)
+    }
+
+    /// Восстановить снимок; снимок от другого ROM отклоняется
+    pub fn load_state(&mut self, state: &SaveState) -> Result<(), SaveStateError> {
+        if state.rom_hash != self.rom_hash {
+            return Err(SaveStateError::RomMismatch {
+                expected: state.rom_hash,
+                found: self.rom_hash,
+            });
+        }
+        state.apply(&mut self.cpu);
+        // Старая история больше не продолжается в текущую
+        if let Some(rewind) = &mut self.rewind {
+            rewind.clear();
+        }
+        Ok(())
+    }
+
+    pub fn cheats(&self) -> &[Cheat] {
+        &self.cheats
+    }
+
+    /// Заморозить значение (сразу и в конце каждого кадра); прежний чит
+    /// на ту же цель заменяется
+    pub fn add_cheat(&mut self, cheat: Cheat) {
+        cheat.apply(&mut self.cpu);
+        match self.cheats.iter_mut().find(|c| c.target == cheat.target) {
+            Some(existing) => *existing = cheat,
+            None => self.cheats.push(cheat),
+        }
+    }
+
+    pub fn remove_cheat(&mut self, index: usize) -> Option<Cheat> {
+        (index < self.cheats.len()).then(|| self.cheats.remove(index))
+    }
+
+    /// Текущие читы списком для сохранения
+    pub fn cheat_list(&self) -> CheatList {
+        CheatList { rom_hash: self.rom_hash, cheats: self.cheats.clone() }
+    }
+
+    /// Добавить читы из списка; список от другого ROM отклоняется
+    pub fn load_cheats(&mut self, list: &CheatList) -> Result<(), CheatError> {
+        list.check_rom(self.rom_hash)?;
+        for cheat in &list.cheats {
+            self.add_cheat(*cheat);
+        }
+        Ok(())
+    }
+
+    /// Выполнить `cycles` инструкций, остановившись на первом сбое
+    pub fn step(&mut self, cycles: usize) -> Result<(), Chip8Fault> {
+        for _ in 0..cycles {
+            self.cpu.cycle()?;
+        }
+        Ok(())
+    }
+
+    /// Один кадр: `instructions` инструкций и один тик таймеров
+    pub fn run_frame(&mut self, instructions: usize) -> Result<(), Chip8Fault> {
+        self.step(instructions)?;
+        self.end_frame();
+        Ok(())
+    }
+
+    /// Конец кадра: звук кадра, тик таймеров, читы, снимок для перемотки,
+    /// запись ввода и клавиши следующего кадра из воспроизводимой записи
+    pub fn end_frame(&mut self) {
+        self.frames += 1;
+        self.audio.frame(&self.cpu);
+        self.tick_timers();
+        for cheat in &self.cheats {
+            cheat.apply(&mut self.cpu);
+        }
+        if let Some(rewind) = &mut self.rewind {
+            rewind.push(&SaveState::capture(&self.cpu, self.rom_hash));
+        }
+        if let Some(movie) = &mut self.recording {
+            movie.push(&self.cpu.keyboard.keys());
+        }
+        if let Some(player) = &mut self.playback {
+            match player.next_keys() {
+                Some(keys) => self.cpu.keyboard.set_keys(&keys),
+                None => self.playback = None,
+            }
+        }
+    }
+
+    /// Число выполненных кадров (сброс и перемотка его не уменьшают)
+    pub fn frame(&self) -> u64 {
+        self.frames
+    }
+
+    /// Перешагнуть сбойную инструкцию (политика "ignore")
+    pub fn skip_instruction(&mut self) {
+        self.cpu.program_counter = self.cpu.program_counter.wrapping_add(2);
+    }
+
+    /// Один тик таймеров (60 Гц)
+    pub fn tick_timers(&mut self) {
+        self.cpu.update_timers();
+    }
+
+    /// Нажать или отпустить клавишу 0-F (во время воспроизведения записи игнорируется)
+    pub fn set_key(&mut self, key: u8, pressed: bool) {
+        if self.playback.is_none() {
+            self.cpu.keyboard.set_key(key, pressed);
+        }
+    }
+
+    /// Установить состояние всех 16 клавиш сразу
+    pub fn set_keys(&mut self, keys: &[bool; 16]) {
+        if self.playback.is_none() {
+            self.cpu.keyboard.set_keys(keys);
+        }
+    }
+
+    pub fn display(&self) -> &Display {
+        &self.cpu.display
+    }
+
+    /// Кадр в формате 0RGB, размер `width() * height()`
+    pub fn framebuffer(&self) -> Vec<u32> {
+        self.cpu.display.to_buffer()
+    }
+
+    pub fn width(&self) -> usize {
+        self.cpu.display.width()
+    }
+
+    pub fn height(&self) -> usize {
+        self.cpu.display.height()
+    }
+
+    /// Есть ли изменения на экране с последнего `mark_drawn`
+    pub fn needs_redraw(&self) -> bool {
+        self.cpu.display.needs_redraw
+    }
+
+    pub fn mark_drawn(&mut self) {
+        self.cpu.display.needs_redraw = false;
+    }
+
+    /// Прочитать `len` байт памяти с адреса `addr` (обрезается по концу памяти)
+    pub fn read_memory(&self, addr: usize, len: usize) -> &[u8] {
+        let start = addr.min(self.cpu.memory.len());
+        let end = addr.saturating_add(len).min(self.cpu.memory.len());
+        &self.cpu.memory[start..end]
+    }
+
+    /// Записать байты в память; всё, что не помещается, отбрасывается
+    pub fn write_memory(&mut self, addr: usize, data: &[u8]) {
+        let start = addr.min(self.cpu.memory.len());
+        let end = addr.saturating_add(data.len()).min(self.cpu.memory.len());
+        self.cpu.memory[start..end].copy_from_slice(&data[..end - start]);
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.cpu.running
+    }
+
+    /// Прямой доступ к процессору для отладки
+    pub fn cpu(&self) -> &CPU {
+        &self.cpu
+    }
+
+    pub fn cpu_mut(&mut self) -> &mut CPU {
+        &mut self.cpu
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::quirks::Preset;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    /// Приёмник звука, считающий кадры со звуком и без
+    struct Frames(Rc<RefCell<Vec<bool>>>);
+
+    impl AudioSink for Frames {
+        fn sample_rate(&self) -> u32 {
+            6000
+        }
+
+        fn write(&mut self, samples: &[f32]) -> io::Result<()> {
+            self.0.borrow_mut().push(samples.iter().any(|&s| s != 0.0));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn sound_plays_while_sound_timer_runs() {
+        let frames = Rc::new(RefCell::new(Vec::new()));
+        let mut chip8 = Chip8::default();
+        chip8.add_audio_sink(Box::new(Frames(frames.clone())));
+        // LD V0, 3; LD ST, V0; JP 204
+        chip8.load(&[0x60, 0x03, 0xF0, 0x18, 0x12, 0x04]).unwrap();
+        for _ in 0..5 {
+            chip8.run_frame(10).unwrap();
+        }
+        assert_eq!(*frames.borrow(), [true, true, true, false, false]);
+        chip8.finish_audio().unwrap();
+    }
+
+    #[test]
+    fn runs_loaded_program() {
+        let mut chip8 = Chip8::default();
+        chip8.load(&[0x60, 0x2A, 0x00, 0xFD]).unwrap();
+        chip8.step(2).unwrap();
+        assert_eq!(chip8.cpu().registers[0], 0x2A);
+        assert!(!chip8.is_running());
+    }
+
+    #[test]
+    fn rejects_rom_that_does_not_fit() {
+        let mut chip8 = Chip8::default();
+        assert!(chip8.load(&[0; 4096]).is_err());
+
+        let mut chip8 = Chip8::new(Variant::XoChip);
+        assert!(chip8.load(&[0; 4096]).is_ok());
+    }
+
+    #[test]
+    fn key_wait_needs_press_and_release() {
+        let mut chip8 = Chip8::default();
+        // LD V0, 10; LD DT, V0; LD V3, K; EXIT
+        chip8.load(&[0x60, 0x0A, 0xF0, 0x15, 0xF3, 0x0A, 0x00, 0xFD]).unwrap();
+        // Клавиша зажата ещё до FX0A - её не ждали
+        chip8.set_key(0xB, true);
+        chip8.run_frame(10).unwrap();
+        chip8.run_frame(10).unwrap();
+        assert!(chip8.is_running());
+
+        chip8.set_key(0xB, false);
+        chip8.run_frame(10).unwrap();
+        chip8.set_key(0xC, true);
+        chip8.run_frame(10).unwrap();
+        assert!(chip8.is_running());
+
+        chip8.set_key(0xC, false);
+        chip8.run_frame(10).unwrap();
+        assert_eq!(chip8.cpu().registers[3], 0xC);
+        assert!(!chip8.is_running());
+        // Таймер шёл всё время ожидания
+        assert_eq!(chip8.cpu().delay_timer, 5);
+    }
+
+    #[test]
+    fn display_wait_draws_once_per_frame() {
+        let mut chip8 = Chip8::default().with_quirks(Preset::Vip.quirks());
+        // DRW V0, V0, 1; V1 += 1; JP 200
+        chip8.load(&[0xD0, 0x01, 0x71, 0x01, 0x12, 0x00]).unwrap();
+        for _ in 0..3 {
+            chip8.run_frame(100).unwrap();
+        }
+        // Кадр 1: DXYN; кадры 2 и 3: V1 += 1, JP, DXYN
+        assert_eq!(chip8.cpu().registers[1], 2);
+    }
+
+    #[test]
+    fn reset_restarts_rom_with_same_settings() {
+        let mut chip8 = Chip8::new(Variant::XoChip).with_quirks(Preset::Schip.quirks());
+        // V0 += 1; нарисовать "0"; зациклиться
+        chip8.load(&[0x70, 0x01, 0xA0, 0x50, 0xD0, 0x05, 0x12, 0x06]).unwrap();
+        chip8.write_memory(0x300, &[0xAA]);
+        chip8.step(3).unwrap();
+
+        chip8.reset();
+        assert_eq!(chip8.cpu().registers[0], 0);
+        assert_eq!(chip8.cpu().program_counter, 0x200);
+        assert_eq!(chip8.read_memory(0x200, 2), [0x70, 0x01]);
+        assert_eq!(chip8.read_memory(0x300, 1), [0]);
+        assert!(chip8.framebuffer().iter().all(|&pixel| pixel == 0));
+        assert_eq!(chip8.cpu().variant, Variant::XoChip);
+        assert_eq!(chip8.cpu().quirks, Preset::Schip.quirks());
+    }
+
+    #[test]
+    fn cheats_hold_values_each_frame() {
+        // V3 -= 1 и V0-V3 в память с 300 в цикле
+        let mut chip8 = Chip8::default();
+        chip8.load(&[0x73, 0xFF, 0xA3, 0x00, 0xF3, 0x55, 0x12, 0x00]).unwrap();
+        chip8.add_cheat("V3=09".parse().unwrap());
+        chip8.add_cheat("V3=05".parse().unwrap());
+        assert_eq!(chip8.cheats().len(), 1);
+        assert_eq!(chip8.cpu().registers[3], 5);
+
+        chip8.run_frame(8).unwrap();
+        assert_eq!(chip8.cpu().registers[3], 5);
+        // Внутри кадра программа видит своё значение, в конце кадра - снова 5
+        assert_eq!(chip8.read_memory(0x303, 1), [3]);
+
+        let list = chip8.cheat_list();
+        assert_eq!(chip8.remove_cheat(0), Some("V3=05".parse().unwrap()));
+        chip8.run_frame(4).unwrap();
+        assert_eq!(chip8.cpu().registers[3], 4);
+
+        chip8.load_cheats(&list).unwrap();
+        assert_eq!(chip8.cpu().registers[3], 5);
+        assert!(chip8.load_cheats(&CheatList::new(1)).is_err());
+    }
+
+    #[test]
+    fn step_stops_on_fault_and_can_skip_it() {
+        let mut chip8 = Chip8::default();
+        chip8.load(&[0x00, 0xEE, 0x60, 0x07]).unwrap();
+        let fault = chip8.step(2).unwrap_err();
+        assert_eq!(fault.pc(), 0x200);
+
+        chip8.skip_instruction();
+        chip8.step(1).unwrap();
+        assert_eq!(chip8.cpu().registers[0], 7);
+    }
+
+    #[test]
+    fn framebuffer_follows_display() {
+        let mut chip8 = Chip8::default();
+        // I = шрифт "0", нарисовать в (0, 0)
+        chip8.load(&[0xA0, 0x50, 0xD0, 0x05]).unwrap();
+        chip8.step(2).unwrap();
+        let frame = chip8.framebuffer();
+        assert_eq!(frame.len(), chip8.width() * chip8.height());
+        assert_eq!(frame[0], 0xFFFFFF);
+        assert_eq!(frame[4], 0x000000);
+    }
+
+    #[test]
+    fn save_state_restores_execution() {
+        let mut chip8 = Chip8::default();
+        // V0 += 1 в цикле
+        chip8.load(&[0x70, 0x01, 0x12, 0x00]).unwrap();
+        chip8.step(4).unwrap();
+        let state = chip8.save_state();
+
+        chip8.step(10).unwrap();
+        chip8.load_state(&state).unwrap();
+        assert_eq!(chip8.cpu().registers[0], 2);
+        assert_eq!(chip8.cpu().program_counter, 0x200);
+    }
+
+    #[test]
+    fn save_state_from_other_rom_is_rejected() {
+        let mut pong = Chip8::default();
+        pong.load(&[0x12, 0x00]).unwrap();
+        let state = pong.save_state();
+
+        let mut tetris = Chip8::default();
+        tetris.load(&[0x13, 0x00]).unwrap();
+        let err = tetris.load_state(&state).unwrap_err();
+        assert!(matches!(err, SaveStateError::RomMismatch { .. }));
+        assert_eq!(tetris.cpu().memory[0x200], 0x13);
+    }
+
+    #[test]
+    fn rewind_steps_back_through_frames() {
+        let mut chip8 = Chip8::default();
+        chip8.set_rewind(1);
+        // V0 += 1 в цикле
+        chip8.load(&[0x70, 0x01, 0x12, 0x00]).unwrap();
+        for _ in 0..3 {
+            chip8.run_frame(2).unwrap();
+        }
+        assert_eq!(chip8.cpu().registers[0], 3);
+
+        assert!(chip8.rewind_frame());
+        assert_eq!(chip8.cpu().registers[0], 2);
+        assert!(chip8.rewind_frame());
+        assert_eq!(chip8.cpu().registers[0], 1);
+        assert!(!chip8.rewind_frame());
+
+        // После перемотки история продолжается с текущего кадра
+        chip8.run_frame(2).unwrap();
+        assert_eq!(chip8.rewind().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn memory_access_is_bounded() {
+        let mut chip8 = Chip8::default();
+        chip8.write_memory(0xFFE, &[1, 2, 3, 4]);
+        assert_eq!(chip8.read_memory(0xFFE, 10), &[1, 2]);
+        assert!(chip8.read_memory(0x2000, 4).is_empty());
+    }
+}
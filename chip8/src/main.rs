@@ -1,34 +1,169 @@
-mod cpu;
-mod constants;
-mod display;
-mod keyboard;
-
-use cpu::CPU;
-use minifb::{Window, WindowOptions, Key};
-use std::env;
+use chip8::audio::{self, WavSink};
+use chip8::cheat::{self, Cheat, CheatList};
+use chip8::constants;
+use chip8::debugger::{self, Command, Debugger};
+use chip8::fault::FaultPolicy;
+use chip8::gdbstub::{GdbStub, Session};
+use chip8::keymap::{self, HostKey, Hotkey, Keymap};
+use chip8::movie::Movie;
+use chip8::profile::{self, Profiler};
+use chip8::quirks::Preset;
+use chip8::rng::RngConfig;
+use chip8::runner::{Input, Runner};
+use chip8::scheduler::{self, Scheduler};
+use chip8::screenshot::{self, GifRecorder};
+use chip8::trace::{self, OpcodeClass, TraceFilter, Tracer};
+use chip8::variant::Variant;
+use chip8::video::{self, Overlay, Palette, Phosphor, Scaling, Video};
+use chip8::Chip8;
+use clap::Parser;
+use minifb::{Window, WindowOptions, Key, KeyRepeat};
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::net::{TcpListener, TcpStream};
+use std::ops::RangeInclusive;
+use std::path::Path;
 use std::process;
-use std::time::{Duration, Instant};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::Duration;
 
-const WINDOW_SCALE: usize = 10; // Увеличиваем окно в 10 раз
+const DEFAULT_WINDOW_SCALE: u16 = 10; // Увеличиваем окно в 10 раз
+
+#[derive(Parser)]
+#[command(name = "chip8")]
+#[command(about = "CHIP-8 emulator", version)]
+struct Cli {
+    /// ROM файл
+    rom: Option<String>,
+
+    /// Профиль совместимости: vip, chip48, schip, xochip
+    #[arg(short, long)]
+    quirks: Option<Preset>,
+
+    /// Вариант машины: classic (4KB) или xochip (64KB, плоскости, звуковой буфер)
+    #[arg(long, default_value_t = Variant::Classic)]
+    variant: Variant,
+
+    /// Писать трассу инструкций в файл (`-` для stdout)
+    #[arg(long)]
+    trace: Option<String>,
+
+    /// Трассировать только адреса из диапазона, например 200-2FF
+    #[arg(long, value_parser = trace::parse_address_range)]
+    trace_range: Option<std::ops::RangeInclusive<u16>>,
+
+    /// Трассировать только группы инструкций: system, jump, call, skip, alu, index, draw, timer, memory, other
+    #[arg(long, value_delimiter = ',')]
+    trace_class: Vec<OpcodeClass>,
+
+    /// Реакция на сбой программы: halt, pause, ignore
+    #[arg(long, default_value_t = FaultPolicy::Pause)]
+    on_fault: FaultPolicy,
+
+    /// Скорость: инструкций за кадр (60 кадров в секунду)
+    #[arg(long, default_value_t = scheduler::DEFAULT_INSTRUCTIONS_PER_FRAME)]
+    speed: usize,
+
+    /// Глубина перемотки (по умолчанию Backspace) в секундах, 0 - выключить
+    #[arg(long, default_value_t = 10)]
+    rewind: u32,
+
+    /// Отладчик: команды из stdin, старт на паузе (help - список команд)
+    #[arg(long)]
+    debug: bool,
+
+    /// Ждать подключения GDB на локальном TCP-порту (target remote :PORT)
+    #[arg(long, conflicts_with = "debug")]
+    gdb: Option<u16>,
+
+    /// Записывать звук в WAV-файл
+    #[arg(long)]
+    wav: Option<String>,
+
+    /// Без звука (живой звук есть только при сборке с --features sound)
+    #[arg(long)]
+    mute: bool,
+
+    /// Палитра: classic, amber, green, lcd, octo или цвета по плоскостям в hex
+    /// (фон, плоскость 1, плоскость 2, обе...), например 000000,FFFFFF,FF0000,00FF00
+    #[arg(long, default_value_t = Palette::default())]
+    palette: Palette,
+
+    /// Послесвечение: доля яркости, остающаяся за кадр (0 - выключено, 0.5 - убирает мерцание)
+    #[arg(long, default_value_t = 0.0, value_parser = video::parse_decay)]
+    phosphor: f32,
+
+    /// Начальный размер окна: во сколько раз больше экрана 64x32
+    #[arg(long, default_value_t = DEFAULT_WINDOW_SCALE, value_parser = clap::value_parser!(u16).range(1..=64))]
+    scale: u16,
+
+    /// Масштабирование в окне: integer (целый масштаб, поля по краям) или fit (на всё окно)
+    #[arg(long, default_value_t = Scaling::Integer)]
+    scaling: Scaling,
+
+    /// Эффект поверх картинки: none, scanlines, grid
+    #[arg(long, default_value_t = Overlay::None)]
+    overlay: Overlay,
+
+    /// Раскладка клавиш в TOML (по умолчанию ~/.config/chip8/keys.toml, если есть)
+    #[arg(long)]
+    keymap: Option<String>,
+
+    /// Записать игру в анимированный GIF (как видно в окне, с масштабом --scale);
+    /// без этого запись включается горячей клавишей (по умолчанию F11)
+    #[arg(long)]
+    gif: Option<String>,
+
+    /// Какие кадры писать в GIF: START-END или START- (по умолчанию все)
+    #[arg(long, value_parser = screenshot::parse_frame_range, requires = "gif")]
+    gif_frames: Option<RangeInclusive<u64>>,
+
+    /// Список читов (по умолчанию файл ROM в ~/.config/chip8/cheats, если есть)
+    #[arg(long)]
+    cheats: Option<String>,
+
+    /// Заморозить адрес или регистр: 3F0=09, V3=05, I=300 (значения в hex), можно повторять
+    #[arg(long)]
+    freeze: Vec<Cheat>,
+
+    /// Случайные числа для CXKK: thread, seed:N (воспроизводимо) или script:AA,BB,... (по кругу)
+    #[arg(long, default_value_t = RngConfig::Thread, conflicts_with = "replay")]
+    rng: RngConfig,
+
+    /// Записать ввод по кадрам в файл (воспроизводится через --replay)
+    #[arg(long)]
+    record: Option<String>,
+
+    /// Воспроизвести запись ввода; вариант, профиль, скорость и seed берутся из неё
+    #[arg(long, conflicts_with = "record")]
+    replay: Option<String>,
+
+    /// Профилировать: при выходе таблицы в консоль и отчёт в JSON в файл
+    #[arg(long)]
+    profile: Option<String>,
+
+    /// Символы ассемблера для отчёта (по умолчанию ROM.sym рядом с ROM, если есть)
+    #[arg(long, requires = "profile")]
+    symbols: Option<String>,
+}
 
 fn main() {
   
     // Получаем аргументы командной строки
-    let args: Vec<String> = env::args().collect();
+    let mut cli = Cli::parse();
     
-    if args.len() < 2 {
-        print_usage(&args[0]);
+    let Some(rom_path) = &cli.rom else {
+        print_usage("chip8");
         return;
-    }
-    
-    let rom_path = &args[1];
+    };
     
     // Создаем окно
     let mut window = Window::new(
         &format!("CHIP-8 Emulator - {}", rom_path),
-        constants::SCREEN_WIDTH * WINDOW_SCALE,
-        constants::SCREEN_HEIGHT * WINDOW_SCALE,
-        WindowOptions::default(),
+        constants::SCREEN_WIDTH * cli.scale as usize,
+        constants::SCREEN_HEIGHT * cli.scale as usize,
+        WindowOptions { resize: true, ..WindowOptions::default() },
     ).unwrap_or_else(|e| {
         panic!("Failed to create window: {}", e);
     });
@@ -36,34 +171,170 @@ fn main() {
     // Ограничиваем FPS для стабильной эмуляции
     window.limit_update_rate(Some(Duration::from_micros(16666))); // ~60 FPS
     
-    // Создаем и настраиваем процессор
-    let mut cpu = CPU::new();
+    // Создаем и настраиваем машину
+    let mut chip8 = match &cli.replay {
+        Some(movie_path) => start_replay(movie_path, rom_path, &mut cli.speed),
+        None => load_machine(&cli, rom_path),
+    };
+    chip8.set_rewind(cli.rewind);
+    load_cheats(&mut chip8, &cli);
+    let keymap = load_keymap(&cli, rom_path, chip8.rom_hash());
+    let mut runner = Runner::new(&chip8, Scheduler::real_time(cli.speed), keymap, rom_path)
+        .with_fault_policy(cli.on_fault);
+    
+    if let Some(path) = &cli.trace {
+        let filter = TraceFilter {
+            addresses: cli.trace_range.clone(),
+            classes: cli.trace_class.clone(),
+        };
+        match Tracer::to_path(filter, path) {
+            Ok(tracer) => chip8.set_tracer(Some(tracer)),
+            Err(e) => {
+                println!("Failed to open trace file '{}': {}", path, e);
+                process::exit(1);
+            }
+        }
+    }
+    
+    let symbols = match &cli.profile {
+        Some(_) => {
+            chip8.set_profiler(Some(Profiler::new()));
+            profile::load_symbols(cli.symbols.as_deref(), Path::new(rom_path)).unwrap_or_else(|e| {
+                println!("Failed to load symbols: {}", e);
+                process::exit(1);
+            })
+        }
+        None => Default::default(),
+    };
+
+    if cli.record.is_some() {
+        chip8.start_recording(cli.speed);
+    }
+    
+    setup_audio(&mut chip8, &cli);
+    run_emulation(&mut chip8, &mut window, &cli, &mut runner);
+    if let Err(e) = chip8.finish_audio() {
+        println!("Audio output failed: {}", e);
+    }
+    if let (Some(path), Some(movie)) = (&cli.record, chip8.stop_recording()) {
+        match movie.write_to(path) {
+            Ok(()) => println!("Movie saved to '{}' ({} frames)", path, movie.frames()),
+            Err(e) => println!("Failed to save movie '{}': {}", path, e),
+        }
+    }
+    if let (Some(path), Some(profiler)) = (&cli.profile, chip8.profiler()) {
+        print!("{}", profiler.text_report(&chip8, &symbols));
+        match fs::write(path, profiler.json_report(&chip8, &symbols)) {
+            Ok(()) => println!("Profile saved to '{}'", path),
+            Err(e) => println!("Failed to save profile '{}': {}", path, e),
+        }
+    }
+}
+
+/// Машина по параметрам командной строки с загруженным ROM
+fn load_machine(cli: &Cli, rom_path: &str) -> Chip8 {
+    let mut chip8 = Chip8::new(cli.variant);
+    if let Some(preset) = cli.quirks {
+        chip8 = chip8.with_quirks(preset.quirks());
+        println!("Quirks preset: {}", preset);
+    }
+    chip8.set_rng(cli.rng.build());
     
     // Загружаем ROM
-    match cpu.load_rom(rom_path) {
+    let loaded = fs::read(rom_path)
+        .map_err(|e| format!("Failed to read ROM file: {}", e))
+        .and_then(|rom| chip8.load(&rom));
+    match loaded {
         Ok(_) => println!("ROM '{}' loaded successfully", rom_path),
         Err(e) => {
             println!("Failed to load ROM '{}': {}", rom_path, e);
             process::exit(1);
         }
     }
-    
-    run_emulation(&mut cpu, &mut window);
+    chip8
+}
+
+/// Машина для воспроизведения записи ввода; скорость берётся из записи
+fn start_replay(movie_path: &str, rom_path: &str, speed: &mut usize) -> Chip8 {
+    let started = Movie::read_from(movie_path)
+        .map_err(|e| e.to_string())
+        .and_then(|movie| {
+            let rom = fs::read(rom_path).map_err(|e| format!("Failed to read ROM file: {}", e))?;
+            *speed = movie.instructions_per_frame as usize;
+            println!("Replaying '{}': {} frames at speed {}", movie_path, movie.frames(), speed);
+            movie.start(&rom).map_err(|e| e.to_string())
+        });
+    started.unwrap_or_else(|e| {
+        println!("Failed to replay '{}': {}", movie_path, e);
+        process::exit(1);
+    })
+}
+
+/// Раскладка из --keymap или из файла по умолчанию; без файла - встроенная
+fn load_keymap(cli: &Cli, rom_path: &str, rom_hash: u64) -> Keymap {
+    let Some(path) = keymap::config_path(cli.keymap.as_deref()) else {
+        return Keymap::default();
+    };
+    match Keymap::load(&path, rom_path, rom_hash) {
+        Ok(keymap) => {
+            println!("Key bindings loaded from '{}'", path.display());
+            keymap
+        }
+        Err(e) => {
+            println!("Failed to load key bindings: {}", e);
+            process::exit(1);
+        }
+    }
+}
+
+/// Читы из --cheats или из файла ROM по умолчанию, затем --freeze
+fn load_cheats(chip8: &mut Chip8, cli: &Cli) {
+    if let Some(path) = cheat::list_path(cli.cheats.as_deref(), chip8.rom_hash()) {
+        match CheatList::read_from(&path).and_then(|list| chip8.load_cheats(&list)) {
+            Ok(()) => println!("Cheats loaded from '{}'", path.display()),
+            Err(e) => {
+                println!("Failed to load cheats '{}': {}", path.display(), e);
+                process::exit(1);
+            }
+        }
+    }
+    for cheat in &cli.freeze {
+        chip8.add_cheat(*cheat);
+    }
+}
+
+/// Подключаем звуковую карту и запись в WAV; без звука эмулятор тоже работает
+fn setup_audio(chip8: &mut Chip8, cli: &Cli) {
+    #[cfg(feature = "sound")]
+    if !cli.mute {
+        match audio::live::LiveSink::open() {
+            Ok(sink) => chip8.add_audio_sink(Box::new(sink)),
+            Err(e) => println!("Sound disabled: {}", e),
+        }
+    }
+
+    if let Some(path) = &cli.wav {
+        match WavSink::create(path, audio::SAMPLE_RATE) {
+            Ok(sink) => {
+                chip8.add_audio_sink(Box::new(sink));
+                println!("Recording sound to '{}'", path);
+            }
+            Err(e) => println!("Failed to create WAV file '{}': {}", path, e),
+        }
+    }
 }
 
 fn print_usage(program_name: &str) {
-    println!("Usage: {} <rom_file>", program_name);
+    println!("Usage: {} [--quirks <vip|chip48|schip|xochip>] [--variant <classic|xochip>] [--speed <instructions_per_frame>] <rom_file>", program_name);
     println!("\nAvailable ROMs:");
     
     let roms_dir = "roms";
     if let Ok(entries) = std::fs::read_dir(roms_dir) {
-        for entry in entries {
-            if let Ok(entry) = entry {
-                if let Some(filename) = entry.file_name().to_str() {
-                    if filename.ends_with(".ch8") {
-                        println!("  {}/{}", roms_dir, filename);
-                    }
-                }
+        for entry in entries.flatten() {
+            if let Some(filename) = entry.file_name().to_str()
+                && filename.ends_with(".ch8")
+            {
+                println!("  {}/{}", roms_dir, filename);
             }
         }
     }
@@ -71,52 +342,322 @@ fn print_usage(program_name: &str) {
     println!("\nExamples:");
     println!("  cargo run -p chip8 -- roms/games/Pong.ch8");
     println!("  cargo run -p chip8 -- roms/games/Tetris.ch8");
+    println!("  cargo run -p chip8 -- --quirks vip roms/games/Blinky.ch8");
+    println!("  cargo run -p chip8 -- --speed 30 roms/games/Tetris.ch8");
+    println!("  cargo run -p chip8 -- --variant xochip --quirks xochip roms/xo/Chicken.ch8");
 }
 
-fn run_emulation(cpu: &mut CPU, window: &mut Window) {
-    let mut last_timer_update = Instant::now();
-    let mut cycle_count = 0;
+fn run_emulation(chip8: &mut Chip8, window: &mut Window, cli: &Cli, runner: &mut Runner) {
+    let mut video = Video::default();
+    video.palette = cli.palette;
+    video.phosphor = (cli.phosphor > 0.0).then(|| Phosphor::new(cli.phosphor));
+    video.scaling = cli.scaling;
+    video.overlay = cli.overlay;
+    let mut window_size = (0, 0);
+    let mut gif = cli.gif.as_ref().map(|path| {
+        let frames = cli.gif_frames.clone().unwrap_or(0..=u64::MAX);
+        GifCapture::start(path, frames, chip8.frame()).unwrap_or_else(|e| {
+            println!("Failed to create GIF '{}': {}", path, e);
+            process::exit(1);
+        })
+    });
+    let mut console = cli.debug.then(|| DebugConsole::start(chip8));
+    let mut gdb = cli.gdb.map(|port| wait_for_gdb(port).unwrap_or_else(|e| {
+        println!("Failed to start GDB server on port {}: {}", port, e);
+        process::exit(1);
+    }));
     
-    // Главный цикл
-    while window.is_open() && !window.is_key_down(Key::Escape) {
-        // Обрабатываем ввод с клавиатуры
-        handle_keyboard_input(cpu, window);
-        
-        // Выполняем один цикл эмуляции
-        cpu.cycle();
-        cycle_count += 1;
-        
-        // Обновляем таймеры 60 раз в секунду
-        if last_timer_update.elapsed() >= Duration::from_millis(16) {
-            cpu.update_timers();
-            last_timer_update = Instant::now();
+    // Главный цикл: каждый проход выполняет накопившиеся кадры,
+    // таймеры тикают внутри кадра, а не по часам окна
+    while window.is_open() {
+        print_messages(runner);
+        let input = Input {
+            held: host_keys(window.get_keys()),
+            pressed: host_keys(window.get_keys_pressed(KeyRepeat::No)),
+        };
+        if !runner.handle_hotkeys(chip8, &input) {
+            break;
         }
-        
-        // Обработка ожидания клавиши
-        if let Some(reg) = cpu.waiting_for_key {
-            if let Some(key) = cpu.keyboard.get_pressed_key() {
-                cpu.registers[reg] = key;
-                cpu.waiting_for_key = None;
-                println!("Key pressed: {} -> V[{}]", key, reg);
+
+        if runner.rewind(chip8, &input) {
+            // Кадр ушёл на перемотку
+        } else if let Some(console) = &mut console {
+            // Под отладчиком сбои и точки останова ставят на паузу
+            runner.apply_keys(chip8, &input);
+            if !console.poll(chip8) {
+                break;
+            }
+            for _ in 0..runner.scheduler.due_frames() {
+                if let Some(stop) = console.debugger.run_frame(chip8, runner.scheduler.instructions_per_frame) {
+                    println!("{}\n{}", stop, debugger::location(chip8));
+                    prompt();
+                    break;
+                }
+            }
+        } else if let Some(stub) = &mut gdb {
+            runner.apply_keys(chip8, &input);
+            let mut session = stub.poll(chip8);
+            if matches!(session, Ok(Session::Active)) {
+                for _ in 0..runner.scheduler.due_frames() {
+                    match stub.run_frame(chip8, runner.scheduler.instructions_per_frame) {
+                        Ok(None) => {}
+                        Ok(Some(_)) => break,
+                        Err(e) => {
+                            session = Err(e);
+                            break;
+                        }
+                    }
+                }
+            }
+            match session {
+                Ok(Session::Active) => {}
+                Ok(Session::Killed) => break,
+                // После отключения отладчика игра продолжается как обычно
+                Ok(Session::Detached) => {
+                    println!("GDB detached");
+                    gdb = None;
+                }
+                Err(e) => {
+                    println!("GDB connection lost: {}", e);
+                    gdb = None;
+                }
+            }
+        } else if !runner.run(chip8, &input) {
+            break;
+        }
+
+        for hotkey in runner.take_frontend_hotkeys() {
+            match hotkey {
+                Hotkey::Screenshot => save_screenshot(&video, chip8, cli),
+                Hotkey::RecordGif => match gif.take() {
+                    Some(capture) => capture.finish(),
+                    None => {
+                        let path = numbered_path(cli, "gif");
+                        match GifCapture::start(&path, 0..=u64::MAX, chip8.frame()) {
+                            Ok(capture) => {
+                                println!("Recording GIF to '{}'", path);
+                                gif = Some(capture);
+                            }
+                            Err(e) => println!("Failed to create GIF '{}': {}", path, e),
+                        }
+                    }
+                },
+                _ => {}
             }
         }
         
-        // Обновляем экран если нужно
-        if cpu.display.needs_redraw {
-            let buffer = cpu.display.to_buffer();
-            window.update_with_buffer(&buffer, constants::SCREEN_WIDTH, constants::SCREEN_HEIGHT)
+        // Обновляем экран если нужно, иначе просто поддерживаем окно живым.
+        // Послесвечение гаснет само, поэтому с ним кадр рисуется всегда
+        let size = window.get_size();
+        if chip8.needs_redraw() || video.is_animated() || size != window_size {
+            // Масштаб и палитра считаются программно под текущий размер окна
+            let buffer = video.render(chip8.display(), size.0, size.1);
+            window.update_with_buffer(&buffer, size.0, size.1)
                 .unwrap();
-            cpu.display.needs_redraw = false;
+            chip8.mark_drawn();
+            window_size = size;
+        } else {
+            window.update();
+        }
+
+        if let Some(capture) = &mut gif
+            && !capture.record(&video, chip8, cli.scale as usize)
+            && let Some(capture) = gif.take()
+        {
+            capture.finish();
         }
-        
+    }
+    print_messages(runner);
+    if let Some(capture) = gif {
+        capture.finish();
     }
     
     println!("\nEmulation finished!");
-    println!("Total cycles: {}", cycle_count);
+    println!("Total cycles: {}", chip8.cpu().cycles);
+}
+
+/// Снимок экрана, как он виден в окне, в `<rom>-NNN.png` рядом с ROM
+fn save_screenshot(video: &Video, chip8: &Chip8, cli: &Cli) {
+    let path = numbered_path(cli, "png");
+    let image = video.snapshot(chip8.display(), cli.scale as usize);
+    let saved = File::create(&path)
+        .map_err(|e| e.to_string())
+        .and_then(|file| screenshot::write_png(&image, BufWriter::new(file)).map_err(|e| e.to_string()));
+    match saved {
+        Ok(()) => println!("Screenshot saved to '{}'", path),
+        Err(e) => println!("Failed to save screenshot '{}': {}", path, e),
+    }
+}
+
+/// Первый свободный `<rom без расширения>-NNN.<extension>`
+fn numbered_path(cli: &Cli, extension: &str) -> String {
+    let rom = cli.rom.as_deref().unwrap_or("chip8");
+    let prefix = Path::new(rom).with_extension("");
+    screenshot::next_free_path(&prefix.to_string_lossy(), extension).display().to_string()
+}
+
+/// Запись GIF из окна: кадры машины из диапазона в том виде, в каком они на экране
+struct GifCapture {
+    recorder: GifRecorder<BufWriter<File>>,
+    path: String,
+    // Номера кадров машины, считая с нуля
+    frames: RangeInclusive<u64>,
+    // Сколько кадров машины уже учтено
+    seen: u64,
+}
+
+impl GifCapture {
+    fn start(path: &str, frames: RangeInclusive<u64>, seen: u64) -> io::Result<Self> {
+        Ok(GifCapture { recorder: GifRecorder::create(path)?, path: path.to_string(), frames, seen })
+    }
+
+    /// Добавить кадры, выполненные с прошлого вызова. false - диапазон кончился
+    fn record(&mut self, video: &Video, chip8: &Chip8, scale: usize) -> bool {
+        let done = chip8.frame();
+        let first = self.seen.max(*self.frames.start());
+        let last = done.min(self.frames.end().saturating_add(1));
+        if first < last {
+            let image = video.snapshot(chip8.display(), scale);
+            if let Err(e) = self.recorder.push(&image, last - first) {
+                println!("Failed to write GIF '{}': {}", self.path, e);
+                return false;
+            }
+        }
+        self.seen = done;
+        done <= *self.frames.end()
+    }
+
+    fn finish(self) {
+        let frames = self.recorder.frames();
+        match self.recorder.finish() {
+            Ok(_) if frames == 0 => println!("GIF '{}' is empty: no frames were recorded", self.path),
+            Ok(_) => println!("GIF saved to '{}' ({} frames)", self.path, frames),
+            Err(e) => println!("Failed to write GIF '{}': {}", self.path, e),
+        }
+    }
+}
+
+fn print_messages(runner: &mut Runner) {
+    for message in runner.take_messages() {
+        println!("{}", message);
+    }
 }
 
-/// Обработка ввода с клавиатуры
-fn handle_keyboard_input(cpu: &mut CPU, window: &Window) {
-    let keys = window.get_keys();
-    cpu.keyboard.update_from_minifb(&keys);
-}
\ No newline at end of file
+/// Консоль отладчика: команды читаются из stdin в отдельном потоке,
+/// чтобы окно продолжало обновляться
+struct DebugConsole {
+    debugger: Debugger,
+    commands: Receiver<String>,
+}
+
+impl DebugConsole {
+    fn start(chip8: &Chip8) -> Self {
+        let (sender, commands) = mpsc::channel();
+        thread::spawn(move || {
+            for line in io::stdin().lines().map_while(Result::ok) {
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        println!("Debugger started, execution paused. Type 'help' for commands.");
+        println!("{}", debugger::location(chip8));
+        prompt();
+        DebugConsole { debugger: Debugger::new(), commands }
+    }
+
+    /// Выполнить введённые команды. false - пользователь вышел
+    fn poll(&mut self, chip8: &mut Chip8) -> bool {
+        while let Ok(line) = self.commands.try_recv() {
+            if !line.trim().is_empty() {
+                match line.parse::<Command>() {
+                    Ok(Command::Quit) => return false,
+                    Ok(command) => println!("{}", self.debugger.execute(command, chip8)),
+                    Err(e) => println!("{}", e),
+                }
+            }
+            prompt();
+        }
+        true
+    }
+}
+
+fn prompt() {
+    print!("(chip8) ");
+    let _ = io::stdout().flush();
+}
+
+/// Принять одно подключение GDB на 127.0.0.1:PORT
+fn wait_for_gdb(port: u16) -> io::Result<GdbStub<TcpStream>> {
+    let listener = TcpListener::bind(("127.0.0.1", port))?;
+    println!("Waiting for GDB on 127.0.0.1:{} ...", port);
+    let (stream, peer) = listener.accept()?;
+    stream.set_nonblocking(true)?;
+    stream.set_nodelay(true)?;
+    println!("GDB connected from {}", peer);
+    Ok(GdbStub::new(stream))
+}
+
+fn host_keys(keys: Vec<Key>) -> Vec<HostKey> {
+    keys.into_iter().filter_map(host_key).collect()
+}
+
+/// Клавиша minifb в общем виде для раскладки
+fn host_key(key: Key) -> Option<HostKey> {
+    const DIGITS: [Key; 10] = [
+        Key::Key0, Key::Key1, Key::Key2, Key::Key3, Key::Key4,
+        Key::Key5, Key::Key6, Key::Key7, Key::Key8, Key::Key9,
+    ];
+    const LETTERS: [Key; 26] = [
+        Key::A, Key::B, Key::C, Key::D, Key::E, Key::F, Key::G, Key::H, Key::I,
+        Key::J, Key::K, Key::L, Key::M, Key::N, Key::O, Key::P, Key::Q, Key::R,
+        Key::S, Key::T, Key::U, Key::V, Key::W, Key::X, Key::Y, Key::Z,
+    ];
+    const F_KEYS: [Key; 15] = [
+        Key::F1, Key::F2, Key::F3, Key::F4, Key::F5, Key::F6, Key::F7, Key::F8,
+        Key::F9, Key::F10, Key::F11, Key::F12, Key::F13, Key::F14, Key::F15,
+    ];
+
+    if let Some(i) = DIGITS.iter().position(|&k| k == key) {
+        return Some(HostKey::Char((b'0' + i as u8) as char));
+    }
+    if let Some(i) = LETTERS.iter().position(|&k| k == key) {
+        return Some(HostKey::Char((b'a' + i as u8) as char));
+    }
+    if let Some(i) = F_KEYS.iter().position(|&k| k == key) {
+        return Some(HostKey::F(i as u8 + 1));
+    }
+    Some(match key {
+        Key::Up => HostKey::Up,
+        Key::Down => HostKey::Down,
+        Key::Left => HostKey::Left,
+        Key::Right => HostKey::Right,
+        Key::Space => HostKey::Space,
+        Key::Enter | Key::NumPadEnter => HostKey::Enter,
+        Key::Escape => HostKey::Escape,
+        Key::Backspace => HostKey::Backspace,
+        Key::Tab => HostKey::Tab,
+        Key::Insert => HostKey::Insert,
+        Key::Delete => HostKey::Delete,
+        Key::Home => HostKey::Home,
+        Key::End => HostKey::End,
+        Key::PageUp => HostKey::PageUp,
+        Key::PageDown => HostKey::PageDown,
+        Key::LeftShift | Key::RightShift => HostKey::Shift,
+        Key::LeftCtrl | Key::RightCtrl => HostKey::Ctrl,
+        Key::LeftAlt | Key::RightAlt => HostKey::Alt,
+        Key::Apostrophe => HostKey::Char('\''),
+        Key::Backquote => HostKey::Char('`'),
+        Key::Backslash => HostKey::Char('\\'),
+        Key::Comma => HostKey::Char(','),
+        Key::Equal => HostKey::Char('='),
+        Key::LeftBracket => HostKey::Char('['),
+        Key::RightBracket => HostKey::Char(']'),
+        Key::Minus => HostKey::Char('-'),
+        Key::Period => HostKey::Char('.'),
+        Key::Semicolon => HostKey::Char(';'),
+        Key::Slash => HostKey::Char('/'),
+        _ => return None,
+    })
+}
@@ -0,0 +1,320 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use thiserror::Error;
+
+use crate::quirks::Quirks;
+use crate::rng::SeededRng;
+use crate::savestate;
+use crate::variant::Variant;
+use crate::Chip8;
+
+/// Сигнатура файла записи ввода
+pub const MAGIC: &[u8; 4] = b"C8MV";
+
+/// Текущая версия формата
+pub const VERSION: u16 = 3;
+
+fn version_message(version: u16) -> String {
+    if version < VERSION {
+        format!(
+            "Movie version {} was recorded by an older emulator and cannot be replayed \
+             (this build reads version {}); record it again",
+            version, VERSION
+        )
+    } else {
+        format!("Movie version {} is newer than this build supports (version {})", version, VERSION)
+    }
+}
+
+/// Ошибка чтения записи или её запуска
+#[derive(Error, Debug)]
+pub enum MovieError {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("Not a CHIP-8 movie")]
+    BadMagic,
+
+    #[error("{}", version_message(*.0))]
+    UnsupportedVersion(u16),
+
+    #[error("Movie was recorded with another ROM (hash {expected:016X}, loaded ROM {found:016X})")]
+    RomMismatch { expected: u64, found: u64 },
+
+    #[error("Movie is truncated")]
+    Truncated,
+
+    #[error("Movie is corrupted: {0}")]
+    Invalid(&'static str),
+
+    #[error("Failed to load ROM: {0}")]
+    Rom(String),
+}
+
+/// Запись ввода: настройки машины, начальное значение генератора случайных
+/// чисел и состояние клавиш в каждом кадре. Одинаковые кадры подряд
+/// хранятся одной серией, поэтому запись занимает немного места.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Movie {
+    pub rom_hash: u64,
+    pub variant: Variant,
+    pub quirks: Quirks,
+    pub instructions_per_frame: u32,
+    pub seed: u64,
+    // Серии кадров: маска нажатых клавиш и сколько кадров подряд она держится
+    runs: Vec<(u16, u32)>,
+}
+
+impl Movie {
+    /// Пустая запись для машины сразу после загрузки ROM
+    pub fn new(chip8: &Chip8, instructions_per_frame: usize, seed: u64) -> Self {
+        let cpu = chip8.cpu();
+        Movie {
+            rom_hash: chip8.rom_hash(),
+            variant: cpu.variant,
+            quirks: cpu.quirks,
+            instructions_per_frame: instructions_per_frame as u32,
+            seed,
+            runs: Vec::new(),
+        }
+    }
+
+    /// Длина записи в кадрах
+    pub fn frames(&self) -> u64 {
+        self.runs.iter().map(|&(_, count)| count as u64).sum()
+    }
+
+    /// Добавить кадр с состоянием клавиш `keys`
+    pub fn push(&mut self, keys: &[bool; 16]) {
+        let mask = key_mask(keys);
+        match self.runs.last_mut() {
+            Some((last, count)) if *last == mask && *count < u32::MAX => *count += 1,
+            _ => self.runs.push((mask, 1)),
+        }
+    }
+
+    /// Состояние клавиш по кадрам
+    pub fn keys(&self) -> impl Iterator<Item = [bool; 16]> + '_ {
+        self.runs
+            .iter()
+            .flat_map(|&(mask, count)| std::iter::repeat_n(keys_from_mask(mask), count as usize))
+    }
+
+    /// Новая машина с настройками записи и загруженным ROM, готовая к воспроизведению
+    pub fn start(self, rom: &[u8]) -> Result<Chip8, MovieError> {
+        let found = savestate::rom_hash(rom);
+        if found != self.rom_hash {
+            return Err(MovieError::RomMismatch { expected: self.rom_hash, found });
+        }
+        let mut chip8 = Chip8::new(self.variant).with_quirks(self.quirks);
+        chip8.load(rom).map_err(MovieError::Rom)?;
+        chip8.set_rng(Box::new(SeededRng::new(self.seed)));
+        chip8.play(self);
+        Ok(chip8)
+    }
+
+    /// Двоичный формат (little-endian): сигнатура, версия, настройки, затем серии
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(32 + self.runs.len() * 6);
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(&VERSION.to_le_bytes());
+        out.extend_from_slice(&self.rom_hash.to_le_bytes());
+        out.push(match self.variant {
+            Variant::Classic => 0,
+            Variant::XoChip => 1,
+        });
+        out.extend_from_slice(&self.quirks.to_bytes());
+        out.extend_from_slice(&self.instructions_per_frame.to_le_bytes());
+        out.extend_from_slice(&self.seed.to_le_bytes());
+        out.extend_from_slice(&(self.runs.len() as u32).to_le_bytes());
+        for &(mask, count) in &self.runs {
+            out.extend_from_slice(&mask.to_le_bytes());
+            out.extend_from_slice(&count.to_le_bytes());
+        }
+        out
+    }
+
+    pub fn from_bytes(mut data: &[u8]) -> Result<Self, MovieError> {
+        let mut take = |len: usize| -> Result<&[u8], MovieError> {
+            if data.len() < len {
+                return Err(MovieError::Truncated);
+            }
+            let (head, tail) = data.split_at(len);
+            data = tail;
+            Ok(head)
+        };
+
+        if take(MAGIC.len()).map_err(|_| MovieError::BadMagic)? != MAGIC {
+            return Err(MovieError::BadMagic);
+        }
+        let version = u16::from_le_bytes(take(2)?.try_into().unwrap());
+        if version != VERSION {
+            return Err(MovieError::UnsupportedVersion(version));
+        }
+        let rom_hash = u64::from_le_bytes(take(8)?.try_into().unwrap());
+        let variant = match take(1)?[0] {
+            0 => Variant::Classic,
+            1 => Variant::XoChip,
+            _ => return Err(MovieError::Invalid("unknown variant")),
+        };
+        let quirks = Quirks::from_bytes(take(Quirks::ENCODED_LEN)?.try_into().unwrap())
+            .map_err(MovieError::Invalid)?;
+        let instructions_per_frame = u32::from_le_bytes(take(4)?.try_into().unwrap());
+        let seed = u64::from_le_bytes(take(8)?.try_into().unwrap());
+
+        let run_count = u32::from_le_bytes(take(4)?.try_into().unwrap()) as usize;
+        let mut runs = Vec::new();
+        for _ in 0..run_count {
+            let mask = u16::from_le_bytes(take(2)?.try_into().unwrap());
+            let count = u32::from_le_bytes(take(4)?.try_into().unwrap());
+            if count == 0 {
+                return Err(MovieError::Invalid("empty run"));
+            }
+            runs.push((mask, count));
+        }
+
+        Ok(Movie { rom_hash, variant, quirks, instructions_per_frame, seed, runs })
+    }
+
+    pub fn write_to(&self, path: impl AsRef<Path>) -> Result<(), MovieError> {
+        fs::write(path, self.to_bytes())?;
+        Ok(())
+    }
+
+    pub fn read_from(path: impl AsRef<Path>) -> Result<Self, MovieError> {
+        Self::from_bytes(&fs::read(path)?)
+    }
+}
+
+/// Воспроизведение записи по кадрам
+#[derive(Debug, Clone)]
+pub struct Player {
+    movie: Movie,
+    // Текущая серия и сколько кадров из неё уже отдано
+    run: usize,
+    offset: u32,
+    frame: u64,
+}
+
+impl Player {
+    pub fn new(movie: Movie) -> Self {
+        Player { movie, run: 0, offset: 0, frame: 0 }
+    }
+
+    pub fn movie(&self) -> &Movie {
+        &self.movie
+    }
+
+    /// Сколько кадров уже воспроизведено
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    /// Клавиши следующего кадра; `None`, когда запись кончилась
+    pub fn next_keys(&mut self) -> Option<[bool; 16]> {
+        let &(mask, count) = self.movie.runs.get(self.run)?;
+        self.offset += 1;
+        if self.offset == count {
+            self.run += 1;
+            self.offset = 0;
+        }
+        self.frame += 1;
+        Some(keys_from_mask(mask))
+    }
+}
+
+fn key_mask(keys: &[bool; 16]) -> u16 {
+    keys.iter().enumerate().fold(0, |mask, (key, &pressed)| mask | ((pressed as u16) << key))
+}
+
+fn keys_from_mask(mask: u16) -> [bool; 16] {
+    std::array::from_fn(|key| mask & (1 << key) != 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::quirks::Preset;
+
+    // Рисует цифру из случайного регистра в позиции, зависящей от нажатой клавиши:
+    // без одинакового seed и ввода кадр не повторить
+    const PROGRAM: &[u8] = &[
+        0xC0, 0x0F, // 200: RND V0, 0F
+        0xF0, 0x29, // 202: LD F, V0
+        0x61, 0x05, // 204: LD V1, 5
+        0xE1, 0xA1, // 206: SKNP V1
+        0x72, 0x01, // 208: ADD V2, 1
+        0xD2, 0x35, // 20A: DRW V2, V3, 5
+        0x73, 0x01, // 20C: ADD V3, 1
+        0x12, 0x00, // 20E: JP 200
+    ];
+
+    fn record(frames: u64) -> (Movie, Chip8) {
+        let mut chip8 = Chip8::new(Variant::Classic).with_quirks(Preset::Vip.quirks());
+        chip8.load(PROGRAM).unwrap();
+        chip8.start_recording(8);
+        for frame in 0..frames {
+            chip8.set_key(5, frame % 7 < 3);
+            chip8.run_frame(8).unwrap();
+        }
+        (chip8.stop_recording().unwrap(), chip8)
+    }
+
+    #[test]
+    fn replay_reproduces_framebuffer() {
+        let (movie, recorded) = record(120);
+        assert_eq!(movie.frames(), 120);
+
+        let mut replay = movie.clone().start(PROGRAM).unwrap();
+        assert!(replay.is_replaying());
+        while replay.is_replaying() {
+            replay.run_frame(movie.instructions_per_frame as usize).unwrap();
+        }
+        assert_eq!(replay.display().pixels, recorded.display().pixels);
+        assert_eq!(replay.cpu().registers, recorded.cpu().registers);
+    }
+
+    #[test]
+    fn runs_are_compressed() {
+        let (movie, _) = record(700);
+        // Клавиша меняется дважды за 7 кадров
+        assert_eq!(movie.runs.len(), 200);
+        assert_eq!(movie.keys().count(), 700);
+        assert_eq!(movie.keys().take(4).map(|keys| keys[5]).collect::<Vec<_>>(), [true, true, true, false]);
+    }
+
+    #[test]
+    fn bytes_round_trip() {
+        let (movie, _) = record(30);
+        let bytes = movie.to_bytes();
+        assert_eq!(Movie::from_bytes(&bytes).unwrap(), movie);
+
+        assert!(matches!(Movie::from_bytes(b"C8ST"), Err(MovieError::BadMagic)));
+        assert!(matches!(Movie::from_bytes(&bytes[..bytes.len() - 1]), Err(MovieError::Truncated)));
+
+        let mut old = bytes.clone();
+        old[4] = 1;
+        let message = Movie::from_bytes(&old).unwrap_err().to_string();
+        assert!(message.contains("older emulator"), "{}", message);
+    }
+
+    #[test]
+    fn rejects_other_rom() {
+        let (movie, _) = record(1);
+        assert!(matches!(movie.start(&[0x12, 0x00]), Err(MovieError::RomMismatch { .. })));
+    }
+
+    #[test]
+    fn player_ends_after_last_frame() {
+        let mut movie = Movie::new(&Chip8::default(), 10, 0);
+        movie.push(&[true; 16]);
+        movie.push(&[true; 16]);
+        let mut player = Player::new(movie);
+        assert_eq!(player.next_keys(), Some([true; 16]));
+        assert_eq!(player.next_keys(), Some([true; 16]));
+        assert_eq!(player.next_keys(), None);
+        assert_eq!(player.frame(), 2);
+    }
+}
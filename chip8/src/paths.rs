@@ -0,0 +1,19 @@
+//! Файлы настроек эмулятора (раскладка, читы) в каталоге пользователя.
+
+use std::path::{Path, PathBuf};
+
+/// Каталог настроек: $XDG_CONFIG_HOME/chip8 или ~/.config/chip8
+pub fn config_dir() -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+    Some(base.join("chip8"))
+}
+
+/// Файл, заданный явно, или файл по умолчанию, если он есть
+pub fn explicit_or_existing(explicit: Option<&str>, default: Option<PathBuf>) -> Option<PathBuf> {
+    match explicit {
+        Some(path) => Some(PathBuf::from(path)),
+        None => default.filter(|path| path.exists()),
+    }
+}
@@ -0,0 +1,497 @@
+//! Профилировщик: сколько раз выполнялся каждый адрес и каждая группа
+//! инструкций, время в подпрограммах по парам 2NNN/00EE и покрытие ROM -
+//! какие байты выполнялись, а какие читались как данные. Время считается
+//! в выполненных инструкциях.
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+
+use crate::constants::{PROGRAM_START, XO_MEMORY_SIZE};
+use crate::disasm::{self, mnemonic};
+use crate::trace::OpcodeClass;
+use crate::Chip8;
+
+/// Сколько самых частых адресов в текстовом отчёте
+pub const HOT_ADDRESSES: usize = 20;
+
+/// Время подпрограммы
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FunctionStats {
+    pub calls: u64,
+    // Вместе с вложенными вызовами (у рекурсии вложенное время считается повторно)
+    pub inclusive: u64,
+    // Только собственные инструкции
+    pub exclusive: u64,
+}
+
+/// Как использовался байт памяти
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ByteUse {
+    Untouched,
+    Code,
+    Data,
+    /// И выполнялся, и читался как данные (самомодифицирующийся код, таблицы в коде)
+    Both,
+}
+
+impl ByteUse {
+    /// Символ в карте покрытия
+    pub fn symbol(&self) -> char {
+        match self {
+            ByteUse::Untouched => '.',
+            ByteUse::Code => 'x',
+            ByteUse::Data => 'd',
+            ByteUse::Both => 'b',
+        }
+    }
+
+    pub fn from_symbol(symbol: char) -> Option<Self> {
+        [ByteUse::Untouched, ByteUse::Code, ByteUse::Data, ByteUse::Both]
+            .into_iter()
+            .find(|byte| byte.symbol() == symbol)
+    }
+
+    pub fn is_code(&self) -> bool {
+        matches!(self, ByteUse::Code | ByteUse::Both)
+    }
+}
+
+/// Покрытие участка памяти, начиная с адреса `start`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Coverage {
+    pub start: u16,
+    pub bytes: Vec<ByteUse>,
+}
+
+impl Coverage {
+    /// Карта одной строкой: `.` - не трогали, `x` - код, `d` - данные, `b` - и то и другое
+    pub fn map(&self) -> String {
+        self.bytes.iter().map(ByteUse::symbol).collect()
+    }
+
+    /// Выполнялся ли байт по адресу `addr`
+    pub fn is_executed(&self, addr: u16) -> bool {
+        addr.checked_sub(self.start)
+            .and_then(|offset| self.bytes.get(offset as usize))
+            .is_some_and(ByteUse::is_code)
+    }
+
+    /// Сколько байт используется так
+    pub fn count(&self, kind: ByteUse) -> usize {
+        self.bytes.iter().filter(|&&byte| byte == kind).count()
+    }
+
+    /// Покрытие из JSON-отчёта профилировщика (поля `coverage_start` и `coverage`)
+    pub fn from_report(json: &str) -> Result<Self, String> {
+        let field = |name: &str| {
+            let key = format!("\"{}\":", name);
+            let start = json.find(&key).ok_or_else(|| format!("report has no '{}' field", name))? + key.len();
+            let value = json[start..].trim_start();
+            let end = value.find([',', '\n', '}']).unwrap_or(value.len());
+            Ok::<&str, String>(value[..end].trim())
+        };
+        let start = field("coverage_start")?;
+        let start: u16 = start.parse().map_err(|_| format!("bad coverage start '{}'", start))?;
+        let map = field("coverage")?.trim_matches('"');
+        let bytes = map
+            .chars()
+            .map(|symbol| ByteUse::from_symbol(symbol).ok_or_else(|| format!("bad coverage symbol '{}'", symbol)))
+            .collect::<Result<Vec<ByteUse>, String>>()?;
+        Ok(Coverage { start, bytes })
+    }
+}
+
+/// Счётчики выполнения; процессор вызывает его после каждой инструкции
+#[derive(Debug, Clone)]
+pub struct Profiler {
+    // Выполнений инструкции по каждому адресу
+    counts: Vec<u64>,
+    // По группам, в порядке OpcodeClass::all()
+    classes: [u64; 10],
+    // Байт выполнялся как часть инструкции
+    code: Vec<bool>,
+    // Байт читался инструкцией как данные (спрайт, FX65, 5XY3, F002)
+    data: Vec<bool>,
+    // Подпрограммы по адресу входа; основная программа - PROGRAM_START
+    functions: BTreeMap<u16, FunctionStats>,
+    // Вызовы: (откуда, куда) -> сколько раз
+    edges: BTreeMap<(u16, u16), u64>,
+    // Теневой стек: подпрограмма и номер инструкции при входе
+    calls: Vec<(u16, u64)>,
+    instructions: u64,
+}
+
+impl Default for Profiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Profiler {
+            counts: vec![0; XO_MEMORY_SIZE],
+            classes: [0; 10],
+            code: vec![false; XO_MEMORY_SIZE],
+            data: vec![false; XO_MEMORY_SIZE],
+            functions: BTreeMap::new(),
+            edges: BTreeMap::new(),
+            calls: Vec::new(),
+            instructions: 0,
+        }
+    }
+
+    /// Выполнена инструкция `opcode` по адресу `pc`
+    pub fn instruction(&mut self, pc: u16, opcode: u16) {
+        self.instructions += 1;
+        self.counts[pc as usize] += 1;
+        let class = OpcodeClass::of(opcode);
+        self.classes[OpcodeClass::all().iter().position(|&c| c == class).unwrap_or(0)] += 1;
+        // F000 NNNN занимает четыре байта
+        let len = if opcode == 0xF000 { 4 } else { 2 };
+        let end = (pc as usize + len).min(self.code.len());
+        for executed in &mut self.code[pc as usize..end] {
+            *executed = true;
+        }
+
+        let current = self.current();
+        self.functions.entry(current).or_default().exclusive += 1;
+        if opcode & 0xF000 == 0x2000 {
+            let target = opcode & 0x0FFF;
+            self.functions.entry(target).or_default().calls += 1;
+            *self.edges.entry((current, target)).or_default() += 1;
+            self.calls.push((target, self.instructions));
+        } else if opcode == 0x00EE
+            && let Some((function, entry)) = self.calls.pop()
+        {
+            self.functions.entry(function).or_default().inclusive += self.instructions - entry;
+        }
+    }
+
+    /// Закрыть незавершённые вызовы (при сбросе машины стек вызовов пропадает)
+    pub fn end_calls(&mut self) {
+        while let Some((function, entry)) = self.calls.pop() {
+            self.functions.entry(function).or_default().inclusive += self.instructions - entry;
+        }
+    }
+
+    /// Инструкция прочитала память как данные
+    pub fn data_read(&mut self, range: Range<usize>) {
+        let end = range.end.min(self.data.len());
+        for read in &mut self.data[range.start.min(end)..end] {
+            *read = true;
+        }
+    }
+
+    pub fn instructions(&self) -> u64 {
+        self.instructions
+    }
+
+    /// Сколько раз выполнялась инструкция по адресу
+    pub fn count(&self, addr: u16) -> u64 {
+        self.counts[addr as usize]
+    }
+
+    pub fn class_count(&self, class: OpcodeClass) -> u64 {
+        OpcodeClass::all().iter().position(|&c| c == class).map_or(0, |i| self.classes[i])
+    }
+
+    /// Время подпрограмм, включая ещё не вернувшиеся (их время идёт до текущего момента)
+    pub fn functions(&self) -> BTreeMap<u16, FunctionStats> {
+        let mut functions = self.functions.clone();
+        let main = functions.entry(PROGRAM_START as u16).or_default();
+        main.calls = main.calls.max(1);
+        main.inclusive = self.instructions;
+        for &(function, entry) in &self.calls {
+            functions.entry(function).or_default().inclusive += self.instructions - entry;
+        }
+        functions
+    }
+
+    /// Рёбра графа вызовов: (откуда, куда) -> число вызовов
+    pub fn edges(&self) -> &BTreeMap<(u16, u16), u64> {
+        &self.edges
+    }
+
+    /// Покрытие `len` байт с адреса `start`
+    pub fn coverage(&self, start: u16, len: usize) -> Coverage {
+        let end = (start as usize + len).min(self.code.len());
+        let bytes = (start as usize..end)
+            .map(|addr| match (self.code[addr], self.data[addr]) {
+                (false, false) => ByteUse::Untouched,
+                (true, false) => ByteUse::Code,
+                (false, true) => ByteUse::Data,
+                (true, true) => ByteUse::Both,
+            })
+            .collect();
+        Coverage { start, bytes }
+    }
+
+    /// Покрытие загруженного ROM
+    pub fn rom_coverage(&self, chip8: &Chip8) -> Coverage {
+        self.coverage(PROGRAM_START as u16, chip8.rom().len())
+    }
+
+    /// Инструкции, которые статический анализ считает кодом, но которые
+    /// ни разу не выполнялись, - диапазонами адресов
+    pub fn unreached(&self, chip8: &Chip8) -> Vec<(u16, u16)> {
+        let listing = disasm::disassemble(chip8.rom(), chip8.cpu().variant);
+        let mut ranges: Vec<(u16, u16)> = Vec::new();
+        for addr in listing.code_addresses().filter(|&addr| !self.code[addr as usize]) {
+            match ranges.last_mut() {
+                Some((_, end)) if *end + 2 == addr => *end = addr,
+                _ => ranges.push((addr, addr)),
+            }
+        }
+        ranges
+    }
+
+    /// Отчёт таблицами; `symbols` - имена адресов из файла .sym
+    pub fn text_report(&self, chip8: &Chip8, symbols: &BTreeMap<u16, String>) -> String {
+        let total = self.instructions.max(1) as f64;
+        let percent = |count: u64| count as f64 * 100.0 / total;
+        let name = |addr: u16| symbols.get(&addr).cloned().unwrap_or_else(|| function_name(addr));
+        let mut out = String::new();
+        let _ = writeln!(out, "Profile: {} instructions", self.instructions);
+
+        let _ = writeln!(out, "\nOpcode classes:");
+        let _ = writeln!(out, "  {:<8} {:>12} {:>6}", "class", "count", "%");
+        for class in OpcodeClass::all() {
+            let count = self.class_count(class);
+            if count > 0 {
+                let _ = writeln!(out, "  {:<8} {:>12} {:>6.1}", class.name(), count, percent(count));
+            }
+        }
+
+        let _ = writeln!(out, "\nHottest addresses:");
+        let _ = writeln!(out, "  {:<4} {:>12} {:>6}  instruction", "addr", "count", "%");
+        let mut hot: Vec<(u16, u64)> =
+            (0..self.counts.len()).filter(|&addr| self.counts[addr] > 0).map(|addr| (addr as u16, self.counts[addr])).collect();
+        hot.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        for &(addr, count) in hot.iter().take(HOT_ADDRESSES) {
+            let opcode = match chip8.read_memory(addr as usize, 2) {
+                [high, low] => u16::from_be_bytes([*high, *low]),
+                _ => 0,
+            };
+            let label = symbols.get(&addr).map(|name| format!("  <{}>", name)).unwrap_or_default();
+            let _ = writeln!(out, "  {:04X} {:>12} {:>6.1}  {}{}", addr, count, percent(count), mnemonic(opcode), label);
+        }
+
+        let _ = writeln!(out, "\nSubroutines (time in instructions):");
+        let _ = writeln!(out, "  {:<4} {:<16} {:>8} {:>12} {:>6} {:>12} {:>6}", "addr", "name", "calls", "total", "%", "self", "%");
+        let mut functions: Vec<(u16, FunctionStats)> = self.functions().into_iter().collect();
+        functions.sort_by(|a, b| b.1.inclusive.cmp(&a.1.inclusive).then(a.0.cmp(&b.0)));
+        for (addr, stats) in functions {
+            let _ = writeln!(
+                out,
+                "  {:04X} {:<16} {:>8} {:>12} {:>6.1} {:>12} {:>6.1}",
+                addr,
+                name(addr),
+                stats.calls,
+                stats.inclusive,
+                percent(stats.inclusive),
+                stats.exclusive,
+                percent(stats.exclusive)
+            );
+        }
+        if !self.edges.is_empty() {
+            let _ = writeln!(out, "\nCalls:");
+            for (&(from, to), calls) in &self.edges {
+                let _ = writeln!(out, "  {:04X} {} -> {:04X} {}: {}", from, name(from), to, name(to), calls);
+            }
+        }
+
+        let coverage = self.rom_coverage(chip8);
+        let len = coverage.bytes.len().max(1) as f64;
+        let share = |kind: ByteUse| {
+            let count = coverage.count(kind);
+            format!("{} ({:.1}%)", count, count as f64 * 100.0 / len)
+        };
+        let _ = writeln!(
+            out,
+            "\nROM coverage, {} bytes: code {}, data {}, both {}, untouched {}",
+            coverage.bytes.len(),
+            share(ByteUse::Code),
+            share(ByteUse::Data),
+            share(ByteUse::Both),
+            share(ByteUse::Untouched)
+        );
+        let unreached = self.unreached(chip8);
+        if !unreached.is_empty() {
+            let ranges: Vec<String> = unreached
+                .iter()
+                .map(|&(start, end)| if start == end { format!("{:04X}", start) } else { format!("{:04X}-{:04X}", start, end) })
+                .collect();
+            let _ = writeln!(out, "Never executed code: {}", ranges.join(", "));
+        }
+        out
+    }
+
+    /// Отчёт в JSON: адреса и счётчики числами, карта покрытия ROM строкой
+    pub fn json_report(&self, chip8: &Chip8, symbols: &BTreeMap<u16, String>) -> String {
+        let name = |addr: u16| symbols.get(&addr).cloned().unwrap_or_else(|| function_name(addr));
+        let classes: Vec<String> = OpcodeClass::all()
+            .iter()
+            .map(|class| format!("\"{}\": {}", class.name(), self.class_count(*class)))
+            .collect();
+        let addresses: Vec<String> = (0..self.counts.len())
+            .filter(|&addr| self.counts[addr] > 0)
+            .map(|addr| format!("    {{\"addr\": {}, \"count\": {}}}", addr, self.counts[addr]))
+            .collect();
+        let functions: Vec<String> = self
+            .functions()
+            .into_iter()
+            .map(|(addr, stats)| {
+                format!(
+                    "    {{\"addr\": {}, \"name\": \"{}\", \"calls\": {}, \"inclusive\": {}, \"exclusive\": {}}}",
+                    addr,
+                    escape(&name(addr)),
+                    stats.calls,
+                    stats.inclusive,
+                    stats.exclusive
+                )
+            })
+            .collect();
+        let edges: Vec<String> = self
+            .edges
+            .iter()
+            .map(|(&(from, to), calls)| format!("    {{\"from\": {}, \"to\": {}, \"calls\": {}}}", from, to, calls))
+            .collect();
+        let unreached: Vec<String> =
+            self.unreached(chip8).iter().map(|(start, end)| format!("[{}, {}]", start, end)).collect();
+        let coverage = self.rom_coverage(chip8);
+
+        let mut out = String::new();
+        out.push_str("{\n");
+        let _ = writeln!(out, "  \"instructions\": {},", self.instructions);
+        let _ = writeln!(out, "  \"classes\": {{{}}},", classes.join(", "));
+        let _ = writeln!(out, "  \"addresses\": [\n{}\n  ],", addresses.join(",\n"));
+        let _ = writeln!(out, "  \"functions\": [\n{}\n  ],", functions.join(",\n"));
+        let _ = writeln!(out, "  \"calls\": [\n{}\n  ],", edges.join(",\n"));
+        let _ = writeln!(out, "  \"unreached\": [{}],", unreached.join(", "));
+        let _ = writeln!(out, "  \"coverage_start\": {},", coverage.start);
+        let _ = writeln!(out, "  \"coverage\": \"{}\"", coverage.map());
+        out.push_str("}\n");
+        out
+    }
+
+    /// Текущая подпрограмма: вершина теневого стека или основная программа
+    fn current(&self) -> u16 {
+        self.calls.last().map_or(PROGRAM_START as u16, |&(function, _)| function)
+    }
+}
+
+/// Имя подпрограммы без файла символов
+fn function_name(addr: u16) -> String {
+    if addr as usize == PROGRAM_START { "main".to_string() } else { format!("sub_{:03X}", addr) }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Файл символов ассемблера: строки `ADDR имя` (ADDR в hex)
+pub fn parse_symbols(text: &str) -> Result<BTreeMap<u16, String>, String> {
+    let mut symbols = BTreeMap::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let (addr, name) = line
+            .split_once(char::is_whitespace)
+            .ok_or_else(|| format!("line {}: expected 'ADDR name'", number + 1))?;
+        let addr = u16::from_str_radix(addr, 16).map_err(|_| format!("line {}: bad address '{}'", number + 1, addr))?;
+        // Первое имя адреса - обычно метка подпрограммы, а не вложенная
+        symbols.entry(addr).or_insert_with(|| name.trim().to_string());
+    }
+    Ok(symbols)
+}
+
+/// Символы для отчёта: файл, заданный явно, или `.sym` рядом с ROM, если он есть
+pub fn load_symbols(explicit: Option<&str>, rom: &Path) -> Result<BTreeMap<u16, String>, String> {
+    let path = match explicit {
+        Some(path) => Path::new(path).to_path_buf(),
+        None => rom.with_extension("sym"),
+    };
+    if explicit.is_none() && !path.exists() {
+        return Ok(BTreeMap::new());
+    }
+    let text = fs::read_to_string(&path).map_err(|e| format!("failed to read '{}': {}", path.display(), e))?;
+    parse_symbols(&text).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profiled(rom: &[u8], instructions: usize) -> Chip8 {
+        let mut chip8 = Chip8::default();
+        chip8.load(rom).unwrap();
+        chip8.set_profiler(Some(Profiler::new()));
+        chip8.step(instructions).unwrap();
+        chip8
+    }
+
+    // 200: CALL 208; 202: JP 200; 204: (не выполняется) CLS; 206: данные
+    // 208: I = 206; 20A: DRW V0, V0, 2; 20C: RET
+    const CALLS: &[u8] = &[0x22, 0x08, 0x12, 0x00, 0x00, 0xE0, 0xF0, 0x90, 0xA2, 0x06, 0xD0, 0x02, 0x00, 0xEE];
+
+    #[test]
+    fn counts_addresses_and_classes() {
+        let chip8 = profiled(CALLS, 10);
+        let profiler = chip8.profiler().unwrap();
+        assert_eq!(profiler.instructions(), 10);
+        assert_eq!(profiler.count(0x200), 2);
+        assert_eq!(profiler.count(0x20C), 2);
+        assert_eq!(profiler.class_count(OpcodeClass::Call), 2);
+        assert_eq!(profiler.class_count(OpcodeClass::Draw), 2);
+    }
+
+    #[test]
+    fn call_graph_time() {
+        let chip8 = profiled(CALLS, 12);
+        let functions = chip8.profiler().unwrap().functions();
+        // Два полных вызова по 3 инструкции (с RET), третий только начался
+        let sub = functions[&0x208];
+        assert_eq!(sub.calls, 3);
+        assert_eq!(sub.inclusive, 3 + 3 + 1);
+        assert_eq!(sub.exclusive, 3 + 3 + 1);
+        assert_eq!(functions[&0x200].exclusive, 5);
+        assert_eq!(functions[&0x200].inclusive, 12);
+        assert_eq!(chip8.profiler().unwrap().edges()[&(0x200, 0x208)], 3);
+    }
+
+    #[test]
+    fn coverage_separates_code_and_data() {
+        let chip8 = profiled(CALLS, 10);
+        let profiler = chip8.profiler().unwrap();
+        let coverage = profiler.rom_coverage(&chip8);
+        assert_eq!(coverage.map(), "xxxx..ddxxxxxx");
+        assert!(coverage.is_executed(0x202));
+        assert!(!coverage.is_executed(0x204));
+        assert_eq!(profiler.unreached(&chip8), []);
+
+        let report = profiler.json_report(&chip8, &BTreeMap::new());
+        assert_eq!(Coverage::from_report(&report), Ok(coverage));
+        assert!(report.contains("\"name\": \"sub_208\""));
+
+        let symbols = parse_symbols("0200 start\n0208 draw\n0208 draw_loop\n").unwrap();
+        let text = profiler.text_report(&chip8, &symbols);
+        assert!(text.contains("Profile: 10 instructions"));
+        assert!(text.contains("0200 start -> 0208 draw: 2"), "{}", text);
+        assert!(text.contains("code 10 (71.4%), data 2 (14.3%)"), "{}", text);
+    }
+
+    #[test]
+    fn reports_unreached_code() {
+        // 200: SNE V0, 0; 202: JP 206; 204: CLS; 206: JP 206 - пропуск не сработал, CLS не выполнялась
+        let chip8 = profiled(&[0x40, 0x00, 0x12, 0x06, 0x00, 0xE0, 0x12, 0x06], 3);
+        let profiler = chip8.profiler().unwrap();
+        assert_eq!(profiler.unreached(&chip8), [(0x204, 0x204)]);
+        assert!(profiler.text_report(&chip8, &BTreeMap::new()).contains("Never executed code: 0204\n"));
+    }
+}
@@ -0,0 +1,215 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// Что происходит с I после FX55/FX65
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IndexIncrement {
+    /// I не меняется (SUPER-CHIP)
+    Unchanged,
+    /// I += X (CHIP-48)
+    ByX,
+    /// I += X + 1 (COSMAC VIP, XO-CHIP)
+    ByXPlusOne,
+}
+
+/// Поведение, в котором расходятся разные реализации CHIP-8
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quirks {
+    /// 8XY6/8XYE сдвигают VY и кладут результат в VX (иначе сдвигается VX)
+    pub shift_uses_vy: bool,
+    /// Изменение I после FX55/FX65
+    pub index_increment: IndexIncrement,
+    /// BXNN прыгает на VX + XNN вместо V0 + NNN
+    pub jump_uses_vx: bool,
+    /// 8XY1/8XY2/8XY3 обнуляют VF
+    pub vf_reset: bool,
+    /// Спрайты обрезаются на краю экрана вместо заворачивания
+    pub clip_sprites: bool,
+    /// DXYN ждёт начала следующего кадра
+    pub display_wait: bool,
+    /// FX0A завершается, когда нажатую клавишу отпустят (иначе сразу по нажатию)
+    pub key_wait_release: bool,
+}
+
+impl Quirks {
+    /// Оригинальный интерпретатор COSMAC VIP
+    pub fn vip() -> Self {
+        Quirks {
+            shift_uses_vy: true,
+            index_increment: IndexIncrement::ByXPlusOne,
+            jump_uses_vx: false,
+            vf_reset: true,
+            clip_sprites: true,
+            display_wait: true,
+            key_wait_release: true,
+        }
+    }
+
+    /// CHIP-48 для HP-48
+    pub fn chip48() -> Self {
+        Quirks {
+            shift_uses_vy: false,
+            index_increment: IndexIncrement::ByX,
+            jump_uses_vx: true,
+            vf_reset: false,
+            clip_sprites: true,
+            display_wait: false,
+            key_wait_release: false,
+        }
+    }
+
+    /// SUPER-CHIP 1.1
+    pub fn schip() -> Self {
+        Quirks {
+            shift_uses_vy: false,
+            index_increment: IndexIncrement::Unchanged,
+            jump_uses_vx: true,
+            vf_reset: false,
+            clip_sprites: true,
+            display_wait: false,
+            key_wait_release: false,
+        }
+    }
+
+    /// XO-CHIP (Octo)
+    pub fn xochip() -> Self {
+        Quirks {
+            shift_uses_vy: true,
+            index_increment: IndexIncrement::ByXPlusOne,
+            jump_uses_vx: false,
+            vf_reset: false,
+            clip_sprites: false,
+            display_wait: false,
+            key_wait_release: true,
+        }
+    }
+}
+
+impl Quirks {
+    /// Размер двоичного представления
+    pub const ENCODED_LEN: usize = 7;
+
+    /// Двоичное представление для сохранений и записей ввода
+    pub fn to_bytes(&self) -> [u8; Self::ENCODED_LEN] {
+        [
+            self.shift_uses_vy as u8,
+            match self.index_increment {
+                IndexIncrement::Unchanged => 0,
+                IndexIncrement::ByX => 1,
+                IndexIncrement::ByXPlusOne => 2,
+            },
+            self.jump_uses_vx as u8,
+            self.vf_reset as u8,
+            self.clip_sprites as u8,
+            self.display_wait as u8,
+            self.key_wait_release as u8,
+        ]
+    }
+
+    pub fn from_bytes(bytes: [u8; Self::ENCODED_LEN]) -> Result<Self, &'static str> {
+        let flag = |byte: u8| match byte {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err("bad boolean"),
+        };
+        Ok(Quirks {
+            shift_uses_vy: flag(bytes[0])?,
+            index_increment: match bytes[1] {
+                0 => IndexIncrement::Unchanged,
+                1 => IndexIncrement::ByX,
+                2 => IndexIncrement::ByXPlusOne,
+                _ => return Err("unknown index increment quirk"),
+            },
+            jump_uses_vx: flag(bytes[2])?,
+            vf_reset: flag(bytes[3])?,
+            clip_sprites: flag(bytes[4])?,
+            display_wait: flag(bytes[5])?,
+            key_wait_release: flag(bytes[6])?,
+        })
+    }
+}
+
+impl Default for Quirks {
+    /// Поведение эмулятора до появления профилей: сдвиг VX, I не меняется,
+    /// BNNN через V0, спрайты заворачиваются. Исключение - FX0A: раньше его
+    /// сразу удовлетворяла уже зажатая клавиша, теперь по умолчанию он
+    /// намеренно ждёт нового нажатия и отпускания
+    fn default() -> Self {
+        Quirks {
+            shift_uses_vy: false,
+            index_increment: IndexIncrement::Unchanged,
+            jump_uses_vx: false,
+            vf_reset: false,
+            clip_sprites: false,
+            display_wait: false,
+            key_wait_release: true,
+        }
+    }
+}
+
+/// Именованные профили для командной строки
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Preset {
+    Vip,
+    Chip48,
+    Schip,
+    XoChip,
+}
+
+impl Preset {
+    pub fn all() -> [Preset; 4] {
+        [Preset::Vip, Preset::Chip48, Preset::Schip, Preset::XoChip]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Preset::Vip => "vip",
+            Preset::Chip48 => "chip48",
+            Preset::Schip => "schip",
+            Preset::XoChip => "xochip",
+        }
+    }
+
+    pub fn quirks(&self) -> Quirks {
+        match self {
+            Preset::Vip => Quirks::vip(),
+            Preset::Chip48 => Quirks::chip48(),
+            Preset::Schip => Quirks::schip(),
+            Preset::XoChip => Quirks::xochip(),
+        }
+    }
+}
+
+impl fmt::Display for Preset {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Preset {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Preset::all()
+            .into_iter()
+            .find(|preset| preset.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| {
+                let names: Vec<_> = Preset::all().iter().map(|p| p.name()).collect();
+                format!("unknown quirks preset '{}', expected one of: {}", s, names.join(", "))
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn presets_parse_by_name() {
+        for preset in Preset::all() {
+            assert_eq!(preset.name().parse::<Preset>(), Ok(preset));
+        }
+        assert_eq!("VIP".parse::<Preset>().map(|p| p.quirks()), Ok(Quirks::vip()));
+        assert!("cosmac".parse::<Preset>().is_err());
+    }
+}
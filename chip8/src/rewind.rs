@@ -0,0 +1,208 @@
+use std::collections::VecDeque;
+
+use crate::savestate::SaveState;
+use crate::scheduler::FRAME_RATE;
+
+/// Соседние отличия, разделённые меньшим числом совпадающих байт, склеиваются
+const MERGE_GAP: usize = 8;
+
+/// Участок байт, который нужно записать по смещению
+#[derive(Debug, Clone)]
+struct Patch {
+    offset: usize,
+    bytes: Vec<u8>,
+}
+
+/// Разница между двумя кадрами
+#[derive(Debug, Clone)]
+enum Delta {
+    Patches(Vec<Patch>),
+    // Размер снимка поменялся (другой вариант машины) - храним целиком
+    Full(Vec<u8>),
+}
+
+impl Delta {
+    /// Разница, превращающая `from` в `to`
+    fn between(from: &[u8], to: &[u8]) -> Self {
+        if from.len() != to.len() {
+            return Delta::Full(to.to_vec());
+        }
+
+        let mut patches: Vec<Patch> = Vec::new();
+        let mut i = 0;
+        while i < to.len() {
+            if from[i] == to[i] {
+                i += 1;
+                continue;
+            }
+            let start = i;
+            let mut end = i + 1;
+            // Идём дальше, пока отличия встречаются чаще, чем раз в MERGE_GAP байт
+            while end < to.len() && (end..(end + MERGE_GAP).min(to.len())).any(|j| from[j] != to[j]) {
+                end += 1;
+            }
+            patches.push(Patch { offset: start, bytes: to[start..end].to_vec() });
+            i = end;
+        }
+        Delta::Patches(patches)
+    }
+
+    fn apply(&self, data: &mut Vec<u8>) {
+        match self {
+            Delta::Patches(patches) => {
+                for patch in patches {
+                    data[patch.offset..patch.offset + patch.bytes.len()].copy_from_slice(&patch.bytes);
+                }
+            }
+            Delta::Full(bytes) => data.clone_from(bytes),
+        }
+    }
+
+    fn size(&self) -> usize {
+        match self {
+            Delta::Patches(patches) => patches
+                .iter()
+                .map(|p| p.bytes.len() + std::mem::size_of::<Patch>())
+                .sum(),
+            Delta::Full(bytes) => bytes.len(),
+        }
+    }
+}
+
+/// Кольцевой буфер покадровых снимков для перемотки назад.
+/// Хранится только последний кадр целиком, более старые - как разница
+/// с кадром, следующим за ними.
+#[derive(Debug, Clone)]
+pub struct Rewind {
+    // Сколько кадров помещается в буфер
+    capacity: usize,
+    // Последний кадр в двоичном виде
+    current: Option<Vec<u8>>,
+    // Разницы назад: последняя превращает `current` в предыдущий кадр
+    deltas: VecDeque<Delta>,
+}
+
+impl Rewind {
+    /// Буфер на `seconds` секунд игры
+    pub fn new(seconds: u32) -> Self {
+        Rewind {
+            capacity: (seconds as usize * FRAME_RATE as usize).max(1),
+            current: None,
+            deltas: VecDeque::new(),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Сколько кадров сейчас в буфере
+    pub fn len(&self) -> usize {
+        self.deltas.len() + self.current.is_some() as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.current.is_none()
+    }
+
+    pub fn clear(&mut self) {
+        self.current = None;
+        self.deltas.clear();
+    }
+
+    /// Примерный объём памяти под снимки, в байтах
+    pub fn memory_usage(&self) -> usize {
+        self.current.as_ref().map_or(0, Vec::len) + self.deltas.iter().map(Delta::size).sum::<usize>()
+    }
+
+    /// Запомнить новый кадр; самый старый вытесняется при переполнении
+    pub fn push(&mut self, state: &SaveState) {
+        let bytes = state.to_bytes();
+        if let Some(current) = self.current.take() {
+            self.deltas.push_back(Delta::between(&bytes, &current));
+        }
+        self.current = Some(bytes);
+
+        while self.len() > self.capacity {
+            self.deltas.pop_front();
+        }
+    }
+
+    /// Шаг назад: выбросить последний кадр и вернуть предыдущий.
+    /// `None`, если возвращаться больше некуда.
+    pub fn pop(&mut self) -> Option<SaveState> {
+        let delta = self.deltas.pop_back()?;
+        let current = self.current.as_mut()?;
+        delta.apply(current);
+        let state = SaveState::from_bytes(current).expect("rewind snapshots are always valid");
+        Some(state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::CPU;
+
+    fn state_with(value: u8) -> SaveState {
+        let mut cpu = CPU::new();
+        cpu.registers[0] = value;
+        cpu.memory[0x300] = value;
+        SaveState::capture(&cpu, 0)
+    }
+
+    #[test]
+    fn pops_frames_in_reverse_order() {
+        let mut rewind = Rewind::new(1);
+        for value in 1..=3 {
+            rewind.push(&state_with(value));
+        }
+        assert_eq!(rewind.len(), 3);
+
+        assert_eq!(rewind.pop().unwrap().registers[0], 2);
+        assert_eq!(rewind.pop().unwrap().registers[0], 1);
+        assert!(rewind.pop().is_none());
+        assert_eq!(rewind.len(), 1);
+    }
+
+    #[test]
+    fn oldest_frames_are_evicted() {
+        let mut rewind = Rewind::new(1);
+        for value in 0..100 {
+            rewind.push(&state_with(value));
+        }
+        assert_eq!(rewind.len(), 60);
+
+        let mut last = None;
+        while let Some(state) = rewind.pop() {
+            last = Some(state.registers[0]);
+        }
+        assert_eq!(last, Some(40));
+    }
+
+    #[test]
+    fn deltas_are_much_smaller_than_snapshots() {
+        let mut rewind = Rewind::new(1);
+        let full = state_with(0).to_bytes().len();
+        for value in 0..60 {
+            rewind.push(&state_with(value));
+        }
+        assert!(rewind.memory_usage() < full * 2);
+    }
+
+    #[test]
+    fn delta_round_trip() {
+        let from = vec![0u8; 64];
+        let mut to = from.clone();
+        to[3] = 1;
+        to[5] = 2;
+        to[40] = 3;
+        let mut data = from.clone();
+        Delta::between(&from, &to).apply(&mut data);
+        assert_eq!(data, to);
+
+        let mut data = from.clone();
+        Delta::between(&from, &[9; 4]).apply(&mut data);
+        assert_eq!(data, [9; 4]);
+    }
+}
@@ -0,0 +1,175 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// Источник случайных байт для CXKK
+pub trait RandomSource {
+    fn next_byte(&mut self) -> u8;
+
+    /// Начальное значение, если последовательность по нему воспроизводима
+    fn seed(&self) -> Option<u64> {
+        None
+    }
+}
+
+/// Системный генератор `rand`: каждый запуск разный
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ThreadRng;
+
+impl RandomSource for ThreadRng {
+    fn next_byte(&mut self) -> u8 {
+        rand::random()
+    }
+}
+
+/// Детерминированный генератор SplitMix64: одинаковый seed - одинаковая
+/// последовательность на любой платформе и версии зависимостей
+#[derive(Debug, Clone)]
+pub struct SeededRng {
+    seed: u64,
+    state: u64,
+}
+
+impl SeededRng {
+    pub fn new(seed: u64) -> Self {
+        SeededRng { seed, state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+}
+
+impl RandomSource for SeededRng {
+    fn next_byte(&mut self) -> u8 {
+        (self.next_u64() >> 56) as u8
+    }
+
+    fn seed(&self) -> Option<u64> {
+        Some(self.seed)
+    }
+}
+
+/// Заданная последовательность байт по кругу - для тестов
+#[derive(Debug, Clone)]
+pub struct ScriptedRng {
+    values: Vec<u8>,
+    position: usize,
+}
+
+impl ScriptedRng {
+    /// Пустая последовательность всегда даёт 0
+    pub fn new(values: Vec<u8>) -> Self {
+        ScriptedRng { values, position: 0 }
+    }
+}
+
+impl RandomSource for ScriptedRng {
+    fn next_byte(&mut self) -> u8 {
+        let Some(&value) = self.values.get(self.position) else {
+            return 0;
+        };
+        self.position = (self.position + 1) % self.values.len();
+        value
+    }
+}
+
+/// Выбор источника в командной строке: `thread`, `seed:N`, `script:AA,BB,...`
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum RngConfig {
+    #[default]
+    Thread,
+    Seeded(u64),
+    // Байты в hex
+    Scripted(Vec<u8>),
+}
+
+impl RngConfig {
+    pub fn build(&self) -> Box<dyn RandomSource> {
+        match self {
+            RngConfig::Thread => Box::new(ThreadRng),
+            RngConfig::Seeded(seed) => Box::new(SeededRng::new(*seed)),
+            RngConfig::Scripted(values) => Box::new(ScriptedRng::new(values.clone())),
+        }
+    }
+}
+
+impl fmt::Display for RngConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RngConfig::Thread => f.write_str("thread"),
+            RngConfig::Seeded(seed) => write!(f, "seed:{}", seed),
+            RngConfig::Scripted(values) => {
+                let bytes: Vec<String> = values.iter().map(|b| format!("{:02X}", b)).collect();
+                write!(f, "script:{}", bytes.join(","))
+            }
+        }
+    }
+}
+
+impl FromStr for RngConfig {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, arg) = s.split_once(':').unwrap_or((s, ""));
+        match kind.to_ascii_lowercase().as_str() {
+            "thread" if arg.is_empty() => Ok(RngConfig::Thread),
+            "seed" => {
+                let seed = match arg.strip_prefix("0x").or_else(|| arg.strip_prefix("0X")) {
+                    Some(hex) => u64::from_str_radix(hex, 16),
+                    None => arg.parse(),
+                };
+                seed.map(RngConfig::Seeded).map_err(|_| format!("bad seed '{}'", arg))
+            }
+            "script" => arg
+                .split(',')
+                .map(|byte| u8::from_str_radix(byte.trim(), 16).map_err(|_| format!("bad byte '{}' in script", byte)))
+                .collect::<Result<Vec<u8>, String>>()
+                .map(RngConfig::Scripted),
+            _ => Err(format!("unknown random source '{}', expected thread, seed:N or script:AA,BB,...", s)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seeded_sequence_is_stable() {
+        let mut a = SeededRng::new(7);
+        let mut b = SeededRng::new(7);
+        let first: Vec<u8> = (0..16).map(|_| a.next_byte()).collect();
+        assert_eq!(first, (0..16).map(|_| b.next_byte()).collect::<Vec<_>>());
+        assert_eq!(a.seed(), Some(7));
+
+        // Эталон SplitMix64 для seed 0
+        assert_eq!(SeededRng::new(0).next_u64(), 0xE220_A839_7B1D_CDAF);
+    }
+
+    #[test]
+    fn scripted_sequence_repeats() {
+        let mut rng = ScriptedRng::new(vec![1, 2, 3]);
+        let values: Vec<u8> = (0..5).map(|_| rng.next_byte()).collect();
+        assert_eq!(values, [1, 2, 3, 1, 2]);
+        assert_eq!(ScriptedRng::new(Vec::new()).next_byte(), 0);
+        assert_eq!(rng.seed(), None);
+    }
+
+    #[test]
+    fn parses_config() {
+        assert_eq!("thread".parse(), Ok(RngConfig::Thread));
+        assert_eq!("seed:42".parse(), Ok(RngConfig::Seeded(42)));
+        assert_eq!("SEED:0x2A".parse(), Ok(RngConfig::Seeded(42)));
+        assert_eq!("script:00,ff, 7".parse(), Ok(RngConfig::Scripted(vec![0, 0xFF, 7])));
+        assert!("seed:x".parse::<RngConfig>().is_err());
+        assert!("script:100".parse::<RngConfig>().is_err());
+        assert!("dice".parse::<RngConfig>().is_err());
+
+        let config = RngConfig::Scripted(vec![0xAB, 1]);
+        assert_eq!(config.to_string().parse(), Ok(config));
+    }
+}
@@ -0,0 +1,354 @@
+//! Общий цикл фронтендов: горячие клавиши, перемотка, ход по часам,
+//! ускорение и реакция на сбои. Фронтенд только собирает [`Input`] и рисует кадр.
+
+use std::mem;
+
+use crate::fault::{Chip8Fault, FaultPolicy};
+use crate::keymap::{HostKey, Hotkey, Keymap};
+use crate::savestate::SaveState;
+use crate::scheduler::Scheduler;
+use crate::Chip8;
+
+/// Во сколько раз быстрее идёт игра, пока зажата клавиша speed_up
+pub const FAST_FORWARD: u64 = 4;
+
+/// Ввод за один проход цикла
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Input {
+    // Зажатые клавиши, включая модификаторы
+    pub held: Vec<HostKey>,
+    // Нажатые с прошлого прохода
+    pub pressed: Vec<HostKey>,
+}
+
+/// Состояние цикла эмуляции между проходами
+pub struct Runner {
+    pub scheduler: Scheduler,
+    pub keymap: Keymap,
+    pub on_fault: FaultPolicy,
+    // Путь ROM: слоты сохранений лежат рядом, <rom>.stateN
+    pub rom_path: String,
+    pub paused: bool,
+    // Прошлый проход шёл с ускорением
+    pub fast_forward: bool,
+    // Воспроизведение записи ещё шло в прошлом проходе
+    replaying: bool,
+    // Сообщения для пользователя, фронтенд забирает их через take_messages
+    messages: Vec<String>,
+    // Действия, которые выполняет сам фронтенд (снимки, запись GIF)
+    frontend_hotkeys: Vec<Hotkey>,
+}
+
+impl Runner {
+    pub fn new(chip8: &Chip8, scheduler: Scheduler, keymap: Keymap, rom_path: &str) -> Self {
+        Runner {
+            scheduler,
+            keymap,
+            on_fault: FaultPolicy::default(),
+            rom_path: rom_path.to_string(),
+            paused: false,
+            fast_forward: false,
+            replaying: chip8.is_replaying(),
+            messages: Vec::new(),
+            frontend_hotkeys: Vec::new(),
+        }
+    }
+
+    pub fn with_fault_policy(mut self, on_fault: FaultPolicy) -> Self {
+        self.on_fault = on_fault;
+        self
+    }
+
+    /// Весь проход для фронтендов без отладчика. false - пора выходить
+    pub fn tick(&mut self, chip8: &mut Chip8, input: &Input) -> bool {
+        if !self.handle_hotkeys(chip8, input) {
+            return false;
+        }
+        if self.rewind(chip8, input) {
+            return true;
+        }
+        self.run(chip8, input)
+    }
+
+    /// Горячие клавиши, нажатые за проход. false - нажат выход
+    pub fn handle_hotkeys(&mut self, chip8: &mut Chip8, input: &Input) -> bool {
+        if self.replaying && !chip8.is_replaying() {
+            self.messages.push("Replay finished, keyboard is live again".to_string());
+            self.replaying = false;
+        }
+
+        for hotkey in self.keymap.hotkeys_pressed(&input.pressed, &input.held) {
+            match hotkey {
+                Hotkey::Quit => return false,
+                Hotkey::Pause => {
+                    self.paused = !self.paused;
+                    self.messages.push(if self.paused { "Paused" } else { "Resumed" }.to_string());
+                }
+                // Сброс и загрузка снимают паузу, в том числе после сбоя
+                Hotkey::Reset if movie_active(chip8) => {
+                    self.messages.push("Cannot reset while a movie is recorded or replayed".to_string());
+                }
+                Hotkey::Reset => {
+                    chip8.reset();
+                    self.paused = false;
+                    self.messages.push("ROM restarted".to_string());
+                }
+                Hotkey::SaveState(slot) => self.save_slot(chip8, slot),
+                Hotkey::LoadState(slot) => {
+                    if self.load_slot(chip8, slot) {
+                        self.paused = false;
+                    }
+                }
+                Hotkey::Screenshot | Hotkey::RecordGif => self.frontend_hotkeys.push(hotkey),
+                // Действуют, пока клавиша зажата
+                Hotkey::Rewind | Hotkey::SpeedUp => {}
+            }
+        }
+        true
+    }
+
+    /// Перемотка по кадру за каждый кадр реального времени, пока зажата
+    /// клавиша. true, если проход ушёл на перемотку (запись ввода при
+    /// перемотке разошлась бы с игрой, поэтому тогда она недоступна)
+    pub fn rewind(&mut self, chip8: &mut Chip8, input: &Input) -> bool {
+        if !self.keymap.is_held(Hotkey::Rewind, &input.held) || movie_active(chip8) {
+            return false;
+        }
+        for _ in 0..self.scheduler.due_frames() {
+            if chip8.rewind_frame() {
+                self.paused = false;
+            }
+        }
+        true
+    }
+
+    /// Передать машине состояние клавиш CHIP-8
+    pub fn apply_keys(&self, chip8: &mut Chip8, input: &Input) {
+        chip8.set_keys(&self.keymap.chip8_keys(&input.held));
+    }
+
+    /// Обычный ход: клавиши и накопившиеся кадры (с ускорением, если зажато).
+    /// false - сбой при политике halt
+    pub fn run(&mut self, chip8: &mut Chip8, input: &Input) -> bool {
+        if self.paused {
+            // Часы идут и на паузе: после неё не нужно догонять
+            self.scheduler.due_frames();
+            return true;
+        }
+        self.apply_keys(chip8, input);
+
+        self.fast_forward = self.keymap.is_held(Hotkey::SpeedUp, &input.held);
+        // Ускорение: лишние кадры сверх реального времени
+        let speed = if self.fast_forward { FAST_FORWARD } else { 1 };
+        for _ in 0..self.scheduler.due_frames() * speed {
+            let Err(fault) = self.run_frame(chip8) else {
+                continue;
+            };
+            self.messages.push(format!("Fault: {}", fault));
+            match self.on_fault {
+                FaultPolicy::Halt => return false,
+                FaultPolicy::Pause => {
+                    self.messages
+                        .push(format!("Emulation paused at {:04X}: load a state, reset or quit", fault.pc()));
+                    self.paused = true;
+                    break;
+                }
+                FaultPolicy::Ignore => unreachable!("ignored faults do not end the frame"),
+            }
+        }
+        true
+    }
+
+    /// Один кадр. При политике ignore сбойная инструкция пропускается, а кадр
+    /// доигрывается до конца, чтобы не потерять тик таймеров и ввод записи
+    fn run_frame(&mut self, chip8: &mut Chip8) -> Result<(), Chip8Fault> {
+        for _ in 0..self.scheduler.instructions_per_frame {
+            if let Err(fault) = chip8.step(1) {
+                if self.on_fault != FaultPolicy::Ignore {
+                    return Err(fault);
+                }
+                self.messages.push(format!("Fault: {}", fault));
+                chip8.skip_instruction();
+            }
+        }
+        chip8.end_frame();
+        Ok(())
+    }
+
+    /// Сообщения с прошлого вызова
+    pub fn take_messages(&mut self) -> Vec<String> {
+        mem::take(&mut self.messages)
+    }
+
+    /// Нажатые с прошлого вызова действия, которые выполняет фронтенд
+    pub fn take_frontend_hotkeys(&mut self) -> Vec<Hotkey> {
+        mem::take(&mut self.frontend_hotkeys)
+    }
+
+    /// Строка состояния: PC, скорость, таймеры и режим
+    pub fn status(&self, chip8: &Chip8) -> String {
+        let cpu = chip8.cpu();
+        let mode = if self.paused {
+            " PAUSED"
+        } else if chip8.is_replaying() {
+            " REPLAY"
+        } else if chip8.is_recording() {
+            " REC"
+        } else if self.fast_forward {
+            " FAST"
+        } else {
+            ""
+        };
+        format!(
+            "PC {:04X}  I {:04X}  speed {}  DT {:3}  ST {:3}{}",
+            cpu.program_counter,
+            cpu.index_register,
+            self.scheduler.instructions_per_frame,
+            cpu.delay_timer,
+            cpu.sound_timer,
+            mode
+        )
+    }
+
+    /// Сохранить машину в слот `<rom>.stateN`
+    fn save_slot(&mut self, chip8: &Chip8, slot: u8) {
+        let path = format!("{}.state{}", self.rom_path, slot);
+        self.messages.push(match chip8.save_state().write_to(&path) {
+            Ok(()) => format!("State saved to slot {} ({})", slot, path),
+            Err(e) => format!("Failed to save slot {}: {}", slot, e),
+        });
+    }
+
+    /// Загрузить слот. Возвращает true, если состояние было загружено
+    fn load_slot(&mut self, chip8: &mut Chip8, slot: u8) -> bool {
+        if movie_active(chip8) {
+            self.messages.push("Cannot load states while a movie is recorded or replayed".to_string());
+            return false;
+        }
+        let path = format!("{}.state{}", self.rom_path, slot);
+        match SaveState::read_from(&path).and_then(|state| chip8.load_state(&state)) {
+            Ok(()) => {
+                self.messages.push(format!("State loaded from slot {}", slot));
+                true
+            }
+            Err(e) => {
+                self.messages.push(format!("Failed to load slot {}: {}", slot, e));
+                false
+            }
+        }
+    }
+}
+
+/// Идёт запись или воспроизведение ввода - состояние машины менять нельзя
+pub fn movie_active(chip8: &Chip8) -> bool {
+    chip8.is_recording() || chip8.is_replaying()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn runner(rom: &[u8]) -> (Runner, Chip8) {
+        let mut chip8 = Chip8::default();
+        chip8.load(rom).unwrap();
+        let runner = Runner::new(&chip8, Scheduler::virtual_time(10), Keymap::default(), "test.ch8");
+        (runner, chip8)
+    }
+
+    fn press(keys: &[HostKey]) -> Input {
+        Input { held: keys.to_vec(), pressed: keys.to_vec() }
+    }
+
+    fn hold(keys: &[HostKey]) -> Input {
+        Input { held: keys.to_vec(), pressed: Vec::new() }
+    }
+
+    // V0 += 1 в цикле
+    const COUNTER: &[u8] = &[0x70, 0x01, 0x12, 0x00];
+
+    #[test]
+    fn pause_and_speed_up() {
+        let (mut runner, mut chip8) = runner(COUNTER);
+        runner.scheduler.advance(Duration::from_millis(50));
+        assert!(runner.tick(&mut chip8, &Input::default()));
+        assert_eq!(chip8.cpu().cycles, 30);
+
+        assert!(runner.tick(&mut chip8, &press(&[HostKey::Char('p')])));
+        runner.scheduler.advance(Duration::from_millis(50));
+        assert!(runner.tick(&mut chip8, &Input::default()));
+        assert_eq!(chip8.cpu().cycles, 30);
+        assert!(runner.status(&chip8).ends_with("PAUSED"));
+
+        assert!(runner.tick(&mut chip8, &press(&[HostKey::Char('p')])));
+        runner.scheduler.advance(Duration::from_millis(50));
+        assert!(runner.tick(&mut chip8, &hold(&[HostKey::Tab])));
+        assert_eq!(chip8.cpu().cycles, 30 + 3 * 10 * FAST_FORWARD);
+        assert_eq!(runner.take_messages(), ["Paused", "Resumed"]);
+    }
+
+    #[test]
+    fn keys_reach_the_machine_and_quit_stops() {
+        // V0 = 5; SKNP V0; EXIT; JP 202
+        let (mut runner, mut chip8) = runner(&[0x60, 0x05, 0xE0, 0xA1, 0x00, 0xFD, 0x12, 0x02]);
+        runner.scheduler.advance(Duration::from_millis(20));
+        assert!(runner.tick(&mut chip8, &hold(&[HostKey::Char('w')])));
+        assert!(!chip8.is_running());
+
+        // Снимок экрана делает фронтенд
+        assert!(runner.tick(&mut chip8, &press(&[HostKey::F(10)])));
+        assert_eq!(runner.take_frontend_hotkeys(), [Hotkey::Screenshot]);
+        assert!(runner.take_frontend_hotkeys().is_empty());
+
+        assert!(!runner.tick(&mut chip8, &press(&[HostKey::Escape])));
+    }
+
+    #[test]
+    fn fault_policy() {
+        // RET с пустым стеком
+        let (runner, mut chip8) = runner(&[0x00, 0xEE, 0x12, 0x02]);
+        let mut runner = runner.with_fault_policy(FaultPolicy::Pause);
+        runner.scheduler.advance(Duration::from_millis(20));
+        assert!(runner.tick(&mut chip8, &Input::default()));
+        assert!(runner.paused);
+
+        // Сброс снимает паузу
+        assert!(runner.tick(&mut chip8, &press(&[HostKey::F(12)])));
+        assert!(!runner.paused);
+
+        runner.on_fault = FaultPolicy::Halt;
+        chip8.reset();
+        runner.scheduler.advance(Duration::from_millis(20));
+        assert!(!runner.tick(&mut chip8, &Input::default()));
+        assert!(runner.take_messages().last().unwrap().starts_with("Fault"));
+    }
+
+    #[test]
+    fn ignored_fault_keeps_the_frame_running() {
+        // RET с пустым стеком; V0 += 1 в цикле
+        let (runner, mut chip8) = runner(&[0x00, 0xEE, 0x70, 0x01, 0x12, 0x02]);
+        let mut runner = runner.with_fault_policy(FaultPolicy::Ignore);
+        chip8.start_recording(10);
+        runner.scheduler.advance(Duration::from_millis(50));
+        assert!(runner.tick(&mut chip8, &Input::default()));
+
+        // Все три кадра прошли целиком: сбой занял одну инструкцию из 30
+        assert_eq!(chip8.frame(), 3);
+        assert_eq!(chip8.cpu().cycles, 29);
+        assert_eq!(chip8.stop_recording().unwrap().keys().count(), 3);
+        assert_eq!(runner.take_messages().len(), 1);
+    }
+
+    #[test]
+    fn rewind_while_held() {
+        let (mut runner, mut chip8) = runner(COUNTER);
+        chip8.set_rewind(1);
+        runner.scheduler.advance(Duration::from_millis(100));
+        runner.tick(&mut chip8, &Input::default());
+        let counter = chip8.cpu().registers[0];
+
+        runner.scheduler.advance(Duration::from_millis(50));
+        runner.tick(&mut chip8, &hold(&[HostKey::Backspace]));
+        assert!(chip8.cpu().registers[0] < counter);
+    }
+}
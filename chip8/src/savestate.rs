@@ -0,0 +1,432 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use thiserror::Error;
+
+use crate::constants::{HIRES_HEIGHT, HIRES_WIDTH};
+use crate::cpu::CPU;
+use crate::quirks::Quirks;
+use crate::variant::Variant;
+
+/// Сигнатура файла сохранения
+pub const MAGIC: &[u8; 4] = b"C8ST";
+
+/// Текущая версия формата; старые версии не читаются
+pub const VERSION: u16 = 3;
+
+/// Ошибка чтения или применения сохранения
+#[derive(Error, Debug)]
+pub enum SaveStateError {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("Not a CHIP-8 save state")]
+    BadMagic,
+
+    #[error("{}", version_message(*.0))]
+    UnsupportedVersion(u16),
+
+    #[error("Save state was made for another ROM (hash {expected:016X}, loaded ROM {found:016X})")]
+    RomMismatch { expected: u64, found: u64 },
+
+    #[error("Save state is truncated")]
+    Truncated,
+
+    #[error("Save state is corrupted: {0}")]
+    Invalid(&'static str),
+}
+
+fn version_message(version: u16) -> String {
+    if version < VERSION {
+        format!(
+            "Save state version {} was made by an older emulator and cannot be loaded \
+             (this build reads version {}); save the game again",
+            version, VERSION
+        )
+    } else {
+        format!("Save state version {} is newer than this build supports (version {})", version, VERSION)
+    }
+}
+
+/// Хеш ROM (FNV-1a, 64 бита) для проверки, что состояние от той же программы
+pub fn rom_hash(rom: &[u8]) -> u64 {
+    rom.iter().fold(0xCBF2_9CE4_8422_2325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01B3)
+    })
+}
+
+/// Полный снимок машины (без трассировщика)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SaveState {
+    pub rom_hash: u64,
+    pub variant: Variant,
+    pub quirks: Quirks,
+    pub registers: [u8; 16],
+    pub index_register: u16,
+    pub program_counter: u16,
+    pub stack: [u16; 16],
+    pub stack_pointer: u8,
+    pub memory: Vec<u8>,
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+    pub pixels: Box<[[u8; HIRES_WIDTH]; HIRES_HEIGHT]>,
+    pub hires: bool,
+    pub planes: u8,
+    pub keys: [bool; 16],
+    pub waiting_for_key: Option<usize>,
+    pub key_wait_pressed: Option<u8>,
+    pub key_wait_held: [bool; 16],
+    pub running: bool,
+    pub vblank_wait: bool,
+    pub rpl_flags: [u8; 16],
+    pub audio_pattern: [u8; 16],
+    pub pattern_loaded: bool,
+    pub pitch: u8,
+    pub cycles: u64,
+}
+
+impl SaveState {
+    pub fn capture(cpu: &CPU, rom_hash: u64) -> Self {
+        SaveState {
+            rom_hash,
+            variant: cpu.variant,
+            quirks: cpu.quirks,
+            registers: cpu.registers,
+            index_register: cpu.index_register,
+            program_counter: cpu.program_counter,
+            stack: cpu.stack,
+            stack_pointer: cpu.stack_pointer,
+            memory: cpu.memory.clone(),
+            delay_timer: cpu.delay_timer,
+            sound_timer: cpu.sound_timer,
+            pixels: Box::new(cpu.display.pixels),
+            hires: cpu.display.hires,
+            planes: cpu.display.planes,
+            keys: cpu.keyboard.keys(),
+            waiting_for_key: cpu.waiting_for_key,
+            key_wait_pressed: cpu.key_wait_pressed,
+            key_wait_held: cpu.key_wait_held,
+            running: cpu.running,
+            vblank_wait: cpu.vblank_wait,
+            rpl_flags: cpu.rpl_flags,
+            audio_pattern: cpu.audio_pattern,
+            pattern_loaded: cpu.pattern_loaded,
+            pitch: cpu.pitch,
+            cycles: cpu.cycles,
+        }
+    }
+
+    /// Перенести снимок в процессор; трассировщик остаётся прежним
+    pub fn apply(&self, cpu: &mut CPU) {
+        cpu.variant = self.variant;
+        cpu.quirks = self.quirks;
+        cpu.registers = self.registers;
+        cpu.index_register = self.index_register;
+        cpu.program_counter = self.program_counter;
+        cpu.stack = self.stack;
+        cpu.stack_pointer = self.stack_pointer;
+        cpu.memory.clone_from(&self.memory);
+        cpu.delay_timer = self.delay_timer;
+        cpu.sound_timer = self.sound_timer;
+        cpu.display.pixels = *self.pixels;
+        cpu.display.hires = self.hires;
+        cpu.display.planes = self.planes;
+        cpu.display.needs_redraw = true;
+        for (key, &pressed) in self.keys.iter().enumerate() {
+            cpu.keyboard.set_key(key as u8, pressed);
+        }
+        cpu.waiting_for_key = self.waiting_for_key;
+        cpu.key_wait_pressed = self.key_wait_pressed;
+        cpu.key_wait_held = self.key_wait_held;
+        cpu.running = self.running;
+        cpu.vblank_wait = self.vblank_wait;
+        cpu.rpl_flags = self.rpl_flags;
+        cpu.audio_pattern = self.audio_pattern;
+        cpu.pattern_loaded = self.pattern_loaded;
+        cpu.pitch = self.pitch;
+        cpu.cycles = self.cycles;
+    }
+
+    /// Двоичный формат (little-endian): сигнатура, версия, хеш ROM, затем поля по порядку
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(self.memory.len() + HIRES_WIDTH * HIRES_HEIGHT + 256);
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(&VERSION.to_le_bytes());
+        out.extend_from_slice(&self.rom_hash.to_le_bytes());
+
+        out.push(match self.variant {
+            Variant::Classic => 0,
+            Variant::XoChip => 1,
+        });
+        out.extend_from_slice(&self.quirks.to_bytes());
+
+        out.extend_from_slice(&self.registers);
+        out.extend_from_slice(&self.index_register.to_le_bytes());
+        out.extend_from_slice(&self.program_counter.to_le_bytes());
+        for addr in self.stack {
+            out.extend_from_slice(&addr.to_le_bytes());
+        }
+        out.push(self.stack_pointer);
+        out.push(self.delay_timer);
+        out.push(self.sound_timer);
+
+        // 0xFF - клавиша не ожидается
+        out.push(self.waiting_for_key.map_or(0xFF, |reg| reg as u8));
+        out.push(self.key_wait_pressed.unwrap_or(0xFF));
+        out.extend_from_slice(&key_mask(&self.key_wait_held).to_le_bytes());
+        out.push(self.running as u8);
+        out.push(self.vblank_wait as u8);
+        out.extend_from_slice(&self.rpl_flags);
+        out.extend_from_slice(&self.audio_pattern);
+        out.push(self.pattern_loaded as u8);
+        out.push(self.pitch);
+        out.extend_from_slice(&self.cycles.to_le_bytes());
+
+        out.extend_from_slice(&key_mask(&self.keys).to_le_bytes());
+
+        out.push(self.hires as u8);
+        out.push(self.planes);
+        for row in self.pixels.iter() {
+            out.extend_from_slice(row);
+        }
+
+        out.extend_from_slice(&(self.memory.len() as u32).to_le_bytes());
+        out.extend_from_slice(&self.memory);
+        out
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self, SaveStateError> {
+        let mut r = Reader { data };
+        if r.take(MAGIC.len()).map_err(|_| SaveStateError::BadMagic)? != MAGIC {
+            return Err(SaveStateError::BadMagic);
+        }
+        let version = r.u16()?;
+        if version != VERSION {
+            return Err(SaveStateError::UnsupportedVersion(version));
+        }
+        let rom_hash = r.u64()?;
+
+        let variant = match r.u8()? {
+            0 => Variant::Classic,
+            1 => Variant::XoChip,
+            _ => return Err(SaveStateError::Invalid("unknown variant")),
+        };
+        let quirks = Quirks::from_bytes(r.array()?).map_err(SaveStateError::Invalid)?;
+
+        let registers = r.array()?;
+        let index_register = r.u16()?;
+        let program_counter = r.u16()?;
+        let mut stack = [0; 16];
+        for addr in stack.iter_mut() {
+            *addr = r.u16()?;
+        }
+        let stack_pointer = r.u8()?;
+        if stack_pointer as usize > stack.len() {
+            return Err(SaveStateError::Invalid("stack pointer out of range"));
+        }
+        let delay_timer = r.u8()?;
+        let sound_timer = r.u8()?;
+
+        let waiting_for_key = match r.u8()? {
+            0xFF => None,
+            reg @ 0..=0xF => Some(reg as usize),
+            _ => return Err(SaveStateError::Invalid("key wait register out of range")),
+        };
+        let key_wait_pressed = match r.u8()? {
+            0xFF => None,
+            key @ 0..=0xF => Some(key),
+            _ => return Err(SaveStateError::Invalid("waited key out of range")),
+        };
+        let key_wait_held = keys_from_mask(r.u16()?);
+        let running = r.bool()?;
+        let vblank_wait = r.bool()?;
+        let rpl_flags = r.array()?;
+        let audio_pattern = r.array()?;
+        let pattern_loaded = r.bool()?;
+        let pitch = r.u8()?;
+        let cycles = r.u64()?;
+
+        let keys = keys_from_mask(r.u16()?);
+
+        let hires = r.bool()?;
+        let planes = r.u8()?;
+        let mut pixels = Box::new([[0; HIRES_WIDTH]; HIRES_HEIGHT]);
+        for row in pixels.iter_mut() {
+            row.copy_from_slice(r.take(HIRES_WIDTH)?);
+        }
+
+        let memory_len = r.u32()? as usize;
+        if memory_len != variant.memory_size() {
+            return Err(SaveStateError::Invalid("memory size does not match variant"));
+        }
+        let memory = r.take(memory_len)?.to_vec();
+
+        Ok(SaveState {
+            rom_hash,
+            variant,
+            quirks,
+            registers,
+            index_register,
+            program_counter,
+            stack,
+            stack_pointer,
+            memory,
+            delay_timer,
+            sound_timer,
+            pixels,
+            hires,
+            planes,
+            keys,
+            waiting_for_key,
+            key_wait_pressed,
+            key_wait_held,
+            running,
+            vblank_wait,
+            rpl_flags,
+            audio_pattern,
+            pattern_loaded,
+            pitch,
+            cycles,
+        })
+    }
+
+    pub fn write_to(&self, path: impl AsRef<Path>) -> Result<(), SaveStateError> {
+        fs::write(path, self.to_bytes())?;
+        Ok(())
+    }
+
+    pub fn read_from(path: impl AsRef<Path>) -> Result<Self, SaveStateError> {
+        Self::from_bytes(&fs::read(path)?)
+    }
+}
+
+fn key_mask(keys: &[bool; 16]) -> u16 {
+    keys.iter().enumerate().fold(0, |mask, (key, &pressed)| mask | ((pressed as u16) << key))
+}
+
+fn keys_from_mask(mask: u16) -> [bool; 16] {
+    std::array::from_fn(|key| mask & (1 << key) != 0)
+}
+
+/// Последовательное чтение полей с проверкой длины
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], SaveStateError> {
+        if self.data.len() < len {
+            return Err(SaveStateError::Truncated);
+        }
+        let (head, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(head)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], SaveStateError> {
+        Ok(self.take(N)?.try_into().unwrap())
+    }
+
+    fn u8(&mut self) -> Result<u8, SaveStateError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn bool(&mut self) -> Result<bool, SaveStateError> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(SaveStateError::Invalid("bad boolean")),
+        }
+    }
+
+    fn u16(&mut self) -> Result<u16, SaveStateError> {
+        Ok(u16::from_le_bytes(self.array()?))
+    }
+
+    fn u32(&mut self) -> Result<u32, SaveStateError> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    fn u64(&mut self) -> Result<u64, SaveStateError> {
+        Ok(u64::from_le_bytes(self.array()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_cpu() -> CPU {
+        let mut cpu = CPU::with_variant(Variant::XoChip);
+        cpu.quirks = Quirks::vip();
+        cpu.registers[3] = 0x42;
+        cpu.index_register = 0x1234;
+        cpu.program_counter = 0x2F0;
+        cpu.stack[0] = 0x204;
+        cpu.stack_pointer = 1;
+        cpu.memory[0xFFFF] = 0xAB;
+        cpu.delay_timer = 9;
+        cpu.sound_timer = 3;
+        cpu.display.pixels[63][127] = 0b11;
+        cpu.display.hires = true;
+        cpu.display.planes = 3;
+        cpu.keyboard.set_key(0xA, true);
+        cpu.waiting_for_key = Some(5);
+        cpu.key_wait_pressed = Some(0xC);
+        cpu.key_wait_held[0xC] = true;
+        cpu.rpl_flags[7] = 0x77;
+        cpu.audio_pattern[0] = 0xF0;
+        cpu.pattern_loaded = true;
+        cpu.pitch = 100;
+        cpu.cycles = 123_456;
+        cpu
+    }
+
+    #[test]
+    fn round_trips_through_bytes() {
+        let state = SaveState::capture(&sample_cpu(), rom_hash(b"rom"));
+        let restored = SaveState::from_bytes(&state.to_bytes()).unwrap();
+        assert_eq!(restored, state);
+    }
+
+    #[test]
+    fn apply_restores_machine() {
+        let state = SaveState::capture(&sample_cpu(), 0);
+        let mut cpu = CPU::new();
+        state.apply(&mut cpu);
+        assert_eq!(SaveState::capture(&cpu, 0), state);
+        assert!(cpu.display.needs_redraw);
+    }
+
+    #[test]
+    fn rejects_bad_headers() {
+        assert!(matches!(SaveState::from_bytes(b"nope"), Err(SaveStateError::BadMagic)));
+
+        let mut bytes = SaveState::capture(&CPU::new(), 0).to_bytes();
+        bytes[4] = 99;
+        assert!(matches!(
+            SaveState::from_bytes(&bytes),
+            Err(SaveStateError::UnsupportedVersion(99))
+        ));
+
+        bytes[4] = 1;
+        let message = SaveState::from_bytes(&bytes).unwrap_err().to_string();
+        assert!(message.contains("older emulator"), "{}", message);
+    }
+
+    #[test]
+    fn rejects_truncated_state() {
+        let bytes = SaveState::capture(&CPU::new(), 0).to_bytes();
+        assert!(matches!(
+            SaveState::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(SaveStateError::Truncated)
+        ));
+    }
+
+    #[test]
+    fn hash_depends_on_content() {
+        assert_ne!(rom_hash(b"pong"), rom_hash(b"tetris"));
+        assert_eq!(rom_hash(b""), 0xCBF2_9CE4_8422_2325);
+    }
+}
@@ -0,0 +1,137 @@
+use std::time::{Duration, Instant};
+
+use crate::fault::Chip8Fault;
+use crate::machine::Chip8;
+
+/// Частота кадров и таймеров CHIP-8
+pub const FRAME_RATE: u64 = 60;
+
+/// Сколько инструкций выполняется за кадр по умолчанию (~600 Гц)
+pub const DEFAULT_INSTRUCTIONS_PER_FRAME: usize = 10;
+
+/// Больше кадров за раз не догоняем - после долгой паузы хоста
+/// эмуляция просто продолжается, а не прокручивает секунды игры
+const MAX_CATCH_UP_FRAMES: u64 = 5;
+
+/// Источник времени планировщика
+#[derive(Debug, Clone, Copy)]
+enum Clock {
+    /// Реальное время с момента создания
+    RealTime { start: Instant },
+    /// Виртуальное время, двигается только через `advance`
+    Virtual { now: Duration },
+}
+
+/// Планировщик: N инструкций на кадр, таймеры ровно раз в кадр
+#[derive(Debug, Clone)]
+pub struct Scheduler {
+    pub instructions_per_frame: usize,
+    clock: Clock,
+    // Сколько кадров уже учтено (выполнено или пропущено)
+    frames: u64,
+}
+
+impl Scheduler {
+    /// Планировщик, идущий по часам хоста
+    pub fn real_time(instructions_per_frame: usize) -> Self {
+        Scheduler {
+            instructions_per_frame,
+            clock: Clock::RealTime { start: Instant::now() },
+            frames: 0,
+        }
+    }
+
+    /// Детерминированный планировщик для тестов и безоконных прогонов
+    pub fn virtual_time(instructions_per_frame: usize) -> Self {
+        Scheduler {
+            instructions_per_frame,
+            clock: Clock::Virtual { now: Duration::ZERO },
+            frames: 0,
+        }
+    }
+
+    /// Сдвинуть виртуальные часы; для реального времени ничего не делает
+    pub fn advance(&mut self, dt: Duration) {
+        if let Clock::Virtual { now } = &mut self.clock {
+            *now += dt;
+        }
+    }
+
+    /// Время от старта по часам планировщика
+    pub fn elapsed(&self) -> Duration {
+        match self.clock {
+            Clock::RealTime { start } => start.elapsed(),
+            Clock::Virtual { now } => now,
+        }
+    }
+
+    /// Номер текущего кадра
+    pub fn frame(&self) -> u64 {
+        self.frames
+    }
+
+    /// Сколько кадров пора выполнить; отмечает их как учтённые
+    pub fn due_frames(&mut self) -> u64 {
+        let target = (self.elapsed().as_nanos() * FRAME_RATE as u128 / 1_000_000_000) as u64;
+        let due = target.saturating_sub(self.frames);
+        let run = match self.clock {
+            Clock::RealTime { .. } => due.min(MAX_CATCH_UP_FRAMES),
+            Clock::Virtual { .. } => due,
+        };
+        // Пропущенные при догоне кадры тоже считаются учтёнными
+        self.frames += due;
+        run
+    }
+
+    /// Выполнить все накопившиеся кадры. Возвращает число выполненных кадров.
+    pub fn run(&mut self, chip8: &mut Chip8) -> Result<u64, Chip8Fault> {
+        let due = self.due_frames();
+        for _ in 0..due {
+            chip8.run_frame(self.instructions_per_frame)?;
+        }
+        Ok(due)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn virtual_time_runs_exact_frames() {
+        let mut scheduler = Scheduler::virtual_time(10);
+        assert_eq!(scheduler.due_frames(), 0);
+
+        scheduler.advance(Duration::from_millis(10));
+        assert_eq!(scheduler.due_frames(), 0);
+
+        scheduler.advance(Duration::from_millis(10));
+        assert_eq!(scheduler.due_frames(), 1);
+
+        scheduler.advance(Duration::from_secs(1));
+        assert_eq!(scheduler.due_frames(), 60);
+        assert_eq!(scheduler.frame(), 61);
+    }
+
+    #[test]
+    fn timers_tick_once_per_frame() {
+        // V0 = 60, DT = V0, бесконечный цикл
+        let mut chip8 = Chip8::default();
+        chip8.load(&[0x60, 0x3C, 0xF0, 0x15, 0x12, 0x04]).unwrap();
+        let mut scheduler = Scheduler::virtual_time(20);
+
+        scheduler.advance(Duration::from_millis(500));
+        assert_eq!(scheduler.run(&mut chip8), Ok(30));
+        assert_eq!(chip8.cpu().delay_timer, 30);
+        assert_eq!(chip8.cpu().cycles, 30 * 20);
+    }
+
+    #[test]
+    fn fault_stops_the_frame() {
+        let mut chip8 = Chip8::default();
+        chip8.load(&[0x00, 0xEE]).unwrap();
+        let mut scheduler = Scheduler::virtual_time(10);
+        scheduler.advance(Duration::from_secs(1));
+        assert!(scheduler.run(&mut chip8).is_err());
+    }
+}
@@ -0,0 +1,237 @@
+use std::ops::RangeInclusive;
+use std::path::PathBuf;
+#[cfg(feature = "gif")]
+use std::fs::File;
+#[cfg(feature = "gif")]
+use std::io::{self, BufWriter};
+#[cfg(any(feature = "png", feature = "gif"))]
+use std::io::Write;
+#[cfg(feature = "gif")]
+use std::path::Path;
+
+use crate::display::Display;
+#[cfg(feature = "gif")]
+use crate::scheduler::FRAME_RATE;
+#[cfg(any(feature = "png", feature = "gif"))]
+use crate::video::Image;
+#[cfg(feature = "gif")]
+use crate::video::{Overlay, Scaling};
+
+/// Длина строки растра PBM (формат рекомендует не больше 70 символов)
+const PBM_LINE: usize = 64;
+
+/// Кадр в текстовом PBM (P1): 1 - горящий пиксель в любой плоскости.
+/// Текстовый вариант удобно сравнивать diff'ом в тестах.
+pub fn to_pbm(display: &Display) -> String {
+    let (width, height) = (display.width(), display.height());
+    let mut out = format!("P1\n{} {}\n", width, height);
+    for row in &display.pixels[..height] {
+        let bits: Vec<u8> = row[..width]
+            .iter()
+            .map(|&pixel| if pixel != 0 { b'1' } else { b'0' })
+            .collect();
+        for chunk in bits.chunks(PBM_LINE) {
+            out.push_str(std::str::from_utf8(chunk).unwrap());
+            out.push('\n');
+        }
+    }
+    out
+}
+
+/// Кадр в PNG (RGB, 8 бит на канал)
+#[cfg(feature = "png")]
+pub fn write_png<W: Write>(image: &Image, writer: W) -> Result<(), png::EncodingError> {
+    let mut encoder = png::Encoder::new(writer, image.width as u32, image.height as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.write_header()?.write_image_data(&image.rgb())
+}
+
+/// Первый свободный файл `<prefix>-001.<extension>`, `<prefix>-002.<extension>`, ...
+pub fn next_free_path(prefix: &str, extension: &str) -> PathBuf {
+    (1..)
+        .map(|n| PathBuf::from(format!("{}-{:03}.{}", prefix, n, extension)))
+        .find(|path| !path.exists())
+        .expect("some file name is free")
+}
+
+/// Диапазон кадров `A-B` или `A-` (до конца), включительно
+pub fn parse_frame_range(s: &str) -> Result<RangeInclusive<u64>, String> {
+    let error = || format!("bad frame range '{}', expected START-END or START-", s);
+    let (start, end) = s.split_once('-').ok_or_else(error)?;
+    let start: u64 = start.trim().parse().map_err(|_| error())?;
+    let end: u64 = match end.trim() {
+        "" => u64::MAX,
+        end => end.parse().map_err(|_| error())?,
+    };
+    if end < start {
+        return Err(error());
+    }
+    Ok(start..=end)
+}
+
+/// Запись анимированного GIF по кадрам эмуляции. Размер задаётся первым
+/// кадром; кадры другого размера (смена lo-res/hi-res) вписываются в него.
+/// Одинаковые кадры подряд склеиваются в один с большей задержкой.
+#[cfg(feature = "gif")]
+pub struct GifRecorder<W: Write> {
+    encoder: Option<gif::Encoder<W>>,
+    writer: Option<W>,
+    width: usize,
+    height: usize,
+    // Кадр, ещё не записанный в файл, и сколько кадров эмуляции он держится
+    pending: Option<(Image, u64)>,
+    // Кадров эмуляции уже в файле
+    frames: u64,
+}
+
+#[cfg(feature = "gif")]
+impl GifRecorder<BufWriter<File>> {
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self::new(BufWriter::new(File::create(path)?)))
+    }
+}
+
+#[cfg(feature = "gif")]
+impl<W: Write> GifRecorder<W> {
+    pub fn new(writer: W) -> Self {
+        GifRecorder { encoder: None, writer: Some(writer), width: 0, height: 0, pending: None, frames: 0 }
+    }
+
+    /// Сколько кадров эмуляции записано, включая ещё не сброшенный
+    pub fn frames(&self) -> u64 {
+        self.frames + self.pending.as_ref().map_or(0, |(_, count)| *count)
+    }
+
+    /// Добавить кадр, который был на экране `frames` кадров эмуляции
+    pub fn push(&mut self, image: &Image, frames: u64) -> Result<(), gif::EncodingError> {
+        if let Some((last, count)) = &mut self.pending
+            && last == image
+        {
+            *count += frames;
+            return Ok(());
+        }
+        self.flush()?;
+        self.pending = Some((image.clone(), frames));
+        Ok(())
+    }
+
+    /// Дописать последний кадр и конец файла. Без кадров ничего не пишется
+    pub fn finish(mut self) -> Result<W, gif::EncodingError> {
+        self.flush()?;
+        match self.encoder.take() {
+            Some(encoder) => Ok(encoder.into_inner()?),
+            None => Ok(self.writer.take().expect("writer is kept until the first frame")),
+        }
+    }
+
+    fn flush(&mut self) -> Result<(), gif::EncodingError> {
+        let Some((image, count)) = self.pending.take() else {
+            return Ok(());
+        };
+        if self.encoder.is_none() {
+            self.width = image.width;
+            self.height = image.height;
+            let writer = self.writer.take().expect("writer is kept until the first frame");
+            let mut encoder = gif::Encoder::new(writer, image.width as u16, image.height as u16, &[])?;
+            encoder.set_repeat(gif::Repeat::Infinite)?;
+            self.encoder = Some(encoder);
+        }
+        let image = if (image.width, image.height) == (self.width, self.height) {
+            image
+        } else {
+            image.resized(self.width, self.height, Scaling::Fit, Overlay::None)
+        };
+
+        // Задержка в сотых долях секунды; округляем от начала записи,
+        // чтобы 60 кадров в секунду не превращались в 50 или 100
+        let centiseconds = |frames: u64| (frames * 100 + FRAME_RATE / 2) / FRAME_RATE;
+        let delay = centiseconds(self.frames + count) - centiseconds(self.frames);
+        self.frames += count;
+
+        let mut frame = gif::Frame::from_rgb_speed(image.width as u16, image.height as u16, &image.rgb(), 10);
+        frame.delay = delay.min(u16::MAX as u64) as u16;
+        self.encoder.as_mut().expect("encoder is created above").write_frame(&frame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(any(feature = "png", feature = "gif"))]
+    use crate::video::{Overlay, Palette};
+
+    #[test]
+    fn pbm_marks_lit_pixels() {
+        let mut display = Display::new();
+        display.pixels[0][0] = 1;
+        display.pixels[1][63] = 2;
+
+        let pbm = to_pbm(&display);
+        let lines: Vec<&str> = pbm.lines().collect();
+        assert_eq!(lines[0], "P1");
+        assert_eq!(lines[1], "64 32");
+        assert_eq!(lines.len(), 2 + 32);
+        assert!(lines[2].starts_with("10"));
+        assert!(lines[3].ends_with("01"));
+    }
+
+    #[test]
+    fn pbm_wraps_hires_rows() {
+        let mut display = Display::new();
+        display.hires = true;
+        assert_eq!(to_pbm(&display).lines().count(), 2 + 64 * 2);
+    }
+
+    #[cfg(feature = "png")]
+    #[test]
+    fn png_has_image_size() {
+        let image = Image::from_display(&Display::new(), &Palette::default()).scaled(2, Overlay::None);
+        let mut png = Vec::new();
+        write_png(&image, &mut png).unwrap();
+        assert_eq!(&png[1..4], b"PNG");
+        // IHDR: ширина и высота сразу после сигнатуры и заголовка чанка
+        assert_eq!(u32::from_be_bytes(png[16..20].try_into().unwrap()), 128);
+        assert_eq!(u32::from_be_bytes(png[20..24].try_into().unwrap()), 64);
+    }
+
+    #[test]
+    fn parses_frame_ranges() {
+        assert_eq!(parse_frame_range("60-180"), Ok(60..=180));
+        assert_eq!(parse_frame_range("10-"), Ok(10..=u64::MAX));
+        assert!(parse_frame_range("20-10").is_err());
+        assert!(parse_frame_range("10").is_err());
+    }
+
+    #[cfg(feature = "gif")]
+    #[test]
+    fn gif_merges_repeated_frames() {
+        let palette = Palette::default();
+        let mut display = Display::new();
+        let blank = Image::from_display(&display, &palette);
+        display.pixels[0][0] = 1;
+        let dot = Image::from_display(&display, &palette);
+        display.hires = true;
+        let hires = Image::from_display(&display, &palette);
+
+        let mut gif = GifRecorder::new(Vec::new());
+        gif.push(&blank, 1).unwrap();
+        gif.push(&blank, 1).unwrap();
+        gif.push(&dot, 1).unwrap();
+        gif.push(&hires, 3).unwrap();
+        assert_eq!(gif.frames(), 6);
+        let data = gif.finish().unwrap();
+
+        let mut decoder = gif::DecodeOptions::new().read_info(data.as_slice()).unwrap();
+        assert_eq!((decoder.width(), decoder.height()), (64, 32));
+        let mut delays = Vec::new();
+        while let Some(frame) = decoder.read_next_frame().unwrap() {
+            assert_eq!((frame.width, frame.height), (64, 32));
+            delays.push(frame.delay);
+        }
+        // 2, 1 и 3 кадра по 1/60 секунды
+        assert_eq!(delays, [3, 2, 5]);
+
+        assert!(GifRecorder::new(Vec::new()).finish().unwrap().is_empty());
+    }
+}
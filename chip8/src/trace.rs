@@ -0,0 +1,414 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::io::{self, Write};
+use std::ops::RangeInclusive;
+use std::rc::Rc;
+use std::str::FromStr;
+
+use crate::cpu::CPU;
+use crate::disasm::mnemonic;
+
+/// Группа инструкций для фильтрации трассы
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OpcodeClass {
+    /// 00E0, 00EE, 00FD, прокрутка и прочие 0NNN
+    System,
+    /// 1NNN, BNNN
+    Jump,
+    /// 2NNN
+    Call,
+    /// 3XKK, 4XKK, 5XY0, 9XY0, EX9E, EXA1
+    Skip,
+    /// 6XKK, 7XKK, 8XYN, CXKK
+    Alu,
+    /// ANNN, FX1E, FX29, FX30, F000
+    Index,
+    /// DXYN
+    Draw,
+    /// FX07, FX0A, FX15, FX18
+    Timer,
+    /// FX33, FX55, FX65, FX75, FX85, 5XY2, 5XY3, F002
+    Memory,
+    /// Всё остальное
+    Other,
+}
+
+impl OpcodeClass {
+    pub fn all() -> [OpcodeClass; 10] {
+        [
+            OpcodeClass::System,
+            OpcodeClass::Jump,
+            OpcodeClass::Call,
+            OpcodeClass::Skip,
+            OpcodeClass::Alu,
+            OpcodeClass::Index,
+            OpcodeClass::Draw,
+            OpcodeClass::Timer,
+            OpcodeClass::Memory,
+            OpcodeClass::Other,
+        ]
+    }
+
+    pub fn of(opcode: u16) -> Self {
+        match (opcode >> 12, opcode & 0x00FF, opcode & 0x000F) {
+            (0x0, _, _) => OpcodeClass::System,
+            (0x1 | 0xB, _, _) => OpcodeClass::Jump,
+            (0x2, _, _) => OpcodeClass::Call,
+            (0x5, _, 0x2 | 0x3) => OpcodeClass::Memory,
+            (0x3 | 0x4 | 0x5 | 0x9 | 0xE, _, _) => OpcodeClass::Skip,
+            (0x6 | 0x7 | 0x8 | 0xC, _, _) => OpcodeClass::Alu,
+            (0xA, _, _) => OpcodeClass::Index,
+            (0xD, _, _) => OpcodeClass::Draw,
+            (0xF, 0x00 | 0x1E | 0x29 | 0x30, _) => OpcodeClass::Index,
+            (0xF, 0x07 | 0x0A | 0x15 | 0x18, _) => OpcodeClass::Timer,
+            (0xF, 0x02 | 0x33 | 0x55 | 0x65 | 0x75 | 0x85, _) => OpcodeClass::Memory,
+            _ => OpcodeClass::Other,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            OpcodeClass::System => "system",
+            OpcodeClass::Jump => "jump",
+            OpcodeClass::Call => "call",
+            OpcodeClass::Skip => "skip",
+            OpcodeClass::Alu => "alu",
+            OpcodeClass::Index => "index",
+            OpcodeClass::Draw => "draw",
+            OpcodeClass::Timer => "timer",
+            OpcodeClass::Memory => "memory",
+            OpcodeClass::Other => "other",
+        }
+    }
+}
+
+impl fmt::Display for OpcodeClass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for OpcodeClass {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        OpcodeClass::all()
+            .into_iter()
+            .find(|class| class.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| format!("unknown opcode class '{}'", s))
+    }
+}
+
+/// Регистр, изменение которого попадает в трассу
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Register {
+    V(u8),
+    I,
+    Sp,
+    Dt,
+    St,
+}
+
+impl Register {
+    /// Текущее значение регистра
+    pub fn read(&self, cpu: &CPU) -> u16 {
+        match *self {
+            Register::V(n) => cpu.registers[n as usize & 0xF] as u16,
+            Register::I => cpu.index_register,
+            Register::Sp => cpu.stack_pointer as u16,
+            Register::Dt => cpu.delay_timer as u16,
+            Register::St => cpu.sound_timer as u16,
+        }
+    }
+
+    /// Записать значение; 8-битные регистры берут младший байт,
+    /// SP не выходит за размер стека
+    pub fn write(&self, cpu: &mut CPU, value: u16) {
+        match *self {
+            Register::V(n) => cpu.registers[n as usize & 0xF] = value as u8,
+            Register::I => cpu.index_register = value,
+            Register::Sp => cpu.stack_pointer = value.min(cpu.stack.len() as u16) as u8,
+            Register::Dt => cpu.delay_timer = value as u8,
+            Register::St => cpu.sound_timer = value as u8,
+        }
+    }
+}
+
+impl FromStr for Register {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_uppercase().as_str() {
+            "I" => Ok(Register::I),
+            "SP" => Ok(Register::Sp),
+            "DT" => Ok(Register::Dt),
+            "ST" => Ok(Register::St),
+            name => name
+                .strip_prefix('V')
+                .filter(|n| n.len() == 1)
+                .and_then(|n| u8::from_str_radix(n, 16).ok())
+                .map(Register::V)
+                .ok_or_else(|| format!("unknown register '{}', expected V0-VF, I, SP, DT or ST", s)),
+        }
+    }
+}
+
+impl fmt::Display for Register {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Register::V(n) => write!(f, "V{:X}", n),
+            Register::I => f.write_str("I"),
+            Register::Sp => f.write_str("SP"),
+            Register::Dt => f.write_str("DT"),
+            Register::St => f.write_str("ST"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RegisterChange {
+    pub register: Register,
+    pub old: u16,
+    pub new: u16,
+}
+
+/// Значения регистров до и после инструкции
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RegisterSnapshot {
+    registers: [u8; 16],
+    index_register: u16,
+    stack_pointer: u8,
+    delay_timer: u8,
+    sound_timer: u8,
+}
+
+impl RegisterSnapshot {
+    pub fn capture(cpu: &CPU) -> Self {
+        RegisterSnapshot {
+            registers: cpu.registers,
+            index_register: cpu.index_register,
+            stack_pointer: cpu.stack_pointer,
+            delay_timer: cpu.delay_timer,
+            sound_timer: cpu.sound_timer,
+        }
+    }
+
+    pub fn diff(&self, after: &RegisterSnapshot) -> Vec<RegisterChange> {
+        let mut changes = Vec::new();
+        let mut push = |register, old: u16, new: u16| {
+            if old != new {
+                changes.push(RegisterChange { register, old, new });
+            }
+        };
+
+        for i in 0..16 {
+            push(Register::V(i as u8), self.registers[i] as u16, after.registers[i] as u16);
+        }
+        push(Register::I, self.index_register, after.index_register);
+        push(Register::Sp, self.stack_pointer as u16, after.stack_pointer as u16);
+        push(Register::Dt, self.delay_timer as u16, after.delay_timer as u16);
+        push(Register::St, self.sound_timer as u16, after.sound_timer as u16);
+        changes
+    }
+}
+
+/// Запись об одной выполненной инструкции
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceRecord {
+    pub cycle: u64,
+    pub pc: u16,
+    pub opcode: u16,
+    pub mnemonic: String,
+    pub changes: Vec<RegisterChange>,
+}
+
+impl fmt::Display for TraceRecord {
+    /// Строка трассы, поля через табуляцию:
+    /// `cycle  PC  opcode  mnemonic  VA=00>2A,I=0200>0300`
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}\t{:04X}\t{:04X}\t{}\t", self.cycle, self.pc, self.opcode, self.mnemonic)?;
+        for (i, change) in self.changes.iter().enumerate() {
+            if i > 0 {
+                f.write_str(",")?;
+            }
+            match change.register {
+                Register::I => write!(f, "{}={:04X}>{:04X}", change.register, change.old, change.new)?,
+                _ => write!(f, "{}={:02X}>{:02X}", change.register, change.old, change.new)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Получатель записей трассы
+pub trait TraceSink {
+    fn record(&mut self, record: &TraceRecord);
+}
+
+/// Сбор записей в память (для тестов и инструментов)
+impl TraceSink for Vec<TraceRecord> {
+    fn record(&mut self, record: &TraceRecord) {
+        self.push(record.clone());
+    }
+}
+
+/// Общий получатель, чтобы прочитать записи после прогона
+impl<T: TraceSink> TraceSink for Rc<RefCell<T>> {
+    fn record(&mut self, record: &TraceRecord) {
+        self.borrow_mut().record(record);
+    }
+}
+
+/// Построчная запись трассы в файл или stdout
+pub struct TraceWriter<W: Write> {
+    out: W,
+}
+
+impl<W: Write> TraceWriter<W> {
+    pub fn new(out: W) -> Self {
+        TraceWriter { out }
+    }
+}
+
+impl<W: Write> TraceSink for TraceWriter<W> {
+    fn record(&mut self, record: &TraceRecord) {
+        // Ошибка записи трассы не должна останавливать эмуляцию
+        let _ = writeln!(self.out, "{}", record);
+    }
+}
+
+impl<W: Write> Drop for TraceWriter<W> {
+    fn drop(&mut self) {
+        let _ = self.out.flush();
+    }
+}
+
+/// Какие инструкции попадают в трассу
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TraceFilter {
+    /// Диапазон адресов PC; `None` - все адреса
+    pub addresses: Option<RangeInclusive<u16>>,
+    /// Группы инструкций; пустой список - все группы
+    pub classes: Vec<OpcodeClass>,
+}
+
+impl TraceFilter {
+    pub fn matches(&self, pc: u16, opcode: u16) -> bool {
+        let in_range = self.addresses.as_ref().is_none_or(|range| range.contains(&pc));
+        let in_class = self.classes.is_empty() || self.classes.contains(&OpcodeClass::of(opcode));
+        in_range && in_class
+    }
+}
+
+/// Разбор диапазона адресов вида `200-2FF` (hex)
+pub fn parse_address_range(s: &str) -> Result<RangeInclusive<u16>, String> {
+    let (start, end) = s
+        .split_once('-')
+        .ok_or_else(|| format!("expected address range like 200-2FF, got '{}'", s))?;
+    let parse = |part: &str| {
+        u16::from_str_radix(part.trim().trim_start_matches("0x"), 16)
+            .map_err(|e| format!("invalid address '{}': {}", part, e))
+    };
+    Ok(parse(start)?..=parse(end)?)
+}
+
+/// Трассировщик, подключаемый к процессору
+pub struct Tracer {
+    pub filter: TraceFilter,
+    sink: Box<dyn TraceSink>,
+}
+
+impl Tracer {
+    pub fn new(filter: TraceFilter, sink: Box<dyn TraceSink>) -> Self {
+        Tracer { filter, sink }
+    }
+
+    /// Трасса в stdout или файл (`-` означает stdout)
+    pub fn to_path(filter: TraceFilter, path: &str) -> io::Result<Self> {
+        let sink: Box<dyn TraceSink> = if path == "-" {
+            Box::new(TraceWriter::new(io::stdout()))
+        } else {
+            Box::new(TraceWriter::new(io::BufWriter::new(std::fs::File::create(path)?)))
+        };
+        Ok(Tracer::new(filter, sink))
+    }
+
+    pub(crate) fn trace(
+        &mut self,
+        cycle: u64,
+        pc: u16,
+        opcode: u16,
+        before: &RegisterSnapshot,
+        after: &RegisterSnapshot,
+    ) {
+        let record = TraceRecord {
+            cycle,
+            pc,
+            opcode,
+            mnemonic: mnemonic(opcode),
+            changes: before.diff(after),
+        };
+        self.sink.record(&record);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_opcodes() {
+        assert_eq!(OpcodeClass::of(0x00E0), OpcodeClass::System);
+        assert_eq!(OpcodeClass::of(0xB200), OpcodeClass::Jump);
+        assert_eq!(OpcodeClass::of(0x5122), OpcodeClass::Memory);
+        assert_eq!(OpcodeClass::of(0x5120), OpcodeClass::Skip);
+        assert_eq!(OpcodeClass::of(0x8124), OpcodeClass::Alu);
+        assert_eq!(OpcodeClass::of(0xF11E), OpcodeClass::Index);
+        assert_eq!(OpcodeClass::of(0xF10A), OpcodeClass::Timer);
+        assert_eq!(OpcodeClass::of(0xF155), OpcodeClass::Memory);
+        assert_eq!(OpcodeClass::of(0xF1FF), OpcodeClass::Other);
+    }
+
+    #[test]
+    fn filter_by_range_and_class() {
+        let filter = TraceFilter {
+            addresses: Some(0x200..=0x20F),
+            classes: vec![OpcodeClass::Draw],
+        };
+        assert!(filter.matches(0x204, 0xD015));
+        assert!(!filter.matches(0x210, 0xD015));
+        assert!(!filter.matches(0x204, 0x6015));
+        assert!(TraceFilter::default().matches(0xFFE, 0x0000));
+    }
+
+    #[test]
+    fn parses_address_range() {
+        assert_eq!(parse_address_range("200-2FF"), Ok(0x200..=0x2FF));
+        assert_eq!(parse_address_range("0x300-0x310"), Ok(0x300..=0x310));
+        assert!(parse_address_range("200").is_err());
+    }
+
+    #[test]
+    fn parses_register_names() {
+        assert_eq!("va".parse(), Ok(Register::V(0xA)));
+        assert_eq!("I".parse(), Ok(Register::I));
+        assert_eq!("dt".parse(), Ok(Register::Dt));
+        assert!("V10".parse::<Register>().is_err());
+        assert!("PC".parse::<Register>().is_err());
+    }
+
+    #[test]
+    fn record_line_format() {
+        let record = TraceRecord {
+            cycle: 7,
+            pc: 0x202,
+            opcode: 0x8124,
+            mnemonic: mnemonic(0x8124),
+            changes: vec![
+                RegisterChange { register: Register::V(1), old: 0xF0, new: 0x10 },
+                RegisterChange { register: Register::V(0xF), old: 0, new: 1 },
+            ],
+        };
+        assert_eq!(record.to_string(), "7\t0202\t8124\tADD V1, V2\tV1=F0>10,VF=00>01");
+    }
+}
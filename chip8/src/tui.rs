@@ -0,0 +1,151 @@
+//! Части терминального фронтенда, не зависящие от терминала: вывод экрана
+//! полублоками и отслеживание зажатых клавиш по событиям нажатия.
+
+use std::time::{Duration, Instant};
+
+use crate::display::Display;
+use crate::keymap::HostKey;
+use crate::runner::Input;
+
+/// Сколько клавиша считается зажатой после последнего нажатия или автоповтора,
+/// если терминал не сообщает об отпускании
+pub const HOLD_TIMEOUT: Duration = Duration::from_millis(150);
+
+/// Экран строками текста: одна ячейка - два пикселя по вертикали.
+/// Пиксель горит, если включена любая плоскость
+pub fn half_blocks(display: &Display) -> Vec<String> {
+    let (width, height) = (display.width(), display.height());
+    (0..height)
+        .step_by(2)
+        .map(|y| {
+            (0..width)
+                .map(|x| {
+                    let top = display.pixels[y][x] != 0;
+                    let bottom = y + 1 < height && display.pixels[y + 1][x] != 0;
+                    match (top, bottom) {
+                        (false, false) => ' ',
+                        (true, false) => '▀',
+                        (false, true) => '▄',
+                        (true, true) => '█',
+                    }
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Зажатые клавиши по событиям терминала. Большинство терминалов присылают
+/// только нажатия и автоповтор, поэтому клавиша отпускается сама через
+/// `timeout`; если отпускания приходят, таймаут не нужен
+#[derive(Debug, Clone)]
+pub struct HeldKeys {
+    // Клавиша, модификаторы при нажатии и когда она отпустится (None - ждём отпускания)
+    held: Vec<(HostKey, Vec<HostKey>, Option<Instant>)>,
+    pressed: Vec<HostKey>,
+    timeout: Option<Duration>,
+}
+
+impl HeldKeys {
+    /// `timeout: None` - терминал сообщает об отпускании клавиш
+    pub fn new(timeout: Option<Duration>) -> Self {
+        HeldKeys { held: Vec::new(), pressed: Vec::new(), timeout }
+    }
+
+    /// Нажатие или автоповтор клавиши с зажатыми модификаторами
+    pub fn press(&mut self, key: HostKey, modifiers: &[HostKey], now: Instant) {
+        let until = self.timeout.map(|timeout| now + timeout);
+        match self.held.iter_mut().find(|(k, _, _)| *k == key) {
+            // Автоповтор продлевает удержание, но нажатием не считается
+            Some(entry) => {
+                entry.1 = modifiers.to_vec();
+                entry.2 = until;
+            }
+            None => {
+                self.held.push((key, modifiers.to_vec(), until));
+                self.pressed.push(key);
+            }
+        }
+    }
+
+    pub fn release(&mut self, key: HostKey) {
+        self.held.retain(|(k, _, _)| *k != key);
+    }
+
+    /// Ввод для прохода цикла: нажатия с прошлого вызова и всё, что ещё зажато
+    pub fn input(&mut self, now: Instant) -> Input {
+        self.held.retain(|(_, _, until)| until.is_none_or(|until| now < until));
+        let mut held: Vec<HostKey> = Vec::new();
+        for (key, modifiers, _) in &self.held {
+            for key in std::iter::once(key).chain(modifiers) {
+                if !held.contains(key) {
+                    held.push(*key);
+                }
+            }
+        }
+        // Клавиша, нажатая и отпущенная между проходами, всё равно нажата
+        let pressed = std::mem::take(&mut self.pressed);
+        for key in &pressed {
+            if !held.contains(key) {
+                held.push(*key);
+            }
+        }
+        Input { held, pressed }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn two_pixel_rows_per_line() {
+        let mut display = Display::new();
+        display.pixels[0][0] = 1;
+        display.pixels[1][1] = 1;
+        display.pixels[2][2] = 2;
+        display.pixels[3][2] = 1;
+
+        let lines = half_blocks(&display);
+        assert_eq!(lines.len(), 16);
+        assert!(lines.iter().all(|line| line.chars().count() == 64));
+        assert!(lines[0].starts_with("▀▄ "));
+        assert!(lines[1].starts_with("  █ "));
+
+        display.hires = true;
+        assert_eq!(half_blocks(&display).len(), 32);
+    }
+
+    #[test]
+    fn keys_expire_without_release_events() {
+        let start = Instant::now();
+        let mut keys = HeldKeys::new(Some(HOLD_TIMEOUT));
+        keys.press(HostKey::F(1), &[HostKey::Shift], start);
+        let input = keys.input(start);
+        assert_eq!(input.pressed, [HostKey::F(1)]);
+        assert_eq!(input.held, [HostKey::F(1), HostKey::Shift]);
+
+        // Автоповтор продлевает удержание без нового нажатия
+        keys.press(HostKey::F(1), &[], start + Duration::from_millis(100));
+        let input = keys.input(start + Duration::from_millis(200));
+        assert!(input.pressed.is_empty());
+        assert_eq!(input.held, [HostKey::F(1)]);
+
+        assert!(keys.input(start + Duration::from_millis(300)).held.is_empty());
+    }
+
+    #[test]
+    fn release_events_end_hold() {
+        let start = Instant::now();
+        let mut keys = HeldKeys::new(None);
+        keys.press(HostKey::Char('w'), &[], start);
+        assert_eq!(keys.input(start + Duration::from_secs(5)).held, [HostKey::Char('w')]);
+
+        // Нажата и отпущена между проходами
+        keys.press(HostKey::Char('q'), &[], start);
+        keys.release(HostKey::Char('q'));
+        keys.release(HostKey::Char('w'));
+        let input = keys.input(start);
+        assert_eq!(input.held, [HostKey::Char('q')]);
+        assert!(keys.input(start).held.is_empty());
+    }
+}
@@ -0,0 +1,51 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::constants::{MEMORY_SIZE, XO_MEMORY_SIZE};
+
+/// Вариант машины: определяет размер памяти и набор расширенных инструкций
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Variant {
+    /// CHIP-8 и SUPER-CHIP: 4KB памяти
+    #[default]
+    Classic,
+    /// XO-CHIP: 64KB памяти, битовые плоскости, звуковой буфер
+    XoChip,
+}
+
+impl Variant {
+    pub fn all() -> [Variant; 2] {
+        [Variant::Classic, Variant::XoChip]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Variant::Classic => "classic",
+            Variant::XoChip => "xochip",
+        }
+    }
+
+    pub fn memory_size(&self) -> usize {
+        match self {
+            Variant::Classic => MEMORY_SIZE,
+            Variant::XoChip => XO_MEMORY_SIZE,
+        }
+    }
+}
+
+impl fmt::Display for Variant {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Variant {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Variant::all()
+            .into_iter()
+            .find(|variant| variant.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| format!("unknown machine variant '{}', expected classic or xochip", s))
+    }
+}
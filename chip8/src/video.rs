@@ -0,0 +1,511 @@
+//! Постобработка кадра в программном виде: палитра, послесвечение люминофора,
+//! масштабирование и эффект строк/сетки. Не зависит от окна, поэтому
+//! проверяется в тестах и годится для любого фронтенда.
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::constants::MAX_PLANES;
+use crate::display::Display;
+
+/// Число цветов палитры: по одному на каждую комбинацию плоскостей
+pub const PALETTE_SIZE: usize = 1 << MAX_PLANES;
+
+/// Цвета 0RGB по маске плоскостей пикселя: 0 - фон, 1 - первая плоскость,
+/// 2 - вторая, 3 - обе и так далее
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Palette {
+    pub colors: [u32; PALETTE_SIZE],
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        PalettePreset::Classic.palette()
+    }
+}
+
+impl Palette {
+    /// Палитра из первых цветов списка; недостающие комбинации плоскостей
+    /// берут последний цвет, так что "фон,цвет" работает для любых режимов
+    pub fn from_colors(colors: &[u32]) -> Option<Self> {
+        let last = *colors.last()?;
+        if colors.len() > PALETTE_SIZE {
+            return None;
+        }
+        Some(Palette { colors: std::array::from_fn(|i| colors.get(i).copied().unwrap_or(last)) })
+    }
+
+    /// Кадр экрана в цветах палитры, размер `width() * height()`
+    pub fn render(&self, display: &Display) -> Vec<u32> {
+        let (width, height) = (display.width(), display.height());
+        display.pixels[..height]
+            .iter()
+            .flat_map(|row| row[..width].iter().map(|&pixel| self.colors[pixel as usize]))
+            .collect()
+    }
+}
+
+impl fmt::Display for Palette {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(preset) = PalettePreset::all().into_iter().find(|preset| preset.palette() == *self) {
+            return f.write_str(preset.name());
+        }
+        let colors: Vec<String> = self.colors.iter().map(|color| format!("{:06X}", color)).collect();
+        f.write_str(&colors.join(","))
+    }
+}
+
+impl FromStr for Palette {
+    type Err = String;
+
+    /// Имя готовой палитры или список цветов в hex: `000000,FFFFFF,FF0000,00FF00`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if !s.contains(',') && let Ok(preset) = s.parse::<PalettePreset>() {
+            return Ok(preset.palette());
+        }
+        let colors = s
+            .split(',')
+            .map(|color| {
+                let hex = color.trim().trim_start_matches('#');
+                u32::from_str_radix(hex, 16)
+                    .ok()
+                    .filter(|_| hex.len() == 6)
+                    .ok_or_else(|| format!("bad colour '{}' in palette, expected RRGGBB", color))
+            })
+            .collect::<Result<Vec<u32>, String>>()?;
+        Palette::from_colors(&colors).ok_or_else(|| {
+            let names: Vec<&str> = PalettePreset::all().iter().map(|preset| preset.name()).collect();
+            format!("palette needs 1-{} colours or one of: {}", PALETTE_SIZE, names.join(", "))
+        })
+    }
+}
+
+/// Готовые палитры
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PalettePreset {
+    /// Белое на чёрном, цветные комбинации плоскостей XO-CHIP
+    Classic,
+    /// Янтарный монитор
+    Amber,
+    /// Зелёный люминофор
+    Green,
+    /// Четыре оттенка зелёного LCD
+    Lcd,
+    /// Цвета Octo по умолчанию
+    Octo,
+}
+
+impl PalettePreset {
+    pub fn all() -> [PalettePreset; 5] {
+        [PalettePreset::Classic, PalettePreset::Amber, PalettePreset::Green, PalettePreset::Lcd, PalettePreset::Octo]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            PalettePreset::Classic => "classic",
+            PalettePreset::Amber => "amber",
+            PalettePreset::Green => "green",
+            PalettePreset::Lcd => "lcd",
+            PalettePreset::Octo => "octo",
+        }
+    }
+
+    pub fn palette(&self) -> Palette {
+        let colors: &[u32] = match self {
+            PalettePreset::Classic => &[
+                0x000000, 0xFFFFFF, 0xAAAAAA, 0x555555,
+                0xFF0000, 0x00FF00, 0x0000FF, 0xFFFF00,
+                0x880000, 0x008800, 0x000088, 0x888800,
+                0xFF00FF, 0x00FFFF, 0x880088, 0x008888,
+            ],
+            PalettePreset::Amber => &[0x000000, 0xFFB000, 0x805800, 0xFFD060],
+            PalettePreset::Green => &[0x001000, 0x33FF33, 0x1A801A, 0x99FF99],
+            PalettePreset::Lcd => &[0x9BBC0F, 0x0F380F, 0x8BAC0F, 0x306230],
+            PalettePreset::Octo => &[0x996600, 0xFFCC00, 0xFF6600, 0x662200],
+        };
+        Palette::from_colors(colors).expect("preset palettes are valid")
+    }
+}
+
+impl fmt::Display for PalettePreset {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for PalettePreset {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        PalettePreset::all()
+            .into_iter()
+            .find(|preset| preset.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| format!("unknown palette '{}'", s))
+    }
+}
+
+/// Послесвечение люминофора: погасший пиксель тускнеет за несколько кадров,
+/// поэтому спрайты, которые игра стирает и рисует заново (XOR), не мерцают
+#[derive(Debug, Clone)]
+pub struct Phosphor {
+    // Какая доля яркости остаётся за кадр: 0 - без следа, ближе к 1 - дольше
+    pub decay: f32,
+    // Яркость каналов RGB каждого пикселя в прошлом кадре
+    glow: Vec<[f32; 3]>,
+}
+
+impl Phosphor {
+    pub fn new(decay: f32) -> Self {
+        Phosphor { decay: decay.clamp(0.0, 1.0), glow: Vec::new() }
+    }
+
+    /// Смешать новый кадр с остаточным свечением. При смене размера кадра
+    /// (переключение lo-res/hi-res) свечение сбрасывается
+    pub fn apply(&mut self, frame: &mut [u32]) {
+        if self.glow.len() != frame.len() {
+            self.glow = frame.iter().map(|&color| channels(color)).collect();
+            return;
+        }
+        for (pixel, glow) in frame.iter_mut().zip(&mut self.glow) {
+            let fresh = channels(*pixel);
+            for (channel, &value) in glow.iter_mut().zip(&fresh) {
+                *channel = value.max(*channel * self.decay);
+            }
+            *pixel = glow.iter().fold(0, |color, &channel| (color << 8) | channel.round() as u32);
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.glow.clear();
+    }
+}
+
+fn channels(color: u32) -> [f32; 3] {
+    [(color >> 16) & 0xFF, (color >> 8) & 0xFF, color & 0xFF].map(|channel| channel as f32)
+}
+
+/// Разобрать долю послесвечения 0..1 (для командной строки)
+pub fn parse_decay(s: &str) -> Result<f32, String> {
+    s.parse::<f32>()
+        .ok()
+        .filter(|decay| (0.0..1.0).contains(decay))
+        .ok_or_else(|| format!("bad phosphor decay '{}', expected a number from 0 up to 1", s))
+}
+
+/// Как картинка вписывается в окно
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Scaling {
+    /// Наибольший целый масштаб: все пиксели одинаковые, по краям поля
+    #[default]
+    Integer,
+    /// На всё окно с сохранением пропорций, пиксели могут отличаться на единицу
+    Fit,
+}
+
+impl Scaling {
+    pub fn all() -> [Scaling; 2] {
+        [Scaling::Integer, Scaling::Fit]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Scaling::Integer => "integer",
+            Scaling::Fit => "fit",
+        }
+    }
+}
+
+impl fmt::Display for Scaling {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Scaling {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Scaling::all()
+            .into_iter()
+            .find(|scaling| scaling.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| format!("unknown scaling '{}', expected integer or fit", s))
+    }
+}
+
+/// Эффект поверх увеличенной картинки: затемнённые границы пикселей
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Overlay {
+    #[default]
+    None,
+    /// Тёмная строка под каждым рядом пикселей, как у ЭЛТ
+    Scanlines,
+    /// Тёмные линии между всеми пикселями, как у LCD
+    Grid,
+}
+
+impl Overlay {
+    pub fn all() -> [Overlay; 3] {
+        [Overlay::None, Overlay::Scanlines, Overlay::Grid]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Overlay::None => "none",
+            Overlay::Scanlines => "scanlines",
+            Overlay::Grid => "grid",
+        }
+    }
+}
+
+impl fmt::Display for Overlay {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Overlay {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Overlay::all()
+            .into_iter()
+            .find(|overlay| overlay.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| format!("unknown overlay '{}', expected none, scanlines or grid", s))
+    }
+}
+
+/// Увеличить кадр `width` x `height` до буфера `out_width` x `out_height`
+/// ближайшим соседом; картинка по центру, поля чёрные
+pub fn scale(
+    frame: &[u32],
+    (width, height): (usize, usize),
+    (out_width, out_height): (usize, usize),
+    scaling: Scaling,
+    overlay: Overlay,
+) -> Vec<u32> {
+    let mut out = vec![0; out_width * out_height];
+    if width == 0 || height == 0 {
+        return out;
+    }
+    let (image_width, image_height) = match scaling {
+        Scaling::Integer => {
+            let factor = (out_width / width).min(out_height / height).max(1);
+            (width * factor, height * factor)
+        }
+        Scaling::Fit if out_width * height <= out_height * width => (out_width, out_width * height / width),
+        Scaling::Fit => (out_height * width / height, out_height),
+    };
+    if image_width == 0 || image_height == 0 {
+        return out;
+    }
+    let left = out_width.saturating_sub(image_width) / 2;
+    let top = out_height.saturating_sub(image_height) / 2;
+
+    // Линии рисуем, только если на пиксель приходится хотя бы две строки (столбца)
+    let rows = overlay != Overlay::None && image_height >= height * 2;
+    let columns = overlay == Overlay::Grid && image_width >= width * 2;
+
+    for y in 0..image_height.min(out_height) {
+        let source_y = y * height / image_height;
+        // Последняя строка исходного пикселя
+        let row_edge = rows && (y + 1) * height / image_height != source_y;
+        let source = &frame[source_y * width..(source_y + 1) * width];
+        let target = &mut out[(top + y) * out_width + left..];
+        for (x, pixel) in target.iter_mut().take(image_width.min(out_width)).enumerate() {
+            let source_x = x * width / image_width;
+            let column_edge = columns && (x + 1) * width / image_width != source_x;
+            let color = source[source_x];
+            *pixel = if row_edge || column_edge { (color >> 1) & 0x7F7F7F } else { color };
+        }
+    }
+    out
+}
+
+/// Кадр 0RGB известного размера: для снимков экрана и GIF
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u32>,
+}
+
+impl Image {
+    /// Экран в цветах палитры, пиксель в пиксель
+    pub fn from_display(display: &Display, palette: &Palette) -> Self {
+        Image { width: display.width(), height: display.height(), pixels: palette.render(display) }
+    }
+
+    /// Копия, увеличенная в `factor` раз, с эффектом `overlay`
+    pub fn scaled(&self, factor: usize, overlay: Overlay) -> Image {
+        let factor = factor.max(1);
+        self.resized(self.width * factor, self.height * factor, Scaling::Integer, overlay)
+    }
+
+    /// Копия размером `width` x `height`, картинка вписана по `scaling`
+    pub fn resized(&self, width: usize, height: usize, scaling: Scaling, overlay: Overlay) -> Image {
+        let pixels = scale(&self.pixels, (self.width, self.height), (width, height), scaling, overlay);
+        Image { width, height, pixels }
+    }
+
+    /// Байты RGB подряд, по строкам
+    pub fn rgb(&self) -> Vec<u8> {
+        self.pixels.iter().flat_map(|color| color.to_be_bytes()[1..].to_vec()).collect()
+    }
+}
+
+/// Вся обработка кадра для фронтенда
+#[derive(Debug, Clone, Default)]
+pub struct Video {
+    pub palette: Palette,
+    // Послесвечение, None - выключено
+    pub phosphor: Option<Phosphor>,
+    pub scaling: Scaling,
+    pub overlay: Overlay,
+    // Последний кадр после послесвечения, для снимков
+    last: Option<Image>,
+}
+
+impl Video {
+    /// Кадр в исходном разрешении: палитра и послесвечение.
+    /// С послесвечением вызывать раз в кадр, даже если экран не менялся
+    pub fn frame(&mut self, display: &Display) -> Image {
+        let mut frame = Image::from_display(display, &self.palette);
+        if let Some(phosphor) = &mut self.phosphor {
+            phosphor.apply(&mut frame.pixels);
+            self.last = Some(frame.clone());
+        }
+        frame
+    }
+
+    /// Кадр для окна `width` x `height`
+    pub fn render(&mut self, display: &Display, width: usize, height: usize) -> Vec<u32> {
+        self.frame(display).resized(width, height, self.scaling, self.overlay).pixels
+    }
+
+    /// Снимок того, что видно на экране: палитра, послесвечение и эффект,
+    /// увеличенный в `factor` раз. Состояние послесвечения не меняется
+    pub fn snapshot(&self, display: &Display, factor: usize) -> Image {
+        let frame = match &self.last {
+            Some(last) if last.width == display.width() && last.height == display.height() => last.clone(),
+            _ => Image::from_display(display, &self.palette),
+        };
+        frame.scaled(factor, self.overlay)
+    }
+
+    /// Нужно ли перерисовывать каждый кадр (свечение гаснет само)
+    pub fn is_animated(&self) -> bool {
+        self.phosphor.as_ref().is_some_and(|phosphor| phosphor.decay > 0.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn palettes_parse_and_cover_all_planes() {
+        assert_eq!("amber".parse(), Ok(PalettePreset::Amber.palette()));
+        assert_eq!(PalettePreset::Octo.palette().to_string(), "octo");
+
+        let palette: Palette = "000000,#FF0000,00FF00".parse().unwrap();
+        assert_eq!(palette.colors[1], 0xFF0000);
+        assert_eq!(palette.colors[2], 0x00FF00);
+        assert_eq!(palette.colors[15], 0x00FF00);
+        assert_eq!(palette.to_string().parse(), Ok(palette));
+
+        assert!("sepia".parse::<Palette>().is_err());
+        assert!("FFF,000".parse::<Palette>().is_err());
+        assert!(vec!["000000"; 17].join(",").parse::<Palette>().is_err());
+    }
+
+    #[test]
+    fn renders_planes_in_palette_colours() {
+        let mut display = Display::new();
+        display.pixels[0][0] = 1;
+        display.pixels[0][1] = 2;
+        display.pixels[0][2] = 3;
+        let frame = PalettePreset::Octo.palette().render(&display);
+        assert_eq!(frame.len(), 64 * 32);
+        assert_eq!(frame[..4], [0xFFCC00, 0xFF6600, 0x662200, 0x996600]);
+        // Палитра по умолчанию совпадает с прежним выводом экрана
+        assert_eq!(Palette::default().render(&display), display.to_buffer());
+    }
+
+    #[test]
+    fn phosphor_fades_erased_pixels() {
+        let mut phosphor = Phosphor::new(0.5);
+        let mut frame = vec![0xFFFFFF, 0];
+        phosphor.apply(&mut frame);
+        assert_eq!(frame, [0xFFFFFF, 0]);
+
+        // Спрайт стёрт: остаётся половина яркости, потом четверть
+        let mut frame = vec![0, 0];
+        phosphor.apply(&mut frame);
+        assert_eq!(frame, [0x808080, 0]);
+        let mut frame = vec![0, 0x0000FF];
+        phosphor.apply(&mut frame);
+        assert_eq!(frame, [0x404040, 0x0000FF]);
+
+        // Новый размер кадра начинает заново
+        let mut frame = vec![0; 3];
+        phosphor.apply(&mut frame);
+        assert_eq!(frame, [0; 3]);
+        assert!(parse_decay("1.5").is_err());
+        assert_eq!(parse_decay("0.25"), Ok(0.25));
+    }
+
+    #[test]
+    fn integer_scaling_centres_image() {
+        // 2x1 в окне 8x3: масштаб 3, поля по бокам
+        let out = scale(&[1, 2], (2, 1), (8, 3), Scaling::Integer, Overlay::None);
+        assert_eq!(&out[..8], &[0, 1, 1, 1, 2, 2, 2, 0]);
+        assert_eq!(&out[16..], &[0, 1, 1, 1, 2, 2, 2, 0]);
+
+        // В окне 4x6: масштаб 2, поля сверху и снизу
+        let out = scale(&[1, 2], (2, 1), (4, 6), Scaling::Integer, Overlay::None);
+        assert_eq!(&out[..8], &[0; 8]);
+        assert_eq!(&out[8..16], &[1, 1, 2, 2, 1, 1, 2, 2]);
+        assert_eq!(&out[16..], &[0; 8]);
+
+        // Fit растягивает на всю ширину
+        let out = scale(&[1, 2], (2, 1), (5, 4), Scaling::Fit, Overlay::None);
+        assert_eq!(&out[5..10], &[1, 1, 1, 2, 2]);
+        assert_eq!(out.iter().filter(|&&pixel| pixel != 0).count(), 10);
+    }
+
+    #[test]
+    fn overlays_darken_pixel_edges() {
+        let white = 0xFFFFFF;
+        let out = scale(&[white; 2], (2, 1), (4, 2), Scaling::Integer, Overlay::Scanlines);
+        assert_eq!(out, [white, white, white, white, 0x7F7F7F, 0x7F7F7F, 0x7F7F7F, 0x7F7F7F]);
+
+        let out = scale(&[white; 2], (2, 1), (4, 2), Scaling::Integer, Overlay::Grid);
+        assert_eq!(out, [white, 0x7F7F7F, white, 0x7F7F7F, 0x7F7F7F, 0x7F7F7F, 0x7F7F7F, 0x7F7F7F]);
+
+        // Без увеличения линиям негде поместиться
+        let out = scale(&[white; 2], (2, 1), (2, 1), Scaling::Integer, Overlay::Grid);
+        assert_eq!(out, [white; 2]);
+    }
+
+    #[test]
+    fn video_pipeline() {
+        let mut display = Display::new();
+        display.pixels[0][0] = 1;
+        let mut video = Video { phosphor: Some(Phosphor::new(0.5)), ..Video::default() };
+        assert!(video.is_animated());
+        let out = video.render(&display, 128, 64);
+        assert_eq!(out.len(), 128 * 64);
+        assert_eq!(out[..3], [0xFFFFFF, 0xFFFFFF, 0]);
+
+        display.pixels[0][0] = 0;
+        assert_eq!(video.render(&display, 128, 64)[0], 0x808080);
+
+        // Снимок показывает то же свечение и не гасит его
+        video.overlay = Overlay::Scanlines;
+        let snapshot = video.snapshot(&display, 2);
+        assert_eq!((snapshot.width, snapshot.height), (128, 64));
+        assert_eq!(snapshot.pixels[..2], [0x808080, 0x808080]);
+        assert_eq!(snapshot.pixels[128], 0x404040);
+        assert_eq!(video.snapshot(&display, 1).pixels[0], 0x808080);
+    }
+}
@@ -81,7 +81,7 @@ impl Chip8Backend {
             ast::Expression::Number(n, _) => {
                 // v0 = 10 -> 0x600A (LD V0, 10)
                 let reg = self.parse_register(target)?;
-                self.emit_instruction(0x6000 | ((reg as u16) << 8) | (*n as u16));
+                self.emit_instruction(0x6000 | ((reg as u16) << 8) | *n);
             }
             ast::Expression::Variable(var, _) => {
                 // v0 = v1 -> 0x8010 (LD V0, V1)
@@ -155,7 +155,7 @@ impl Chip8Backend {
         match end {
             ast::Expression::Number(n, _) => {
                 // VE = end
-                self.emit_instruction(0x6000 | ((temp_reg as u16) << 8) | (*n as u16));
+                self.emit_instruction(0x6000 | ((temp_reg as u16) << 8) | *n);
             }
             ast::Expression::Variable(var, _) => {
                 let reg_end = self.parse_register(var)?;
@@ -174,7 +174,7 @@ impl Chip8Backend {
         self.emit_instruction(0x8005 | ((reg_counter as u16) << 8) | ((temp_reg as u16) << 4));
         
         // Если VF == 1 (reg_counter >= end), прыгаем за цикл
-        self.emit_instruction(0x3000 | ((0xF as u16) << 8) | 0x01); // SE VF, 1
+        self.emit_instruction(0x3000 | (0xF << 8) | 0x01); // SE VF, 1
         let exit_jump_placeholder = self.emit_jump_placeholder();
         
         // 4. Тело цикла
@@ -208,12 +208,8 @@ impl Chip8Backend {
             ast::Condition::Greater(left, right) => {
                 self.compile_greater_check(left, right, jump_on_false)?;
             }
-            ast::Condition::Less(_left, _right) => {
-                ()
-            }
-            ast::Condition::KeyPressed(_key_expr) => {
-                ()
-            }
+            ast::Condition::Less(_left, _right) => {}
+            ast::Condition::KeyPressed(_key_expr) => {}
         }
         Ok(())
     }
@@ -231,10 +227,10 @@ impl Chip8Backend {
                 
                 if jump_when_equal {
                     // Прыгаем если Vx == n
-                    self.emit_instruction(0x3000 | ((reg as u16) << 8) | (*n as u16)); // SE Vx, byte
+                    self.emit_instruction(0x3000 | ((reg as u16) << 8) | *n); // SE Vx, byte
                 } else {
                     // Прыгаем если Vx != n  
-                    self.emit_instruction(0x4000 | ((reg as u16) << 8) | (*n as u16)); // SNE Vx, byte
+                    self.emit_instruction(0x4000 | ((reg as u16) << 8) | *n); // SNE Vx, byte
                 }
                 
                 // Условный прыжок (2 байта пропускаются если условие истинно)
@@ -281,17 +277,17 @@ impl Chip8Backend {
                 let temp_reg = 0xE; // Используем VE как временный регистр
                 
                 // VE = n
-                self.emit_instruction(0x6000 | ((temp_reg as u16) << 8) | (*n as u16));
+                self.emit_instruction(0x6000 | ((temp_reg as u16) << 8) | *n);
                 
                 // Vx - VE, устанавливает VF
                 self.emit_instruction(0x8005 | ((reg_left as u16) << 8) | ((temp_reg as u16) << 4));
                 
                 if jump_when_greater {
                     // Прыгаем если Vx > VE (VF == 1)
-                    self.emit_instruction(0x3000 | ((0xF as u16) << 8) | 0x01); // SE VF, 1
+                    self.emit_instruction(0x3000 | (0xF << 8) | 0x01); // SE VF, 1
                 } else {
                     // Прыгаем если Vx <= VE (VF == 0)
-                    self.emit_instruction(0x3000 | ((0xF as u16) << 8) | 0x00); // SE VF, 0
+                    self.emit_instruction(0x3000 | (0xF << 8)); // SE VF, 0
                 }
                 
                 let jump_addr = self.emit_jump_placeholder();
@@ -323,12 +319,12 @@ impl Chip8Backend {
                         // Загружаем левую переменную в целевой регистр
                         self.emit_instruction(0x8000 | ((reg_target as u16) << 8) | ((reg_left as u16) << 4));
                         // Добавляем число
-                        self.emit_instruction(0x7000 | ((reg_target as u16) << 8) | (*n as u16));
+                        self.emit_instruction(0x7000 | ((reg_target as u16) << 8) | *n);
                     }
                     // Случай: v0 = 5 + v1  
                     (ast::Expression::Number(n, _), ast::Expression::Variable(right_var, _)) => {
                         // Загружаем число в целевой регистр
-                        self.emit_instruction(0x6000 | ((reg_target as u16) << 8) | (*n as u16));
+                        self.emit_instruction(0x6000 | ((reg_target as u16) << 8) | *n);
                         // Добавляем переменную
                         let reg_right = self.parse_register(right_var)?;
                         self.emit_instruction(0x8004 | ((reg_target as u16) << 8) | ((reg_right as u16) << 4));
@@ -359,7 +355,7 @@ impl Chip8Backend {
     fn _compile_to_register(&mut self, reg: u8, expr: &ast::Expression) -> Result<(), CompileError> {
         match expr {
             ast::Expression::Number(n, _) => {
-                self.emit_instruction(0x6000 | ((reg as u16) << 8) | (*n as u16));
+                self.emit_instruction(0x6000 | ((reg as u16) << 8) | *n);
             }
             ast::Expression::Variable(var, _) => {
                 let reg_src = self.parse_register(var)?;
@@ -429,12 +425,12 @@ impl Chip8Backend {
     }
 
     fn parse_register(&self, name: &str) -> Result<u8, CompileError> {
-        if name.len() == 2 && name.starts_with('v') {
-            if let Ok(reg) = u8::from_str_radix(&name[1..], 16) {
-                if reg < 16 {
-                    return Ok(reg);
-                }
-            }
+        if name.len() == 2
+            && let Some(digit) = name.strip_prefix('v')
+            && let Ok(reg) = u8::from_str_radix(digit, 16)
+            && reg < 16
+        {
+            return Ok(reg);
         }
         Err(CompileError::UnknownRegister { name: name.to_string() })
     }
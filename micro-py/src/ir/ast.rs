@@ -1,3 +1,6 @@
+// Позиции (span) пока не читаются - они для будущих сообщений об ошибках;
+// конструкции с allow(dead_code) парсер ещё не порождает
+
 use crate::span::Span;
 
 #[derive(Debug, Clone)]
@@ -7,11 +10,13 @@ pub struct Program {
 
 #[derive(Debug, Clone)]
 pub enum Statement {
+    #[allow(dead_code)]
     Pass,
     /// присваивание, например: v0 = 10
     Assign {
         target: String,
         value: Expression,
+        #[allow(dead_code)]
         span: Span,
     },
     /// Рисовать символ, пример: print(x, y, "A")
@@ -19,9 +24,11 @@ pub enum Statement {
         x: Expression,
         y: Expression,
         character: char,
+        #[allow(dead_code)]
         span: Span,
     },
     /// if условие: ... 
+    #[allow(dead_code)]
     If {
         condition: Condition,
         then_branch: Vec<Statement>,
@@ -31,6 +38,7 @@ pub enum Statement {
     While {
         condition: Condition,
         body: Vec<Statement>,
+        #[allow(dead_code)]
         span: Span,
     },
     For {
@@ -38,19 +46,23 @@ pub enum Statement {
         start: Expression,
         end: Expression,
         body: Vec<Statement>,
+        #[allow(dead_code)]
         span: Span,
     },
     /// jump("label")
+    #[allow(dead_code)]
     Jump {
         label: String,
     },
     /// label:
+    #[allow(dead_code)]
     Label {
         name: String,
     },
     /// Очистка экрана, пример: clear()
     ClearScreen,
     /// Задержка в секундах, пример: sleep(5)
+    #[allow(dead_code)]
     Delay {
         frames: Expression,
     },
@@ -59,14 +71,15 @@ pub enum Statement {
 #[derive(Debug, Clone)]
 pub enum Expression {
     /// 10, 0xFF, 0b1010 (decimal, hex, binary)
-    Number(u16, Span),
+    Number(u16, #[allow(dead_code)] Span),
     /// v0, x, y
-    Variable(String, Span),
+    Variable(String, #[allow(dead_code)] Span),
     /// v0 + 5
     BinaryOp {
         left: Box<Expression>,
         op: BinaryOperator,
         right: Box<Expression>,
+        #[allow(dead_code)]
         span: Span,
     },
 }
@@ -82,6 +95,7 @@ pub enum Condition {
     Greater(Expression, Expression),
     Less(Expression, Expression),
     /// key_pressed(1)
+    #[allow(dead_code)]
     KeyPressed(Expression),
 }
 
@@ -89,8 +103,12 @@ pub enum Condition {
 pub enum BinaryOperator {
     Add,      // +
     Subtract, // -
+    #[allow(dead_code)]
     Multiply, // *
+    #[allow(dead_code)]
     Or,       // |
+    #[allow(dead_code)]
     And,      // &
+    #[allow(dead_code)]
     Xor,      // ^
 }
\ No newline at end of file
@@ -1,4 +1,8 @@
 use std::fs;
+use chip8::{asm, disasm};
+use chip8::disasm::Line;
+use chip8::profile::Coverage;
+use chip8::variant::Variant;
 use clap::{Parser, Subcommand};
 
 mod error;
@@ -43,6 +47,34 @@ enum Commands {
         input: String,
     },
     
+    /// Дизассемблировать .ch8 файл
+    Disasm {
+        /// ROM файл
+        input: String,
+
+        /// Вариант машины: classic или xochip (длинный LD I и XO-инструкции)
+        #[arg(long, default_value_t = Variant::Classic)]
+        variant: Variant,
+
+        /// Записать листинг в файл вместо stdout
+        #[arg(short, long)]
+        output: Option<String>,
+
+        /// JSON-отчёт профилировщика (chip8 --profile): показать код, который ни разу не выполнялся
+        #[arg(long)]
+        coverage: Option<String>,
+    },
+
+    /// Собрать CHIP-8 ассемблер в .ch8 и файл символов .sym
+    Asm {
+        /// Исходник на ассемблере
+        input: String,
+
+        /// Собранный файл (по умолчанию input.ch8)
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+
     /// Список поддерживаемых архитектур
     Targets,
 }
@@ -86,12 +118,9 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                     println!("Code size: {} bytes", machine_code.len());
                     
                     // Показываем дизассемблированный код
-                    println!("Disassembly:");
-                    for (i, chunk) in machine_code.chunks(2).enumerate() {
-                        if chunk.len() == 2 {
-                            let instruction = ((chunk[0] as u16) << 8) | (chunk[1] as u16);
-                            println!("  0x{:03X}: {:04X}", 0x200 + i * 2, instruction);
-                        }
+                    if target == "chip8" {
+                        println!("Disassembly:");
+                        print!("{}", disasm::disassemble(&machine_code, Variant::Classic));
                     }
                 }
                 _ => {
@@ -123,6 +152,55 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 }
             }
         }
+        Commands::Disasm { input, variant, output, coverage } => {
+            let rom = fs::read(&input)?;
+            let disassembly = disasm::disassemble(&rom, variant);
+            let listing = disassembly.to_string();
+            match output {
+                Some(path) => {
+                    fs::write(&path, listing)?;
+                    println!("Disassembly written to: {}", path);
+                }
+                None => print!("{}", listing),
+            }
+            if let Some(path) = coverage {
+                let report = fs::read_to_string(&path)?;
+                let coverage = Coverage::from_report(&report).map_err(|e| format!("{}: {}", path, e))?;
+                if coverage.bytes.len() != rom.len() {
+                    eprintln!("Warning: coverage covers {} bytes, ROM has {}", coverage.bytes.len(), rom.len());
+                }
+                // Комментариями, чтобы листинг в stdout по-прежнему собирался
+                let dead: Vec<String> = disassembly
+                    .lines
+                    .iter()
+                    .filter_map(|line| match line {
+                        Line::Instruction { addr, text, .. } if !coverage.is_executed(*addr) => {
+                            Some(format!(";   {:04X}: {}", addr, text))
+                        }
+                        _ => None,
+                    })
+                    .collect();
+                println!("; Never executed: {} of {} instructions", dead.len(), disassembly.code_addresses().count());
+                for line in dead {
+                    println!("{}", line);
+                }
+            }
+        }
+        Commands::Asm { input, output } => {
+            let assembly = asm::assemble_file(&input)?;
+
+            let output_path = output.unwrap_or_else(|| {
+                let base_name = input.trim_end_matches(".asm").trim_end_matches(".s");
+                format!("{}.ch8", base_name)
+            });
+            let symbols_path = format!("{}.sym", output_path.trim_end_matches(".ch8"));
+
+            fs::write(&output_path, &assembly.bytes)?;
+            fs::write(&symbols_path, assembly.symbol_file())?;
+            println!("Assembled to: {}", output_path);
+            println!("Symbols: {} ({} labels)", symbols_path, assembly.labels.len());
+            println!("Code size: {} bytes", assembly.bytes.len());
+        }
         Commands::Targets => {
             println!("Supported targets:");
             for backend in BackendType::all() {
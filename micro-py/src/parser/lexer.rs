@@ -46,7 +46,10 @@ pub enum TokenKind {
     RParen,        // )
     Colon,         // :
     Comma,         // ,
+    // Кавычки и отступы зарезервированы под будущий синтаксис
+    #[allow(dead_code)]
     Qoute,         // "
+    #[allow(dead_code)]
     SQoute,        // '
     // Идентификаторы и литералы
     Identifier(String),
@@ -55,7 +58,9 @@ pub enum TokenKind {
     StringLiteral(String),
     // Специальные
     Newline,
+    #[allow(dead_code)]
     Indent,
+    #[allow(dead_code)]
     Dedent,
     Eof,
 }
@@ -112,7 +117,7 @@ impl<'a> Lexer<'a> {
         
         while let Some(&ch) = self.peek_char() {
             match ch {
-                '0'..='9' | 'a'..='f' | 'A'..='F' | 'x' | 'X' | 'b' | 'B' | '_' => {
+                '0'..='9' | 'a'..='f' | 'A'..='F' | 'x' | 'X' | '_' => {
                     num_str.push(ch);
                     self.next_char();
                 }
@@ -123,14 +128,14 @@ impl<'a> Lexer<'a> {
         // Пропускаем подчеркивания (1_000_000)
         let clean_num = num_str.replace('_', "");
         
-        let value = if clean_num.starts_with("0x") {
-            u16::from_str_radix(&clean_num[2..], 16).map_err(|_| LexerError::InvalidNumber {
+        let value = if let Some(hex) = clean_num.strip_prefix("0x") {
+            u16::from_str_radix(hex, 16).map_err(|_| LexerError::InvalidNumber {
                 number: clean_num.clone(),
                 line,
                 column,
             })?
-        } else if clean_num.starts_with("0b") {
-            u16::from_str_radix(&clean_num[2..], 2).map_err(|_| LexerError::InvalidNumber {
+        } else if let Some(bin) = clean_num.strip_prefix("0b") {
+            u16::from_str_radix(bin, 2).map_err(|_| LexerError::InvalidNumber {
                 number: clean_num.clone(),
                 line,
                 column,
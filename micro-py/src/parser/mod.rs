@@ -1,4 +1,5 @@
 pub mod lexer;
+#[allow(clippy::module_inception)]
 pub mod parser;
 
 use crate::error::CompileError;
@@ -406,10 +406,10 @@ impl Parser {
                         break;
                     }
                     TokenKind::Newline => {
-                        if let Some(next_token) = self.lookahead(1) {
-                            if matches!(next_token.kind, TokenKind::While | TokenKind::If | TokenKind::Def | TokenKind::For) {
-                                break;
-                            }
+                        if let Some(next_token) = self.lookahead(1)
+                            && matches!(next_token.kind, TokenKind::While | TokenKind::If | TokenKind::Def | TokenKind::For)
+                        {
+                            break;
                         }
                         self.advance();
                         continue;